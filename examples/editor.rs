@@ -89,6 +89,18 @@ fn setup(mut commands: Commands) {
             ),
         ]),
     ));
+
+    // Editable text in world space
+    commands.spawn(
+        TextEditor2dBundle::from_section(
+            "World space",
+            TextStyle {
+                font_size: 40.0,
+                ..default()
+            },
+        )
+        .with_transform(Transform::from_xyz(0.0, -200.0, 0.0)),
+    );
 }
 
 // fn animate_cursor(mut query: Query<&mut CursorConfig>, time: Res<Time>) {
@@ -1,37 +1,237 @@
 pub mod prelude {
     pub use crate::plugin::*;
+    /// The cosmic-text types used by the editor's public API, so that they can be used without
+    /// importing `bevy::text::cosmic_text`
+    pub use bevy::text::cosmic_text::{
+        Affinity, Buffer, Cursor, LayoutRun, Motion, Scroll, Selection,
+    };
 }
 
 mod plugin {
 
     use std::cmp;
-    use std::collections::{HashMap, VecDeque};
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::marker::PhantomData;
+    use std::sync::Arc;
     use std::time::{Duration, Instant};
 
+    #[cfg(feature = "accesskit")]
+    use bevy::a11y::accesskit::{
+        Action as AccessAction, ActionData, NodeBuilder, NodeId, Role, TextPosition, TextSelection,
+    };
+    #[cfg(feature = "accesskit")]
+    use bevy::a11y::{AccessibilityNode, AccessibilitySystem, ActionRequest};
     use bevy::ecs::system::SystemParam;
     use bevy::input::keyboard::{Key, KeyboardInput};
     use bevy::input::ButtonState;
     use bevy::prelude::*;
+    use bevy::render::camera::RenderTarget;
+    #[cfg(feature = "render")]
     use bevy::render::{Extract, ExtractSchedule, RenderApp};
+    use bevy::sprite::{Anchor, SpriteSource};
+    #[cfg(feature = "render")]
+    use bevy::sprite::{ExtractedSprite, ExtractedSprites, SpriteSystem};
     use bevy::text::cosmic_text::{
-        Action, Attrs, AttrsList, Buffer, Cursor, Edit, Editor, LayoutRun, Motion, Selection,
+        Action, Align, Attrs, AttrsList, Buffer, BufferLine, Change, Cursor, Edit, Editor,
+        FontSystem, LayoutRun, Motion, Scroll, Selection, Wrap,
     };
-    use bevy::text::{BreakLineOn, CosmicBuffer, TextLayoutInfo};
-    use bevy::ui::widget::TextFlags;
-    use bevy::ui::{
-        ContentSize, ExtractedUiNode, ExtractedUiNodes, FocusPolicy, NodeType, RenderUiSystem,
+    #[cfg(feature = "render")]
+    use bevy::text::PositionedGlyph;
+    use bevy::text::{
+        BreakLineOn, CosmicBuffer, FontAtlasSets, Text2dBounds, TextError, TextLayoutInfo,
+        TextPipeline, YAxisOrientation,
     };
+    use bevy::ui::widget::TextFlags;
+    use bevy::ui::{ContentSize, FocusPolicy, UiSystem};
+    #[cfg(feature = "render")]
+    use bevy::ui::{ExtractedUiNode, ExtractedUiNodes, NodeType, RenderUiSystem};
     use bevy::window::PrimaryWindow;
     use unicode_segmentation::UnicodeSegmentation as _;
 
     pub struct TextEditorPlugin;
 
+    /// The systems of the [`TextEditorPlugin`], for ordering other systems around them
+    #[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum TextEditorSet {
+        /// Pointer input: clicking, right-clicking, dragging and scrollbars, in [`PreUpdate`]
+        Hit,
+        /// Keyboard input to the [`Focused`] editor, in [`PreUpdate`] after [`TextEditorSet::Hit`]
+        Input,
+        /// Extracting the selections, cursors, placeholders and scrollbars of editors, in
+        /// [`ExtractSchedule`]
+        Extract,
+    }
+
     impl Plugin for TextEditorPlugin {
         fn build(&self, app: &mut App) {
             app.add_systems(
                 PreUpdate,
-                (hit.pipe(handle_click), listen_keyboard_input_events),
+                (
+                    (
+                        track_touch_pointer,
+                        update_pointer_hit,
+                        (handle_scrollbar, handle_span_hover, update_cursor_icon),
+                        (
+                            handle_click,
+                            handle_right_click,
+                            handle_middle_click,
+                            handle_span_click,
+                            handle_drag,
+                            handle_text_drag,
+                        )
+                            .run_if(editing_enabled),
+                    )
+                        .chain()
+                        .in_set(TextEditorSet::Hit),
+                    (
+                        traverse_focus.before(listen_keyboard_input_events),
+                        listen_keyboard_input_events,
+                        gamepad_navigation,
+                    )
+                        .in_set(TextEditorSet::Input),
+                ),
+            )
+            .add_systems(PreUpdate, send_focus_events.after(TextEditorSet::Input))
+            .add_systems(
+                PreUpdate,
+                forward_edits_to_spans.after(TextEditorSet::Input),
+            )
+            .configure_sets(
+                PreUpdate,
+                TextEditorSet::Input
+                    .after(TextEditorSet::Hit)
+                    .run_if(editing_enabled),
+            )
+            .add_systems(Update, blink_cursor)
+            .add_systems(
+                PostUpdate,
+                (
+                    layout_placeholder.after(UiSystem::Layout),
+                    auto_height.before(UiSystem::Layout),
+                    compose_span_sections
+                        .before(bevy::ui::widget::text_system)
+                        .before(bevy::text::update_text2d_layout),
+                    update_wrap
+                        .before(bevy::ui::widget::text_system)
+                        .before(bevy::text::update_text2d_layout),
+                    update_tab_width
+                        .before(bevy::ui::widget::text_system)
+                        .before(bevy::text::update_text2d_layout),
+                    apply_wrap_width
+                        .after(bevy::ui::widget::text_system)
+                        .before(clamp_editor_state)
+                        .before(scroll_to_cursor)
+                        .before(mask_text),
+                    grow_to_fit_content
+                        .after(apply_wrap_width)
+                        .after(bevy::ui::widget::text_system),
+                    clamp_editor_state
+                        .after(bevy::ui::widget::text_system)
+                        .after(bevy::text::update_text2d_layout),
+                    scroll_to_cursor
+                        .after(clamp_editor_state)
+                        .before(restore_scroll),
+                    restore_scroll.after(clamp_editor_state),
+                    offset_scrolled_glyphs
+                        .after(restore_scroll)
+                        .after(mask_text),
+                    mask_text
+                        .after(bevy::ui::widget::text_system)
+                        .after(bevy::text::update_text2d_layout),
+                    layout_line_numbers.after(restore_scroll),
+                    match_brackets
+                        .after(clamp_editor_state)
+                        .before(compute_editor_visuals),
+                    compute_editor_visuals
+                        .after(restore_scroll)
+                        .before(update_cursor_visuals),
+                    update_cursor_visuals,
+                ),
+            )
+            .init_resource::<ClickConfig>()
+            .init_resource::<ClickHistory>()
+            .init_resource::<DragScrollConfig>()
+            .init_resource::<TouchPointer>()
+            .init_resource::<PointerHit>()
+            .init_resource::<ScrollbarDrag>()
+            .init_resource::<TextDrag>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<KillRing>()
+            .init_resource::<RichClipboard>()
+            .init_resource::<EditorClipboard>()
+            .init_resource::<EnablePrimarySelection>()
+            .init_resource::<EditingEnabled>()
+            .register_type::<Focused>()
+            .register_type::<WordBoundaryConfig>()
+            .register_type::<Scrollbar>()
+            .register_type::<GamepadEditing>()
+            .register_type::<UndoHistory>()
+            .register_type::<EditorState>()
+            .register_type::<EditorSpan>()
+            .register_type::<WrapWidth>()
+            .register_type::<GrowToFitContent>()
+            .register_type::<AutoHeight>()
+            .register_type::<CursorConfig>()
+            .register_type::<ComputedEditorVisuals>()
+            .register_type::<CursorVisual>()
+            .register_type::<CursorBlink>()
+            .register_type::<TabBehavior>()
+            .register_type::<SmartHome>()
+            .register_type::<TabIndex>()
+            .register_type::<TabWidth>()
+            .register_type::<LineEnding>()
+            .register_type::<EditorFontConfig>()
+            .register_type::<MaxLength>()
+            .register_type::<TextMask>()
+            .register_type::<ReadOnly>()
+            .register_type::<ShiftEnterNewline>()
+            .register_type::<ClipboardMode>()
+            .register_type::<Placeholder>()
+            .register_type::<FindHighlights>()
+            .register_type::<SelectionConfig>()
+            .register_type::<EmptyLineSelection>()
+            .register_type::<SelectionLayers>()
+            .register_type::<BracketMatch>()
+            .register_type::<EditorCapabilities>()
+            .register_type::<InteractiveSpans>()
+            .register_type::<LineNumbers>()
+            .add_event::<TextChanged>()
+            .add_event::<ContextMenuRequested>()
+            .add_event::<VirtualKeyboardRequested>()
+            .add_event::<Submit>()
+            .add_event::<InputRejected>()
+            .add_event::<FocusGained>()
+            .add_event::<Blurred>()
+            .add_event::<SpanClicked>()
+            .add_event::<SpanHovered>()
+            .add_event::<SpanUnhovered>();
+
+            // the rest of the editor works without rendering, e.g. when headless
+            #[cfg(feature = "render")]
+            Self::build_render(app);
+            #[cfg(feature = "accesskit")]
+            Self::build_accessibility(app);
+        }
+    }
+
+    impl TextEditorPlugin {
+        #[cfg(feature = "accesskit")]
+        fn build_accessibility(app: &mut App) {
+            app.add_systems(
+                PreUpdate,
+                handle_accessibility_actions.in_set(TextEditorSet::Input),
+            )
+            .add_systems(
+                PostUpdate,
+                update_accessibility_nodes
+                    .after(bevy::ui::widget::text_system)
+                    .after(bevy::text::update_text2d_layout)
+                    .before(AccessibilitySystem::Update),
             );
+        }
+
+        #[cfg(feature = "render")]
+        fn build_render(app: &mut App) {
             let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
                 return;
             };
@@ -39,8 +239,12 @@ mod plugin {
                 ExtractSchedule,
                 (
                     extract_selection.before(RenderUiSystem::ExtractText),
-                    extract_cursor.after(RenderUiSystem::ExtractText),
-                ),
+                    extract_placeholder.after(RenderUiSystem::ExtractText),
+                    extract_line_numbers.after(RenderUiSystem::ExtractText),
+                    extract_scrollbar.after(RenderUiSystem::ExtractText),
+                    (extract_selection_2d, extract_cursor_2d).after(SpriteSystem::ExtractSprites),
+                )
+                    .in_set(TextEditorSet::Extract),
             );
         }
     }
@@ -89,7 +293,21 @@ mod plugin {
         pub background_color: BackgroundColor,
         pub editor_state: EditorState,
         pub cursor_config: CursorConfig,
+        pub cursor_blink: CursorBlink,
         pub selection_config: SelectionConfig,
+        pub tab_behavior: TabBehavior,
+        pub tab_width: TabWidth,
+        pub line_ending: LineEnding,
+        pub placeholder: Placeholder,
+        pub max_length: MaxLength,
+        pub mask: TextMask,
+        pub read_only: ReadOnly,
+        pub undo_history: UndoHistory,
+        pub clipboard_mode: ClipboardMode,
+        pub smart_home: SmartHome,
+        pub capabilities: EditorCapabilities,
+        pub shift_enter_newline: ShiftEnterNewline,
+        pub computed_visuals: ComputedEditorVisuals,
     }
 
     impl TextEditorBundle {
@@ -137,6 +355,61 @@ mod plugin {
             self.text.linebreak_behavior = BreakLineOn::NoWrap;
             self
         }
+
+        /// Returns this [`TextEditorBundle`] with a [`Placeholder`] in the default style.
+        pub fn with_placeholder(mut self, value: impl Into<String>) -> Self {
+            self.placeholder = Placeholder::new(value);
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] with tab stops every `tab_width` spaces.
+        pub const fn with_tab_width(mut self, tab_width: u32) -> Self {
+            self.tab_width = TabWidth(tab_width);
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] limited to `max_length` characters.
+        pub const fn with_max_length(mut self, max_length: usize) -> Self {
+            self.max_length = MaxLength(Some(max_length));
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] with every character drawn as `mask`.
+        pub const fn with_mask(mut self, mask: char) -> Self {
+            self.mask = TextMask(Some(mask));
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] as read-only.
+        pub const fn read_only(mut self) -> Self {
+            self.read_only = ReadOnly(true);
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] copying and pasting the styles of the text, within the app.
+        pub const fn with_rich_clipboard(mut self) -> Self {
+            self.clipboard_mode = ClipboardMode::Rich;
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] with Home moving to the first non-whitespace character first.
+        pub const fn with_smart_home(mut self) -> Self {
+            self.smart_home = SmartHome(true);
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] allowing only the given editing actions.
+        pub const fn with_capabilities(mut self, capabilities: EditorCapabilities) -> Self {
+            self.capabilities = capabilities;
+            self
+        }
+
+        /// Returns this [`TextEditorBundle`] inserting a line break on Shift+Enter, even without
+        /// [`EditorCapabilities::NEWLINE`].
+        pub const fn with_shift_enter_newline(mut self) -> Self {
+            self.shift_enter_newline = ShiftEnterNewline(true);
+            self
+        }
     }
 
     impl<I> From<I> for TextEditorBundle
@@ -148,15 +421,232 @@ mod plugin {
         }
     }
 
+    /// A 2d (world space) text entity that is editable, to be viewed with a 2d camera
+    ///
+    /// This is the editable counterpart to [`Text2dBundle`]: the position is controlled manually
+    /// with the [`Transform`] rather than by the UI layout system.
+    #[derive(Bundle, Debug, Default)]
+    pub struct TextEditor2dBundle {
+        /// Contains the text
+        pub text: Text,
+        /// How the text is positioned relative to its transform
+        pub text_anchor: Anchor,
+        /// The maximum width and height of the text
+        pub text_2d_bounds: Text2dBounds,
+        /// Cached cosmic buffer for layout
+        pub buffer: CosmicBuffer,
+        /// The transform of the text
+        pub transform: Transform,
+        /// The global transform of the text
+        pub global_transform: GlobalTransform,
+        /// The visibility properties of the text
+        pub visibility: Visibility,
+        /// Inherited visibility of an entity
+        pub inherited_visibility: InheritedVisibility,
+        /// Algorithmically-computed indication of whether an entity is visible and should be extracted for rendering
+        pub view_visibility: ViewVisibility,
+        /// Text layout information
+        pub text_layout_info: TextLayoutInfo,
+        /// Marks that this is a [`SpriteSource`], needed for visibility computation
+        pub sprite_source: SpriteSource,
+        pub editor_state: EditorState,
+        pub cursor_config: CursorConfig,
+        pub cursor_blink: CursorBlink,
+        pub selection_config: SelectionConfig,
+        pub tab_behavior: TabBehavior,
+        pub tab_width: TabWidth,
+        pub line_ending: LineEnding,
+        pub max_length: MaxLength,
+        pub mask: TextMask,
+        pub read_only: ReadOnly,
+        pub undo_history: UndoHistory,
+        pub clipboard_mode: ClipboardMode,
+        pub smart_home: SmartHome,
+        pub capabilities: EditorCapabilities,
+        pub shift_enter_newline: ShiftEnterNewline,
+        pub computed_visuals: ComputedEditorVisuals,
+    }
+
+    impl TextEditor2dBundle {
+        /// Create a [`TextEditor2dBundle`] from a single section.
+        ///
+        /// See [`Text::from_section`] for usage.
+        pub fn from_section(value: impl Into<String>, style: TextStyle) -> Self {
+            Self {
+                text: Text::from_section(value, style),
+                ..Default::default()
+            }
+        }
+
+        /// Create a [`TextEditor2dBundle`] from a list of sections.
+        ///
+        /// See [`Text::from_sections`] for usage.
+        pub fn from_sections(sections: impl IntoIterator<Item = TextSection>) -> Self {
+            Self {
+                text: Text::from_sections(sections),
+                ..Default::default()
+            }
+        }
+
+        /// Returns this [`TextEditor2dBundle`] with a new [`Transform`].
+        pub const fn with_transform(mut self, transform: Transform) -> Self {
+            self.transform = transform;
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] with a new [`Anchor`].
+        pub const fn with_anchor(mut self, anchor: Anchor) -> Self {
+            self.text_anchor = anchor;
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] with new [`Text2dBounds`].
+        pub const fn with_bounds(mut self, bounds: Text2dBounds) -> Self {
+            self.text_2d_bounds = bounds;
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] with tab stops every `tab_width` spaces.
+        pub const fn with_tab_width(mut self, tab_width: u32) -> Self {
+            self.tab_width = TabWidth(tab_width);
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] limited to `max_length` characters.
+        pub const fn with_max_length(mut self, max_length: usize) -> Self {
+            self.max_length = MaxLength(Some(max_length));
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] with every character drawn as `mask`.
+        pub const fn with_mask(mut self, mask: char) -> Self {
+            self.mask = TextMask(Some(mask));
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] as read-only.
+        pub const fn read_only(mut self) -> Self {
+            self.read_only = ReadOnly(true);
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] copying and pasting the styles of the text, within the app.
+        pub const fn with_rich_clipboard(mut self) -> Self {
+            self.clipboard_mode = ClipboardMode::Rich;
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] with Home moving to the first non-whitespace character first.
+        pub const fn with_smart_home(mut self) -> Self {
+            self.smart_home = SmartHome(true);
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] allowing only the given editing actions.
+        pub const fn with_capabilities(mut self, capabilities: EditorCapabilities) -> Self {
+            self.capabilities = capabilities;
+            self
+        }
+
+        /// Returns this [`TextEditor2dBundle`] inserting a line break on Shift+Enter, even without
+        /// [`EditorCapabilities::NEWLINE`].
+        pub const fn with_shift_enter_newline(mut self) -> Self {
+            self.shift_enter_newline = ShiftEnterNewline(true);
+            self
+        }
+    }
+
+    /// How clicks are combined into double/triple-clicks
+    #[derive(Resource, Clone, Copy, Debug)]
+    pub struct ClickConfig {
+        /// How far apart consecutive clicks can be, in the buffer's coordinates
+        pub max_distance: f32,
+        /// How long between consecutive clicks
+        pub max_interval: Duration,
+        /// How many consecutive clicks are remembered
+        pub max_clicks: usize,
+        /// What a triple-click selects
+        pub triple_click_selects: TripleClickSelects,
+        /// How far apart consecutive taps can be, as taps are less precise than clicks
+        pub max_tap_distance: f32,
+    }
+
+    /// What a triple-click selects
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum TripleClickSelects {
+        /// The line as it's laid out, which is only part of the logical line when it's soft-wrapped
+        VisualLine,
+        /// The whole line, up to its line ending
+        #[default]
+        LogicalLine,
+    }
+
+    impl Default for ClickConfig {
+        fn default() -> Self {
+            Self {
+                max_distance: 2.0,
+                max_interval: Duration::from_millis(500),
+                max_clicks: 4,
+                triple_click_selects: TripleClickSelects::default(),
+                max_tap_distance: 20.0,
+            }
+        }
+    }
+
+    /// How dragging a selection near the top or bottom of an editor scrolls it
+    #[derive(Resource, Clone, Copy, Debug)]
+    pub struct DragScrollConfig {
+        /// How close the pointer needs to be to the top or bottom of the editor to scroll, in the
+        /// buffer's coordinates
+        pub edge: f32,
+        /// How fast the editor scrolls when the pointer is at (or past) the edge, in the buffer's
+        /// coordinates per second
+        pub max_speed: f32,
+    }
+
+    impl Default for DragScrollConfig {
+        fn default() -> Self {
+            Self {
+                edge: 24.0,
+                max_speed: 800.0,
+            }
+        }
+    }
+
+    impl DragScrollConfig {
+        /// The speed to scroll at with the pointer at `y` in an editor of `height`, negative for
+        /// scrolling up
+        ///
+        /// This is proportional to how close the pointer is to the edge.
+        pub fn speed(&self, y: f32, height: f32) -> f32 {
+            if self.edge <= 0.0 {
+                return 0.0;
+            }
+            let proximity = if y < self.edge {
+                -(self.edge - y)
+            } else if y > height - self.edge {
+                y - (height - self.edge)
+            } else {
+                return 0.0;
+            };
+            self.max_speed * (proximity / self.edge).clamp(-1.0, 1.0)
+        }
+    }
+
     #[derive(Debug)]
     pub struct ClickHistoryEntry {
         pub position: Vec2,
         pub time: Instant,
     }
 
-    #[derive(Debug)]
+    #[derive(Resource, Debug)]
     pub struct ClickHistory {
         pub history: VecDeque<ClickHistoryEntry>,
+        /// How many consecutive clicks the last click completed, e.g. 2 for a double-click
+        pub level: usize,
+        /// The selection made by the last click, which dragging after a multi-click extends by
+        /// words, lines or paragraphs
+        pub selected: Option<(Cursor, Cursor)>,
     }
 
     impl Default for ClickHistory {
@@ -166,19 +656,17 @@ mod plugin {
     }
 
     impl ClickHistory {
-        const MAX_ENTRIES: usize = 4;
-        const MAX_DISTANCE: f32 = 2.0;
-        const MAX_INTERVAL: Duration = Duration::from_millis(500);
-
         pub fn new() -> Self {
             Self {
-                history: VecDeque::with_capacity(Self::MAX_ENTRIES),
+                history: VecDeque::new(),
+                level: 0,
+                selected: None,
             }
         }
 
-        pub fn add_entry(&mut self, position: Vec2) {
+        pub fn add_entry(&mut self, position: Vec2, config: &ClickConfig) {
             // drop down to the most recent entries, with room for one more
-            while self.history.len() >= Self::MAX_ENTRIES {
+            while !self.history.is_empty() && self.history.len() >= config.max_clicks {
                 self.history.pop_back();
             }
             // add the new entry
@@ -188,7 +676,7 @@ mod plugin {
             });
         }
 
-        pub fn clicked(&self, times: usize) -> bool {
+        pub fn clicked(&self, times: usize, config: &ClickConfig) -> bool {
             let len = self.history.len();
             if len < times {
                 return false;
@@ -197,10 +685,10 @@ mod plugin {
             while let Some(a) = iter.next() {
                 if let Some(b) = iter.peek() {
                     debug_assert!(a.time > b.time);
-                    if a.position.distance(b.position) > Self::MAX_DISTANCE {
+                    if a.position.distance(b.position) > config.max_distance {
                         return false;
                     }
-                    if a.time - b.time > Self::MAX_INTERVAL {
+                    if a.time - b.time > config.max_interval {
                         return false;
                     }
                 }
@@ -209,20 +697,318 @@ mod plugin {
         }
     }
 
-    /// Piped from [`hit`]
+    /// The offset of `cursor` in the text of `buffer` in characters, with lines joined by `\n`
+    #[cfg(feature = "accesskit")]
+    fn char_offset(buffer: &Buffer, cursor: Cursor) -> usize {
+        buffer.lines[..cursor.line]
+            .iter()
+            .map(|line| line.text().chars().count() + 1)
+            .sum::<usize>()
+            + buffer.lines.get(cursor.line).map_or(0, |line| {
+                line.text()[..cursor.index.min(line.text().len())]
+                    .chars()
+                    .count()
+            })
+    }
+
+    /// The cursor at `offset` characters into the text of `buffer` (see [`char_offset`]),
+    /// clamped to the end of the buffer
+    #[cfg(feature = "accesskit")]
+    fn cursor_at_char(buffer: &Buffer, mut offset: usize) -> Cursor {
+        for (line_index, line) in buffer.lines.iter().enumerate() {
+            let text = line.text();
+            let chars = text.chars().count();
+            if offset <= chars {
+                let index = text
+                    .char_indices()
+                    .nth(offset)
+                    .map_or(text.len(), |(index, _)| index);
+                return Cursor::new(line_index, index);
+            }
+            offset -= chars + 1;
+        }
+        buffer_end(buffer)
+    }
+
+    /// Describes each editor to assistive technology, e.g. screen readers, through an
+    /// [`AccessibilityNode`]
+    ///
+    /// The node is a text input with the editor's text as its value, and its selection (or caret,
+    /// when nothing is selected) as character offsets into that value. Masked editors are password
+    /// inputs, with the masked text as their value. Also moves the accessibility
+    /// [`Focus`](bevy::a11y::Focus) to newly [`Focused`] editors.
+    #[cfg(feature = "accesskit")]
+    #[allow(clippy::type_complexity)]
+    pub fn update_accessibility_nodes(
+        mut commands: Commands,
+        editors: Query<
+            (
+                Entity,
+                &CosmicBuffer,
+                &EditorState,
+                Option<&ReadOnly>,
+                Option<&TextMask>,
+                Option<&Placeholder>,
+                Option<&EditorCapabilities>,
+            ),
+            Or<(Changed<Text>, Changed<EditorState>)>,
+        >,
+        focused: Query<Entity, Added<Focused>>,
+        focus: Option<ResMut<bevy::a11y::Focus>>,
+    ) {
+        for (entity, buffer, editor_state, read_only, mask, placeholder, capabilities) in &editors {
+            let mask = mask.and_then(|mask| mask.0);
+            let multiline = capabilities
+                .copied()
+                .unwrap_or_default()
+                .contains(EditorCapabilities::NEWLINE);
+            let role = match (mask, multiline) {
+                (Some(_), _) => Role::PasswordInput,
+                (None, true) => Role::MultilineTextInput,
+                (None, false) => Role::TextInput,
+            };
+            let mut node = NodeBuilder::new(role);
+
+            let value = buffer
+                .lines
+                .iter()
+                .map(|line| line.text())
+                .collect::<Vec<_>>()
+                .join("\n");
+            node.set_value(match mask {
+                Some(mask) => value
+                    .chars()
+                    .map(|c| if c == '\n' { c } else { mask })
+                    .collect(),
+                None => value,
+            });
+            if let Some(placeholder) =
+                placeholder.filter(|placeholder| !placeholder.value.is_empty())
+            {
+                node.set_placeholder(placeholder.value.clone());
+            }
+            if read_only.is_some_and(|read_only| read_only.0) {
+                node.set_read_only();
+            } else {
+                node.add_action(AccessAction::SetValue);
+            }
+            node.add_action(AccessAction::Focus);
+            node.add_action(AccessAction::SetTextSelection);
+
+            if let Some(cursor) = editor_state.cursor {
+                let anchor = match editor_state.selection {
+                    Selection::None => cursor,
+                    Selection::Normal(select)
+                    | Selection::Line(select)
+                    | Selection::Word(select) => select,
+                };
+                let position = |cursor: Cursor| TextPosition {
+                    node: NodeId(entity.to_bits()),
+                    character_index: char_offset(buffer, cursor),
+                };
+                node.set_text_selection(TextSelection {
+                    anchor: position(anchor),
+                    focus: position(cursor),
+                });
+            }
+            commands.entity(entity).insert(AccessibilityNode(node));
+        }
+
+        if let (Some(mut focus), Some(entity)) = (focus, focused.iter().last()) {
+            focus.0 = Some(entity);
+        }
+    }
+
+    /// Applies the actions requested by assistive technology to editors: focusing them, setting
+    /// their text (see [`EditorState::set_text`]) and setting their selection
+    #[cfg(feature = "accesskit")]
+    #[allow(clippy::type_complexity)]
+    pub fn handle_accessibility_actions(
+        mut requests: EventReader<ActionRequest>,
+        mut commands: Commands,
+        mut editors: Query<(
+            Entity,
+            &mut CosmicBuffer,
+            &mut Text,
+            &mut EditorState,
+            Has<Focused>,
+            Option<&ReadOnly>,
+        )>,
+        mut text_pipeline: ResMut<bevy::text::TextPipeline>,
+        mut text_changed: EventWriter<TextChanged>,
+    ) {
+        for request in requests.read() {
+            let Ok(target) = Entity::try_from_bits(request.target.0) else {
+                continue;
+            };
+            match (request.action, &request.data) {
+                (AccessAction::Focus, _) => {
+                    if !editors.contains(target) {
+                        continue;
+                    }
+                    // like clicking, this clears the cursor and selection of the focused editor
+                    for (entity, _, _, mut editor_state, focused, _) in &mut editors {
+                        if entity == target {
+                            if !focused {
+                                commands.entity(entity).insert(Focused);
+                            }
+                        } else if focused {
+                            commands.entity(entity).remove::<Focused>();
+                            *editor_state = EditorState::default();
+                        }
+                    }
+                }
+                (AccessAction::SetValue, Some(ActionData::Value(value))) => {
+                    let Ok((entity, mut buf, mut text, mut editor_state, _, read_only)) =
+                        editors.get_mut(target)
+                    else {
+                        continue;
+                    };
+                    if read_only.is_some_and(|read_only| read_only.0) {
+                        continue;
+                    }
+                    editor_state.set_text(&mut buf, &mut text, &mut text_pipeline, value);
+                    text_changed.send(TextChanged { entity });
+                }
+                (AccessAction::SetTextSelection, Some(ActionData::SetTextSelection(selection))) => {
+                    let Ok((_, mut buf, _, mut editor_state, _, _)) = editors.get_mut(target)
+                    else {
+                        continue;
+                    };
+                    let anchor = cursor_at_char(&buf, selection.anchor.character_index);
+                    let focus = cursor_at_char(&buf, selection.focus.character_index);
+                    editor_state.resume(&mut buf).with_editor_mut(|editor| {
+                        editor.set_cursor(focus);
+                        editor.set_selection(if anchor == focus {
+                            Selection::None
+                        } else {
+                            Selection::Normal(anchor)
+                        });
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Marks the editor that receives keyboard input
+    ///
+    /// Focus is moved to an editor when it is clicked, and cleared when clicking outside of all
+    /// editors.
+    ///
+    /// Check whether an editor is focused with `Has<Focused>`, and react to focus moving with the
+    /// [`FocusGained`] and [`Blurred`] events.
+    #[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct Focused;
+
+    /// Sent when an editor gains [`Focused`]
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct FocusGained {
+        pub entity: Entity,
+    }
+
+    /// Sent when an editor loses [`Focused`], e.g. to validate it once the user leaves it
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct Blurred {
+        pub entity: Entity,
+    }
+
+    /// Whether editors take input, e.g. turned off while a modal is open so that typing doesn't
+    /// reach the editors behind it
+    ///
+    /// While disabled, [`TextEditorSet::Input`] and clicking and dragging in editors don't run
+    /// (see [`editing_enabled`]). Scrollbars still work. Focus is kept, so the focused editor takes
+    /// input again as soon as editing is enabled.
+    #[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct EditingEnabled(pub bool);
+
+    impl Default for EditingEnabled {
+        fn default() -> Self {
+            Self(true)
+        }
+    }
+
+    /// Run condition of the input systems, true unless [`EditingEnabled`] is off
+    pub fn editing_enabled(enabled: Res<EditingEnabled>) -> bool {
+        enabled.0
+    }
+
+    /// Sends [`Blurred`] and [`FocusGained`] when [`Focused`] moves between editors, however it
+    /// was moved
+    pub fn send_focus_events(
+        focused: Query<Entity, Added<Focused>>,
+        mut unfocused: RemovedComponents<Focused>,
+        mut focus_gained: EventWriter<FocusGained>,
+        mut blurred: EventWriter<Blurred>,
+    ) {
+        // the old editor is blurred before the new one is focused
+        for entity in unfocused.read() {
+            // unless it was focused again since
+            if !focused.contains(entity) {
+                blurred.send(Blurred { entity });
+            }
+        }
+        for entity in &focused {
+            focus_gained.send(FocusGained { entity });
+        }
+    }
+
+    /// Reads the [`PointerHit`]
     ///
-    /// TODO: This should respect UI stack indexes / Z ordering
+    /// Also moves [`Focused`] to the clicked editor, clearing the cursor and selection of the
+    /// previously focused editor. Clicking outside of all editors clears focus.
     #[allow(clippy::type_complexity)]
     pub fn handle_click(
-        In(hit): In<Option<HitOutput>>,
-        mut click_history: Local<ClickHistory>,
+        pointer_hit: Res<PointerHit>,
+        mut commands: Commands,
+        mut click_history: ResMut<ClickHistory>,
+        click_config: Res<ClickConfig>,
         mouse_button: Res<ButtonInput<MouseButton>>,
-        mut buffer: Query<(&mut CosmicBuffer, &mut EditorState), With<Text>>,
+        touch_pointer: Res<TouchPointer>,
+        scrollbar_drag: Res<ScrollbarDrag>,
+        mut text_drag: ResMut<TextDrag>,
+        keys: Res<ButtonInput<KeyCode>>,
+        mut buffer: Query<
+            (
+                Entity,
+                &mut CosmicBuffer,
+                &mut EditorState,
+                Has<Focused>,
+                Option<&WordBoundaryConfig>,
+            ),
+            With<Text>,
+        >,
         mut text_pipeline: ResMut<bevy::text::TextPipeline>,
     ) {
-        if !mouse_button.just_pressed(MouseButton::Left) {
+        let hit = pointer_hit.0;
+        let tapped = touch_pointer.just_pressed();
+        if !mouse_button.just_pressed(MouseButton::Left) && !tapped
+            || scrollbar_drag.entity.is_some()
+        {
             return;
         }
+        let click_config = if tapped {
+            ClickConfig {
+                max_distance: click_config.max_tap_distance,
+                ..*click_config
+            }
+        } else {
+            *click_config
+        };
+
+        let clicked = hit.as_ref().map(|hit| hit.entity);
+        for (entity, _, mut editor_state, focused, _) in &mut buffer {
+            if Some(entity) == clicked {
+                if !focused {
+                    commands.entity(entity).insert(Focused);
+                }
+            } else if focused {
+                commands.entity(entity).remove::<Focused>();
+                *editor_state = EditorState::default();
+            }
+        }
+
         let Some(HitOutput {
             entity: parent,
             span_index: _,
@@ -231,33 +1017,104 @@ mod plugin {
         else {
             return;
         };
-        click_history.add_entry(position);
+        click_history.add_entry(position, &click_config);
+        click_history.level = (1..=4)
+            .rev()
+            .find(|times| click_history.clicked(*times, &click_config))
+            .unwrap_or(1);
 
-        let Ok((mut buf, mut editor_state)) = buffer.get_mut(parent) else {
+        let Ok((_, mut buf, mut editor_state, focused, word_boundary_config)) =
+            buffer.get_mut(parent)
+        else {
             return;
         };
+        // Ctrl+click adds the cursor of the focused editor to its secondary cursors
+        let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        let mut secondary_cursors = std::mem::take(&mut editor_state.secondary_cursors);
+        if ctrl && focused && click_history.level == 1 && editor_state.selection == Selection::None
+        {
+            secondary_cursors.extend(editor_state.cursor);
+        } else {
+            secondary_cursors.clear();
+        }
+        let mut drag_from = None;
         editor_state.resume(&mut buf).with_editor_mut(|editor| {
             let font_system = text_pipeline.font_system_mut();
-            if click_history.clicked(3) {
-                info!("triple-click: {click_history:?}");
+            if click_history.clicked(4, &click_config) {
                 editor.action(
                     font_system,
-                    Action::TripleClick {
+                    Action::Click {
                         x: position.x as i32,
                         y: position.y as i32,
                     },
                 );
-            } else if click_history.clicked(2) {
-                info!("double-click: {click_history:?}");
+                let line = editor.cursor().line;
+                let (start, end) = editor.with_buffer(|buffer| paragraph_bounds(buffer, line));
+                editor.set_selection(Selection::Normal(start));
+                editor.set_cursor(end);
+            } else if click_history.clicked(3, &click_config) {
+                info!("triple-click: {click_history:?}");
                 editor.action(
                     font_system,
-                    Action::DoubleClick {
+                    Action::Click {
                         x: position.x as i32,
                         y: position.y as i32,
                     },
                 );
-            } else if click_history.clicked(1) {
+                let cursor = editor.cursor();
+                let (start, end) =
+                    editor.with_buffer(|buffer| match click_config.triple_click_selects {
+                        TripleClickSelects::VisualLine => visual_line_bounds(buffer, cursor),
+                        TripleClickSelects::LogicalLine => logical_line_bounds(buffer, cursor.line),
+                    });
+                editor.set_selection(Selection::Normal(start));
+                editor.set_cursor(end);
+            } else if click_history.clicked(2, &click_config) {
+                info!("double-click: {click_history:?}");
+                match word_boundary_config {
+                    Some(config) => {
+                        editor.action(
+                            font_system,
+                            Action::Click {
+                                x: position.x as i32,
+                                y: position.y as i32,
+                            },
+                        );
+                        let cursor = editor.cursor();
+                        let word = editor.with_buffer(|buffer| {
+                            buffer
+                                .lines
+                                .get(cursor.line)
+                                .map(|line| config.word_at(line.text(), cursor.index))
+                        });
+                        if let Some(word) = word {
+                            editor.set_selection(Selection::Normal(Cursor::new(
+                                cursor.line,
+                                word.start,
+                            )));
+                            editor.set_cursor(Cursor::new(cursor.line, word.end));
+                        }
+                    }
+                    None => editor.action(
+                        font_system,
+                        Action::DoubleClick {
+                            x: position.x as i32,
+                            y: position.y as i32,
+                        },
+                    ),
+                }
+            } else if click_history.clicked(1, &click_config) {
                 info!("single-click: {click_history:?}");
+                // pressing on the selection of the focused editor starts dragging it
+                let pressed = editor.with_buffer(|buffer| buffer.hit(position.x, position.y));
+                if let (true, Some(pressed), Some((start, end))) =
+                    (focused, pressed, editor.selection_bounds())
+                {
+                    if cursor_in_range(pressed, start, end) {
+                        drag_from = Some(pressed);
+                        return;
+                    }
+                }
                 editor.action(
                     font_system,
                     Action::Click {
@@ -269,680 +1126,8176 @@ mod plugin {
                 unreachable!("clicked but zero clicks?");
             }
         });
+        // clicking on a secondary cursor doesn't add it twice
+        let primary = editor_state.cursor;
+        secondary_cursors.retain(|cursor| Some(*cursor) != primary);
+        secondary_cursors.sort_by_key(|cursor| (cursor.line, cursor.index));
+        secondary_cursors.dedup();
+        editor_state.secondary_cursors = secondary_cursors;
+        click_history.selected = editor_state.selection_bounds;
+        if let Some(pressed_at) = drag_from {
+            *text_drag = TextDrag {
+                entity: Some(parent),
+                pressed_at,
+                moved: false,
+            };
+        }
     }
 
-    pub fn listen_keyboard_input_events(
-        mut events: EventReader<KeyboardInput>,
-        mut buffer: Query<(&mut CosmicBuffer, &mut Text, &mut EditorState)>,
-        mut text_pipeline: ResMut<bevy::text::TextPipeline>,
-        mut scratch_spans_for_deletion: Local<Vec<usize>>,
-        mut scratch_spans_for_update: Local<HashMap<usize, String>>,
-    ) {
-        for event in events.read() {
-            // Only trigger changes when the key is first pressed.
-            if event.state == ButtonState::Released {
-                continue;
-            }
-
-            for (mut buf, mut text, mut editor_state) in &mut buffer {
-                // temporary hack:
-                // see https://github.com/pop-os/cosmic-text/issues/290
-                // for new-lines (\n), sets the metadata of the line's default attrs to that new-line's span index
+    /// Which characters double-click treats as part of a word
+    ///
+    /// Without this component, double-click uses cosmic-text's word selection. With it, the word
+    /// under the cursor is found from the unicode word boundaries, joining neighbouring words and
+    /// runs of [`WordBoundaryConfig::word_chars`], so e.g. `kebab-case` can be selected as one word.
+    #[derive(Component, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct WordBoundaryConfig {
+        /// Characters that are part of a word, in addition to letters and numbers
+        pub word_chars: String,
+    }
 
-                let mut line_no = 0;
-                let mut map_from_line_to_span_index = HashMap::new();
-                for (span_idx, span) in text.sections.iter().enumerate() {
-                    // find all newlines
-                    for c in span.value.chars() {
-                        if c == '\n' {
-                            map_from_line_to_span_index.insert(line_no, span_idx);
-                            line_no += 1;
-                        }
-                    }
-                }
+    impl WordBoundaryConfig {
+        pub fn new(word_chars: impl Into<String>) -> Self {
+            Self {
+                word_chars: word_chars.into(),
+            }
+        }
 
-                for (line_no, line) in buf.lines.iter_mut().enumerate() {
-                    let attrs_list = line.attrs_list();
-                    let attrs = Attrs {
-                        metadata: map_from_line_to_span_index.get(&line_no).copied().unwrap(),
-                        ..Attrs::new()
-                    };
-                    let mut attrs_list_new = AttrsList::new(attrs);
-                    for (range, attrs) in attrs_list.spans() {
-                        attrs_list_new.add_span(range.clone(), attrs.as_attrs());
-                    }
-                    line.set_attrs_list(attrs_list_new);
-                }
+        /// Treats `_` and `-` as word characters, for identifiers in code
+        pub fn code() -> Self {
+            Self::new("_-")
+        }
 
-                // /temporary hack
+        fn is_word(&self, segment: &str) -> bool {
+            segment.chars().any(char::is_alphanumeric)
+                || segment.chars().all(|c| self.word_chars.contains(c))
+        }
 
-                editor_state.resume(&mut buf).with_editor_mut(|editor| {
-                    let font_system = text_pipeline.font_system_mut();
-                    // info!("Before: {:?}", editor.cursor());
-                    match &event.logical_key {
-                        Key::Character(character) => {
-                            for c in character.chars() {
-                                editor.action(font_system, Action::Insert(c));
-                            }
-                        }
-                        Key::Enter => editor.action(font_system, Action::Enter),
-                        Key::Space => editor.action(font_system, Action::Insert(' ')),
-                        Key::Backspace => editor.action(font_system, Action::Backspace),
-                        Key::Delete => editor.action(font_system, Action::Delete),
-                        Key::Control => {
-                            info!("TODO: Control");
-                        }
-                        Key::Shift => {
-                            info!("TODO: Shift");
-                        }
-                        Key::Tab => {
-                            info!("TODO: Tab");
-                        }
-                        Key::ArrowDown => editor.action(font_system, Action::Motion(Motion::Down)),
-                        Key::ArrowLeft => editor.action(font_system, Action::Motion(Motion::Left)),
-                        Key::ArrowRight => {
-                            editor.action(font_system, Action::Motion(Motion::Right))
-                        }
-                        Key::ArrowUp => editor.action(font_system, Action::Motion(Motion::Up)),
-                        Key::End => editor.action(font_system, Action::Motion(Motion::End)),
-                        Key::Home => editor.action(font_system, Action::Motion(Motion::Home)),
-                        Key::PageDown => {
-                            editor.action(font_system, Action::Motion(Motion::PageDown))
-                        }
-                        Key::PageUp => editor.action(font_system, Action::Motion(Motion::PageUp)),
-                        _ => {}
-                    }
-                });
+        /// The byte range of the word in `text` at byte `index`
+        ///
+        /// If `index` isn't in a word (e.g. it's in whitespace), the range of that segment is
+        /// returned instead.
+        pub fn word_at(&self, text: &str, index: usize) -> std::ops::Range<usize> {
+            let segments: Vec<_> = text
+                .split_word_bound_indices()
+                .map(|(start, segment)| start..start + segment.len())
+                .collect();
+            let Some(hit) = segments
+                .iter()
+                .position(|range| index < range.end)
+                .or(segments.len().checked_sub(1))
+            else {
+                return index..index;
+            };
+            if !self.is_word(&text[segments[hit].clone()]) {
+                return segments[hit].clone();
+            }
 
-                // rebuild the text from scratch (writeback)
-                // this still isn't quite right
+            let is_word = |i: &usize| self.is_word(&text[segments[*i].clone()]);
+            let first = (0..hit).rev().take_while(is_word).last().unwrap_or(hit);
+            let last = (hit + 1..segments.len())
+                .take_while(is_word)
+                .last()
+                .unwrap_or(hit);
+            segments[first].start..segments[last].end
+        }
+    }
 
-                // dbg!(buf.lines.len());
-                // dbg!(buf
-                //     .lines
-                //     .iter()
-                //     .map(|l| l.text().to_owned() + l.ending().as_str())
-                //     .collect::<String>());
+    /// The sections of an editor that can be clicked like links, by their index in
+    /// [`Text::sections`]
+    ///
+    /// Clicking one sends [`SpanClicked`]. The indices aren't updated as the text is edited.
+    #[derive(Component, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct InteractiveSpans(pub HashSet<usize>);
 
-                let mut bevy_span_index = 0;
-                for line in &buf.lines {
-                    let line_text = dbg!(line.text());
-                    let len = line_text.len();
-                    let ending = line.ending().as_str();
-                    let spans = line.attrs_list().spans();
-                    let default_attrs = line.attrs_list().defaults();
-                    // NOTE: cosmic-text allows for "unstyled" (default-styled) spans/ranges
-                    //       this means not all `spans` actually have styles
-                    //       so imagine a line with 21 characters (full range 0..21)
-                    //       the `spans` iterator can yield for example 2..7, 9..12, 12..13, 13..16, 17..19
-                    //       so 0..2, 7..9, 16..17, 19..21 are unstyled, and we have to specially handle these
-                    //       in this case, we will style
-                    //       0..2 like 2..7 (unstyled span will be styled like next styled span)
-                    //       7..9 like 9..12 (unstyled span will be styled like next styled span)
-                    //       16..17 like 17..19 (unstyled span will be styled like next styled span)
-                    //       19..21 like 17..19 (final part of line, unstyled span will be styled like previous styled span)
-                    let mut current_pos = 0;
-                    if spans.is_empty() {
-                        let s = scratch_spans_for_update
-                            .entry(default_attrs.metadata) // from the hack above
-                            .or_default();
-                        // push the line ending
-                        s.push_str(ending);
-                    } else {
-                        for (range, attrs) in spans.into_iter() {
-                            bevy_span_index = attrs.metadata;
-                            let s = scratch_spans_for_update.entry(bevy_span_index).or_default();
-                            // "unstyled" spans will take the following range's attrs
-                            if current_pos < range.start {
-                                s.push_str(&line_text[current_pos..range.start]);
-                            }
-                            // push the styled span
-                            s.push_str(&line_text[range.clone()]);
-                            current_pos = range.end;
-                            // push the line ending if we've reached the end of the line
-                            if current_pos == len {
-                                s.push_str(ending);
-                            }
-                        }
-                    }
-                    // final part of the line
-                    if current_pos < len {
-                        let s = scratch_spans_for_update.entry(bevy_span_index).or_default();
-                        // push the styled span
-                        s.push_str(&line_text[current_pos..len]);
-                        // push the line ending since we've reached the end of the line
-                        s.push_str(ending);
-                    }
-                }
+    /// Sent when one of the [`InteractiveSpans`] of an editor is clicked
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct SpanClicked {
+        pub entity: Entity,
+        pub span_index: usize,
+    }
 
-                dbg!(&scratch_spans_for_update);
+    /// Reads the [`PointerHit`]
+    ///
+    /// Sends [`SpanClicked`] when the pointer is pressed and released on one of the
+    /// [`InteractiveSpans`] without moving further than [`ClickConfig::max_distance`], so that
+    /// selecting by dragging doesn't click.
+    pub fn handle_span_click(
+        pointer_hit: Res<PointerHit>,
+        mut pressed: Local<Option<HitOutput>>,
+        click_config: Res<ClickConfig>,
+        mouse_button: Res<ButtonInput<MouseButton>>,
+        touch_pointer: Res<TouchPointer>,
+        spans: Query<&InteractiveSpans>,
+        mut span_clicked: EventWriter<SpanClicked>,
+    ) {
+        let hit = pointer_hit.0;
+        if mouse_button.just_pressed(MouseButton::Left) || touch_pointer.just_pressed() {
+            *pressed = hit.filter(|hit| {
+                spans
+                    .get(hit.entity)
+                    .is_ok_and(|spans| spans.0.contains(&hit.span_index))
+            });
+        } else if !mouse_button.pressed(MouseButton::Left) && !touch_pointer.pressed() {
+            if let Some(HitOutput {
+                entity, span_index, ..
+            }) = pressed.take()
+            {
+                span_clicked.send(SpanClicked { entity, span_index });
+            }
+        } else if let Some(press) = *pressed {
+            // moving away from where it was pressed makes it a drag
+            let still = hit.is_some_and(|hit| {
+                hit.entity == press.entity
+                    && hit.position.distance(press.position) <= click_config.max_distance
+            });
+            if !still {
+                *pressed = None;
+            }
+        }
+    }
 
-                // it may just be easier and less error-prone to reconstruct the entire text component
+    /// Sent when the pointer moves onto a section of an editor, e.g. to show a tooltip for it
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct SpanHovered {
+        pub entity: Entity,
+        /// The index of the section in [`Text::sections`]
+        pub span_index: usize,
+        /// Where the pointer is, in the buffer's coordinates
+        pub position: Vec2,
+    }
 
-                *text = Text::from_sections({
-                    let mut spans: Vec<(usize, TextSection)> = scratch_spans_for_update
-                        .drain()
-                        .map(|(i, s)| (i, TextSection::new(s, text.sections[i].style.clone())))
-                        .collect();
-                    spans.sort_by_key(|(i, _)| *i);
-                    spans.into_iter().map(|(_, s)| s).collect::<Vec<_>>()
-                });
+    /// Sent when the pointer moves off a section of an editor that it hovered, see
+    /// [`SpanHovered`]
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct SpanUnhovered {
+        pub entity: Entity,
+        pub span_index: usize,
+    }
 
-                dbg!(text);
+    /// Reads the [`PointerHit`]
+    ///
+    /// Sends [`SpanHovered`] and [`SpanUnhovered`] when the section under the pointer changes.
+    /// Like clicks, only the topmost editor is hovered. The section is the one the text nearest
+    /// the pointer is in, as for placing the cursor.
+    pub fn handle_span_hover(
+        pointer_hit: Res<PointerHit>,
+        mut hovered: Local<Option<(Entity, usize)>>,
+        mut span_hovered: EventWriter<SpanHovered>,
+        mut span_unhovered: EventWriter<SpanUnhovered>,
+    ) {
+        let hit = pointer_hit.0;
+        let now_hovered = hit.map(|hit| (hit.entity, hit.span_index));
+        if *hovered == now_hovered {
+            return;
+        }
+        if let Some((entity, span_index)) = hovered.take() {
+            span_unhovered.send(SpanUnhovered { entity, span_index });
+        }
+        if let Some(hit) = hit {
+            span_hovered.send(SpanHovered {
+                entity: hit.entity,
+                span_index: hit.span_index,
+                position: hit.position,
+            });
+        }
+        *hovered = now_hovered;
+    }
 
-                // // apply the changes (well, everything) to the text component
-                // for i in 0..text.sections.len() {
-                //     match scratch_spans_for_update.remove(&i) {
-                //         // TODO: should be forwarded to the TextSpan component for child spans instead
-                //         // TODO: could be more efficient (don't update the whole string if no changes were made)
-                //         Some(s) => text.sections[i].value = s,
-                //         None => scratch_spans_for_deletion.push(i),
-                //     }
-                // }
-                // scratch_spans_for_deletion.reverse();
-                // for i in scratch_spans_for_deletion.drain(..) {
-                //     if text.sections.len() > 1 {
-                //         text.sections.remove(i);
-                //     } else {
-                //         text.sections[0].value = String::new();
-                //     }
-                //     // text.sections[i].value = " ".to_string();
-                // }
+    /// Reads the [`PointerHit`]
+    ///
+    /// Shows the text (I-beam) cursor icon in the primary window while the pointer is over an
+    /// editor whose text can be selected, including read-only editors, and restores the previous
+    /// icon when it leaves. Editors without [`EditorCapabilities::SELECT`], or any editor while
+    /// [`EditingEnabled`] is off, keep the previous icon.
+    pub fn update_cursor_icon(
+        pointer_hit: Res<PointerHit>,
+        mut previous_icon: Local<Option<CursorIcon>>,
+        editing_enabled: Res<EditingEnabled>,
+        editors: Query<Option<&EditorCapabilities>, With<EditorState>>,
+        mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    ) {
+        let hit = pointer_hit.0;
+        let over_editor = editing_enabled.0
+            && hit.is_some_and(|hit| {
+                editors.get(hit.entity).is_ok_and(|capabilities| {
+                    capabilities
+                        .copied()
+                        .unwrap_or_default()
+                        .contains(EditorCapabilities::SELECT)
+                })
+            });
+        let Ok(mut window) = windows.get_single_mut() else {
+            return;
+        };
+        if over_editor {
+            // moving from one editor to another keeps the icon from before the first
+            if previous_icon.is_none() {
+                *previous_icon = Some(window.cursor.icon);
+                window.cursor.icon = CursorIcon::Text;
             }
+        } else if let Some(icon) = previous_icon.take() {
+            window.cursor.icon = icon;
         }
     }
 
-    /// Adapted from `bevy_ui::extract_uinode_text` and `bevy_ui::extract_uinode_background_colors`
+    /// Sent when an editor is right-clicked, so that a context menu can be shown
+    ///
+    /// The crate doesn't draw a menu itself.
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct ContextMenuRequested {
+        pub entity: Entity,
+        /// The index of the section under the cursor
+        pub span_index: usize,
+        /// Where the editor was clicked, in the buffer's coordinates
+        pub position: Vec2,
+        /// Whether the editor has a selection, e.g. to enable "cut" and "copy"
+        pub has_selection: bool,
+    }
+
+    /// Reads the [`PointerHit`]
+    ///
+    /// Sends [`ContextMenuRequested`] when an editor is right-clicked. This leaves the cursor and
+    /// selection as they are.
+    pub fn handle_right_click(
+        pointer_hit: Res<PointerHit>,
+        mouse_button: Res<ButtonInput<MouseButton>>,
+        scrollbar_drag: Res<ScrollbarDrag>,
+        buffer: Query<&EditorState, With<Text>>,
+        mut context_menu_requested: EventWriter<ContextMenuRequested>,
+    ) {
+        let hit = pointer_hit.0;
+        if !mouse_button.just_pressed(MouseButton::Right) || scrollbar_drag.entity.is_some() {
+            return;
+        }
+        let Some(HitOutput {
+            entity,
+            span_index,
+            position,
+        }) = hit
+        else {
+            return;
+        };
+        let Ok(editor_state) = buffer.get(entity) else {
+            return;
+        };
+        context_menu_requested.send(ContextMenuRequested {
+            entity,
+            span_index,
+            position,
+            has_selection: editor_state.selection != Selection::None,
+        });
+    }
+
+    /// Reads the [`PointerHit`]
+    ///
+    /// Pastes the primary selection (see [`Clipboard::get_primary`]) where an editor is
+    /// middle-clicked, if [`EnablePrimarySelection`] is set. This doesn't move focus, so an
+    /// editor that isn't focused keeps its cursor and selection.
     #[allow(clippy::type_complexity)]
-    pub fn extract_cursor(
-        mut commands: Commands,
-        mut extracted_uinodes: ResMut<ExtractedUiNodes>,
-        camera_query: Extract<Query<(Entity, &Camera)>>,
-        default_ui_camera: Extract<DefaultUiCamera>,
-        ui_scale: Extract<Res<UiScale>>,
-        // TODO: the cursor should be its own entity!
-        uinode_query: Extract<
-            Query<
-                (
-                    &Node,
-                    &GlobalTransform,
-                    &ViewVisibility,
-                    Option<&CalculatedClip>,
-                    Option<&TargetCamera>,
-                    Option<&CursorConfig>,
-                    &CosmicBuffer,
-                    &EditorState,
-                ),
-                With<Text>,
-            >,
-        >,
+    pub fn handle_middle_click(
+        pointer_hit: Res<PointerHit>,
+        mouse_button: Res<ButtonInput<MouseButton>>,
+        scrollbar_drag: Res<ScrollbarDrag>,
+        enable_primary_selection: Res<EnablePrimarySelection>,
+        mut clipboard: ResMut<EditorClipboard>,
+        mut buffer: Query<(
+            &mut CosmicBuffer,
+            &mut Text,
+            &mut EditorState,
+            Has<Focused>,
+            Option<&ReadOnly>,
+            Option<&EditorCapabilities>,
+            Option<&MaxLength>,
+            Option<&mut UndoHistory>,
+            Option<&InputFilter>,
+        )>,
+        mut text_pipeline: ResMut<bevy::text::TextPipeline>,
+        mut text_changed: EventWriter<TextChanged>,
     ) {
-        for (
-            uinode,
-            global_transform,
-            view_visibility,
-            clip,
-            camera,
-            cursor_config,
-            buffer,
-            editor_state,
-        ) in &uinode_query
+        let hit = pointer_hit.0;
+        if !enable_primary_selection.0
+            || !mouse_button.just_pressed(MouseButton::Middle)
+            || scrollbar_drag.entity.is_some()
         {
-            let Some(cursor) = editor_state.cursor else {
-                continue;
+            return;
+        }
+        let Some(HitOutput {
+            entity, position, ..
+        }) = hit
+        else {
+            return;
+        };
+        let Ok((
+            mut buf,
+            mut text,
+            mut editor_state,
+            focused,
+            read_only,
+            capabilities,
+            max_length,
+            undo_history,
+            input_filter,
+        )) = buffer.get_mut(entity)
+        else {
+            return;
+        };
+        let capabilities = capabilities.copied().unwrap_or_default();
+        if read_only.is_some_and(|read_only| read_only.0)
+            || !capabilities.contains(EditorCapabilities::PASTE)
+        {
+            return;
+        }
+        let Some(pasted) = clipboard.0.get_primary().filter(|text| !text.is_empty()) else {
+            return;
+        };
+        let pasted = LineEnding::normalize(&pasted);
+        let pasted = capabilities.fit_lines(&pasted);
+        let pasted = filter_input(input_filter, &pasted);
+        let max_length = max_length.and_then(|max_length| max_length.0);
+
+        if let Some(mut history) = undo_history {
+            history.record(&text, &editor_state);
+        }
+        let previous = editor_state.clone();
+        let font_system = text_pipeline.font_system_mut();
+        editor_state.resume(&mut buf).with_editor_mut(|editor| {
+            editor.action(
+                font_system,
+                Action::Click {
+                    x: position.x as i32,
+                    y: position.y as i32,
+                },
+            );
+            let pasted = fit_max_length(editor, max_length, &pasted);
+            let cursor = editor.cursor();
+            let offset = editor.with_buffer(|buffer| text_offset(buffer, cursor));
+            replace_in_sections(&mut text.sections, offset..offset, pasted);
+            insert_lines(editor, font_system, pasted, None);
+        });
+        if !focused {
+            *editor_state = EditorState {
+                scroll: editor_state.scroll,
+                ..previous
             };
+        }
+        text_changed.send(TextChanged { entity });
+    }
 
-            let Some(camera_entity) = camera.map(TargetCamera::entity).or(default_ui_camera.get())
+    /// Extends the selection of the [`Focused`] editor while the left mouse button is held
+    ///
+    /// Positions outside of the editor are clamped to the bounds of the buffer, and the editor is
+    /// scrolled while the pointer is near its top or bottom (see [`DragScrollConfig`]). After a
+    /// multi-click, the selection is extended by whole words (double-click), lines (triple-click)
+    /// or paragraphs (quadruple-click).
+    #[allow(clippy::type_complexity)]
+    pub fn handle_drag(
+        mut last_position: Local<Option<Vec2>>,
+        mouse_button: Res<ButtonInput<MouseButton>>,
+        touch_pointer: Res<TouchPointer>,
+        click_history: Res<ClickHistory>,
+        click_config: Res<ClickConfig>,
+        drag_scroll_config: Res<DragScrollConfig>,
+        scrollbar_drag: Res<ScrollbarDrag>,
+        text_drag: Res<TextDrag>,
+        time: Res<Time>,
+        pointer: PointerParams,
+        mut buffer: Query<
+            (
+                &mut CosmicBuffer,
+                &mut EditorState,
+                &GlobalTransform,
+                Option<&Anchor>,
+                Option<&TargetCamera>,
+                Option<&WordBoundaryConfig>,
+            ),
+            (With<Focused>, With<Text>),
+        >,
+        mut text_pipeline: ResMut<bevy::text::TextPipeline>,
+    ) {
+        // the initial press is handled by `handle_click`
+        let pressed = mouse_button.pressed(MouseButton::Left) || touch_pointer.pressed();
+        let just_pressed =
+            mouse_button.just_pressed(MouseButton::Left) || touch_pointer.just_pressed();
+        if !pressed || just_pressed || scrollbar_drag.entity.is_some() || text_drag.entity.is_some()
+        {
+            *last_position = None;
+            return;
+        }
+
+        for (mut buf, mut editor_state, transform, anchor, target_camera, word_boundary_config) in
+            &mut buffer
+        {
+            let Some((position, size)) =
+                pointer.buffer_position(&buf, transform, anchor, target_camera)
             else {
                 continue;
             };
+            // scroll while the pointer is near or past the top or bottom of the editor
+            let speed = drag_scroll_config.speed(position.y, size.y);
+            let scroll = scroll_vertically(
+                &buf,
+                editor_state.scroll,
+                speed * time.delta_seconds(),
+                size.y,
+            );
+            let scrolled = scroll != editor_state.scroll;
+            if scrolled {
+                editor_state.scroll = scroll;
+                buf.set_scroll(scroll);
+                buf.shape_until_scroll(text_pipeline.font_system_mut(), false);
+            }
 
-            // Skip if not visible or if size is set to zero (e.g. when a parent is set to `Display::None`)
-            if !view_visibility.get() || uinode.size().x == 0. || uinode.size().y == 0. {
+            let position = position.clamp(Vec2::ZERO, size) + Vec2::X * buf.scroll().horizontal;
+            if !scrolled && *last_position == Some(position) {
                 continue;
             }
+            *last_position = Some(position);
 
-            let scale_factor = camera_query
-                .get(camera_entity)
-                .ok()
-                .and_then(|(_, c)| c.target_scaling_factor())
-                .unwrap_or(1.0)
-                * ui_scale.0;
-            let inverse_scale_factor = scale_factor.recip();
+            editor_state.resume(&mut buf).with_editor_mut(|editor| {
+                editor.action(
+                    text_pipeline.font_system_mut(),
+                    Action::Drag {
+                        x: position.x as i32,
+                        y: position.y as i32,
+                    },
+                );
 
-            // Align the text to the nearest physical pixel:
-            // * Translate by minus the text node's half-size
-            //      (The transform translates to the center of the node but the text coordinates are relative to the node's top left corner)
-            // * Multiply the logical coordinates by the scale factor to get its position in physical coordinates
-            // * Round the physical position to the nearest physical pixel
-            // * Multiply by the rounded physical position by the inverse scale factor to return to logical coordinates
+                let (level @ 2.., Some((start, end))) =
+                    (click_history.level, click_history.selected)
+                else {
+                    return;
+                };
+                let cursor = editor.cursor();
+                let (unit_start, unit_end) = editor.with_buffer(|buffer| match level {
+                    2 => word_bounds(buffer, cursor, word_boundary_config),
+                    3 => match click_config.triple_click_selects {
+                        TripleClickSelects::VisualLine => visual_line_bounds(buffer, cursor),
+                        TripleClickSelects::LogicalLine => logical_line_bounds(buffer, cursor.line),
+                    },
+                    _ => paragraph_bounds(buffer, cursor.line),
+                });
+                // the multi-clicked unit stays selected, extended to the unit under the pointer
+                if (cursor.line, cursor.index) < (start.line, start.index) {
+                    editor.set_selection(Selection::Normal(end));
+                    editor.set_cursor(unit_start);
+                } else {
+                    editor.set_selection(Selection::Normal(start));
+                    if (unit_end.line, unit_end.index) > (end.line, end.index) {
+                        editor.set_cursor(unit_end);
+                    } else {
+                        editor.set_cursor(end);
+                    }
+                }
+            });
+        }
+    }
 
-            let logical_top_left = -0.5 * uinode.size();
+    /// Whether `cursor` is between `start` (inclusive) and `end` (exclusive)
+    fn cursor_in_range(cursor: Cursor, start: Cursor, end: Cursor) -> bool {
+        (start.line, start.index) <= (cursor.line, cursor.index)
+            && (cursor.line, cursor.index) < (end.line, end.index)
+    }
 
-            let mut transform = global_transform.affine()
-                * bevy::math::Affine3A::from_translation(logical_top_left.extend(0.));
+    /// The selection being dragged to move it, started by pressing on the selection of the
+    /// [`Focused`] editor, see [`handle_text_drag`]
+    #[derive(Resource, Clone, Copy, Debug, Default)]
+    pub struct TextDrag {
+        /// The editor whose selection is dragged
+        pub entity: Option<Entity>,
+        /// Where the selection was pressed
+        pub pressed_at: Cursor,
+        /// Whether the pointer has left where it was pressed
+        pub moved: bool,
+    }
 
-            transform.translation *= scale_factor;
-            transform.translation = transform.translation.round();
-            transform.translation *= inverse_scale_factor;
+    /// Drags the selection of an editor, started by [`handle_click`]
+    ///
+    /// While dragging, the [`EditorState::drop_cursor`] shows where the selection would be dropped.
+    /// Releasing over the editor moves the selected text there, or copies it while Ctrl is held.
+    /// A copy is limited like pasted text, by the [`EditorCapabilities`], [`InputFilter`] and
+    /// [`MaxLength`], sending [`InputRejected`] for what's left out. Releasing outside of the editor
+    /// or on the selection does nothing, and releasing without dragging places the cursor where the
+    /// selection was pressed, like a click.
+    #[allow(clippy::type_complexity)]
+    pub fn handle_text_drag(
+        mut text_drag: ResMut<TextDrag>,
+        mouse_button: Res<ButtonInput<MouseButton>>,
+        touch_pointer: Res<TouchPointer>,
+        keys: Res<ButtonInput<KeyCode>>,
+        pointer: PointerParams,
+        mut buffer: Query<
+            (
+                &mut CosmicBuffer,
+                &mut Text,
+                &mut EditorState,
+                &GlobalTransform,
+                Option<&Anchor>,
+                Option<&TargetCamera>,
+                Option<&ReadOnly>,
+                Option<&EditorCapabilities>,
+                Option<&mut UndoHistory>,
+                Option<&MaxLength>,
+                Option<&InputFilter>,
+            ),
+            With<Focused>,
+        >,
+        mut text_pipeline: ResMut<bevy::text::TextPipeline>,
+        mut text_changed: EventWriter<TextChanged>,
+        mut input_rejected: EventWriter<InputRejected>,
+    ) {
+        let Some(entity) = text_drag.entity else {
+            return;
+        };
+        let Ok((
+            mut buf,
+            mut text,
+            mut editor_state,
+            transform,
+            anchor,
+            target_camera,
+            read_only,
+            capabilities,
+            undo_history,
+            max_length,
+            input_filter,
+        )) = buffer.get_mut(entity)
+        else {
+            *text_drag = TextDrag::default();
+            return;
+        };
+        let Some((start, end)) = editor_state.selection_bounds else {
+            *text_drag = TextDrag::default();
+            editor_state.drop_cursor = None;
+            return;
+        };
 
-            let cursor_config = match cursor_config {
-                Some(c) => *c,
-                None => Default::default(),
-            };
-            let color = cursor_config.color.into();
-            let width = cursor_config.width;
+        // the cursor under the pointer, while it's over the editor
+        let font_system = text_pipeline.font_system_mut();
+        if buf.scroll() != editor_state.scroll {
+            buf.set_scroll(editor_state.scroll);
+            buf.shape_until_scroll(font_system, false);
+        }
+        let hovered = pointer
+            .buffer_position(&buf, transform, anchor, target_camera)
+            .filter(|(position, size)| Rect::from_corners(Vec2::ZERO, *size).contains(*position))
+            .and_then(|(position, _)| {
+                let position = position + Vec2::X * buf.scroll().horizontal;
+                buf.hit(position.x, position.y)
+            });
+        let drop_cursor = hovered.filter(|cursor| !cursor_in_range(*cursor, start, end));
 
-            // TODO: we can locate the exact layout_run by the cursor position
-            for run in buffer.layout_runs() {
-                // TODO: this should happen in the main world so that we do as little work as possible here
-                if let Some((x, y)) = cursor_position(&cursor, &run) {
-                    let position = Vec2::new(x as f32, y as f32 + run.line_height / 2.0);
-                    extracted_uinodes.uinodes.insert(
-                        commands.spawn_empty().id(),
-                        ExtractedUiNode {
-                            stack_index: uinode.stack_index(),
-                            transform: transform
-                                * Mat4::from_translation(
-                                    position.extend(0.) * inverse_scale_factor,
-                                ),
-                            color,
-                            rect: Rect {
-                                min: Vec2::ZERO,
-                                // TODO: size?
-                                max: Vec2::new(width, run.line_height),
-                            },
-                            image: AssetId::default(),
-                            atlas_size: None,
-                            clip: clip.map(|clip| clip.clip),
-                            flip_x: false,
-                            flip_y: false,
-                            camera_entity,
-                            border: [0.; 4],
-                            border_radius: [0.; 4],
-                            node_type: NodeType::Rect,
-                        },
-                    );
-                }
+        if mouse_button.pressed(MouseButton::Left) || touch_pointer.pressed() {
+            if hovered != Some(text_drag.pressed_at) {
+                text_drag.moved = true;
+            }
+            if editor_state.drop_cursor != drop_cursor {
+                editor_state.drop_cursor = drop_cursor;
             }
+            return;
         }
-    }
 
-    /// Adapted from `bevy_ui::extract_uinode_text` and `bevy_ui::extract_uinode_background_colors`
-    #[allow(clippy::type_complexity)]
-    pub fn extract_selection(
-        mut commands: Commands,
-        mut extracted_uinodes: ResMut<ExtractedUiNodes>,
-        camera_query: Extract<Query<(Entity, &Camera)>>,
-        default_ui_camera: Extract<DefaultUiCamera>,
-        ui_scale: Extract<Res<UiScale>>,
-        // TODO: the selection should be its own entity!?
-        uinode_query: Extract<
-            Query<
-                (
-                    &Node,
-                    &GlobalTransform,
-                    &ViewVisibility,
-                    Option<&CalculatedClip>,
-                    Option<&TargetCamera>,
-                    Option<&SelectionConfig>,
-                    &CosmicBuffer,
-                    &EditorState,
-                ),
-                With<Text>,
-            >,
-        >,
+        // released
+        let TextDrag {
+            pressed_at, moved, ..
+        } = std::mem::take(&mut *text_drag);
+        editor_state.drop_cursor = None;
+        if !moved {
+            editor_state.resume(&mut buf).with_editor_mut(|editor| {
+                editor.set_selection(Selection::None);
+                editor.set_cursor(pressed_at);
+            });
+            return;
+        }
+        let Some(target) = drop_cursor else {
+            return;
+        };
+        let copy = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        let capabilities = capabilities.copied().unwrap_or_default();
+        let required = if copy {
+            EditorCapabilities::INSERT
+        } else {
+            EditorCapabilities::INSERT | EditorCapabilities::DELETE
+        };
+        let mut reject = |reason| input_rejected.send(InputRejected { entity, reason });
+        if read_only.is_some_and(|read_only| read_only.0) {
+            reject(RejectReason::ReadOnly);
+            return;
+        }
+        if !capabilities.contains(required) {
+            reject(RejectReason::CapabilityDisabled);
+            return;
+        }
+
+        let (source, target_offset) = (
+            text_offset(&buf, start)..text_offset(&buf, end),
+            text_offset(&buf, target),
+        );
+        let value: String = text
+            .sections
+            .iter()
+            .map(|section| section.value.as_str())
+            .collect();
+        let Some(mut dragged) = value.get(source.clone()).map(str::to_string) else {
+            return;
+        };
+        if copy {
+            // copying adds text, so it's limited like pasted text
+            let normalized = LineEnding::normalize(&dragged);
+            let fitted = capabilities.fit_lines(&normalized);
+            let filtered = filter_input(input_filter, &fitted);
+            if filtered.len() < fitted.len() {
+                reject(RejectReason::InputFilter);
+            }
+            // the dragged text stays, so only the room left next to all of the text can be used
+            let room = max_length
+                .and_then(|max_length| max_length.0)
+                .map_or(usize::MAX, |max_length| {
+                    max_length.saturating_sub(buffer_char_count(&buf))
+                });
+            let len = filtered
+                .char_indices()
+                .nth(room)
+                .map_or(filtered.len(), |(i, _)| i);
+            if len < filtered.len() {
+                reject(RejectReason::MaxLength);
+            }
+            dragged = filtered[..len].to_string();
+            if dragged.is_empty() {
+                return;
+            }
+        }
+
+        if let Some(mut history) = undo_history {
+            history.record(&text, &editor_state);
+        }
+
+        // the later of the two edits is made first, so that the earlier offset stays valid
+        let delete_first = !copy && target_offset <= source.start;
+        if delete_first {
+            replace_in_sections(&mut text.sections, source.clone(), "");
+        }
+        replace_in_sections(&mut text.sections, target_offset..target_offset, &dragged);
+        if !copy && !delete_first {
+            replace_in_sections(&mut text.sections, source.clone(), "");
+        }
+
+        editor_state.resume(&mut buf).with_editor_mut(|editor| {
+            if delete_first {
+                editor.delete_selection();
+            }
+            editor.set_selection(Selection::None);
+            editor.set_cursor(target);
+            insert_lines(editor, font_system, &dragged, None);
+            let mut dropped = (target, editor.cursor());
+            if !copy && !delete_first {
+                editor.set_cursor(start);
+                editor.set_selection(Selection::Normal(end));
+                editor.delete_selection();
+                // the dropped text moved back by the deleted text
+                dropped = editor.with_buffer(|buffer| {
+                    let moved_back = |cursor| {
+                        cursor_at_offset(buffer, text_offset(buffer, cursor) - source.len())
+                    };
+                    (moved_back(dropped.0), moved_back(dropped.1))
+                });
+            }
+            // the dropped text is selected
+            editor.set_selection(Selection::Normal(dropped.0));
+            editor.set_cursor(dropped.1);
+            editor.shape_as_needed(font_system, false);
+        });
+        text_changed.send(TextChanged { entity });
+    }
+
+    /// A vertical scrollbar along the right edge of a UI editor, drawn while its text overflows
+    ///
+    /// Scrollbars are opt-in: insert this component to add one. The thumb is sized and positioned
+    /// from the part of the text that's scrolled into view. Dragging the thumb scrolls the editor,
+    /// and pressing the track elsewhere first moves the thumb to the pointer.
+    #[derive(Component, Clone, Copy, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct Scrollbar {
+        /// The width of the scrollbar, in logical pixels
+        pub width: f32,
+        pub track_color: Color,
+        pub thumb_color: Color,
+    }
+
+    impl Default for Scrollbar {
+        fn default() -> Self {
+            Self {
+                width: 8.0,
+                track_color: Color::srgba(0.0, 0.0, 0.0, 0.1),
+                thumb_color: Color::srgba(0.0, 0.0, 0.0, 0.4),
+            }
+        }
+    }
+
+    impl Scrollbar {
+        /// The track and the thumb of the scrollbar of an editor of `node_size`, relative to the
+        /// top left of the node, in logical pixels
+        ///
+        /// Returns `None` when the text fits in the editor. The buffer is laid out in physical
+        /// pixels, which are converted with `scale_factor`.
+        pub fn rects(
+            &self,
+            buffer: &Buffer,
+            scroll: Scroll,
+            node_size: Vec2,
+            scale_factor: f32,
+        ) -> Option<(Rect, Rect)> {
+            let text_height = lines_height(buffer, 0..buffer.lines.len()) / scale_factor;
+            if text_height <= node_size.y {
+                return None;
+            }
+            let track = Rect::new(node_size.x - self.width, 0.0, node_size.x, node_size.y);
+            // the thumb is kept at least as tall as it's wide, so it can still be grabbed
+            let thumb_height =
+                (node_size.y * node_size.y / text_height).max(self.width.min(node_size.y));
+            let scrolled = scroll_top(buffer, scroll) / scale_factor / (text_height - node_size.y);
+            let thumb_top = scrolled.clamp(0.0, 1.0) * (node_size.y - thumb_height);
+            let thumb = Rect::new(
+                track.min.x,
+                thumb_top,
+                track.max.x,
+                thumb_top + thumb_height,
+            );
+            Some((track, thumb))
+        }
+
+        /// The scroll that moves the top of the thumb to `thumb_top`, in logical pixels from the
+        /// top of the node
+        pub fn scroll_for_thumb(
+            &self,
+            buffer: &Buffer,
+            scroll: Scroll,
+            node_size: Vec2,
+            scale_factor: f32,
+            thumb_top: f32,
+        ) -> Scroll {
+            let Some((track, thumb)) = self.rects(buffer, scroll, node_size, scale_factor) else {
+                return scroll;
+            };
+            let thumb_travel = track.height() - thumb.height();
+            if thumb_travel <= 0.0 {
+                return scroll;
+            }
+            let height = node_size.y * scale_factor;
+            let max_top = lines_height(buffer, 0..buffer.lines.len()) - height;
+            let top = (thumb_top / thumb_travel).clamp(0.0, 1.0) * max_top;
+            let from_top = Scroll {
+                line: 0,
+                vertical: 0.0,
+                ..scroll
+            };
+            scroll_vertically(buffer, from_top, top, height)
+        }
+    }
+
+    /// The editor whose [`Scrollbar`] thumb is being dragged, if any
+    #[derive(Resource, Clone, Copy, Debug, Default)]
+    pub struct ScrollbarDrag {
+        pub entity: Option<Entity>,
+        /// How far below the top of the thumb it's held, in logical pixels
+        pub grab_offset: f32,
+    }
+
+    /// Scrolls an editor while the thumb of its [`Scrollbar`] is dragged
+    ///
+    /// While a thumb is dragged, [`handle_click`], [`handle_right_click`] and [`handle_drag`]
+    /// ignore the pointer, leaving the focus, cursor and selection as they are.
+    #[allow(clippy::type_complexity)]
+    pub fn handle_scrollbar(
+        mouse_button: Res<ButtonInput<MouseButton>>,
+        touch_pointer: Res<TouchPointer>,
+        pointer: PointerParams,
+        mut scrollbar_drag: ResMut<ScrollbarDrag>,
+        mut query: Query<(
+            Entity,
+            &CosmicBuffer,
+            &mut EditorState,
+            &Node,
+            &GlobalTransform,
+            &Scrollbar,
+            Option<&TargetCamera>,
+        )>,
     ) {
-        for (
-            uinode,
-            global_transform,
-            view_visibility,
-            clip,
-            camera,
-            selection_config,
+        let pressed = mouse_button.pressed(MouseButton::Left) || touch_pointer.pressed();
+        let just_pressed =
+            mouse_button.just_pressed(MouseButton::Left) || touch_pointer.just_pressed();
+        if !pressed && !just_pressed {
+            *scrollbar_drag = ScrollbarDrag::default();
+            return;
+        }
+
+        // the pointer relative to the top left of the node, in logical pixels
+        let node_position = |node: &Node, transform: &GlobalTransform, target_camera| {
+            let (window_position, scale_factor) = pointer.ui_cursor_position(target_camera)?;
+            let top_left = transform.translation().truncate() - node.size() / 2.0;
+            Some((window_position - top_left, scale_factor))
+        };
+
+        if just_pressed {
+            *scrollbar_drag = ScrollbarDrag::default();
+            // the topmost scrollbar under the pointer
+            let mut topmost = None;
+            for (entity, buffer, editor_state, node, transform, scrollbar, target_camera) in &query
+            {
+                if topmost.is_some_and(|stack_index| stack_index >= node.stack_index()) {
+                    continue;
+                }
+                let Some((position, scale_factor)) = node_position(node, transform, target_camera)
+                else {
+                    continue;
+                };
+                let Some((track, thumb)) =
+                    scrollbar.rects(buffer, editor_state.scroll, node.size(), scale_factor)
+                else {
+                    continue;
+                };
+                if !track.contains(position) {
+                    continue;
+                }
+                topmost = Some(node.stack_index());
+                let grab_offset = if thumb.contains(position) {
+                    position.y - thumb.min.y
+                } else {
+                    thumb.height() / 2.0
+                };
+                *scrollbar_drag = ScrollbarDrag {
+                    entity: Some(entity),
+                    grab_offset,
+                };
+            }
+        }
+
+        let Some(entity) = scrollbar_drag.entity else {
+            return;
+        };
+        let Ok((_, buffer, mut editor_state, node, transform, scrollbar, target_camera)) =
+            query.get_mut(entity)
+        else {
+            *scrollbar_drag = ScrollbarDrag::default();
+            return;
+        };
+        let Some((position, scale_factor)) = node_position(node, transform, target_camera) else {
+            return;
+        };
+        let scroll = scrollbar.scroll_for_thumb(
             buffer,
-            editor_state,
-        ) in &uinode_query
-        {
-            if editor_state.selection == Selection::None {
+            editor_state.scroll,
+            node.size(),
+            scale_factor,
+            position.y - scrollbar_drag.grab_offset,
+        );
+        if editor_state.scroll != scroll {
+            editor_state.scroll = scroll;
+        }
+    }
+
+    /// Sent when the [`Text`] of an editor is changed by editing
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct TextChanged {
+        pub entity: Entity,
+    }
+
+    /// A section of the text of the editor that this is a child of, as an entity of its own
+    ///
+    /// The [`Text::sections`] of an editor with `EditorSpan` children are composed from them, in
+    /// order, whenever they change (see [`compose_span_sections`]). Edits are forwarded to the
+    /// child of each edited section (see [`forward_edits_to_spans`]), so each styled run of a rich
+    /// text editor can be kept and queried as a separate entity. Sections emptied by typing are kept
+    /// rather than dropped, so that each child stays paired with the same section.
+    #[derive(Component, Clone, Debug, Default, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct EditorSpan {
+        pub value: String,
+        pub style: TextStyle,
+    }
+
+    impl EditorSpan {
+        pub fn new(value: impl Into<String>, style: TextStyle) -> Self {
+            Self {
+                value: value.into(),
+                style,
+            }
+        }
+    }
+
+    /// Replaces the [`Text::sections`] of editors with the [`EditorSpan`]s of their children, in
+    /// order, when any of them change
+    pub fn compose_span_sections(
+        mut editors: Query<(Ref<Children>, &mut Text), With<EditorState>>,
+        spans: Query<Ref<EditorSpan>>,
+    ) {
+        for (children, mut text) in &mut editors {
+            let mut changed = children.is_changed();
+            let mut sections = Vec::new();
+            for span in spans.iter_many(children.iter()) {
+                changed |= span.is_changed();
+                sections.push(TextSection::new(span.value.clone(), span.style.clone()));
+            }
+            if changed && !sections.is_empty() {
+                text.sections = sections;
+            }
+        }
+    }
+
+    /// Writes the sections of each editor whose [`Text`] changed by editing to its [`EditorSpan`]
+    /// children, pairing them in order
+    ///
+    /// Only the spans whose text changed are written to. Sections without a span, e.g. pasted from
+    /// the rich clipboard, get a new child, and spans without a section are emptied.
+    pub fn forward_edits_to_spans(
+        mut commands: Commands,
+        mut text_changed: EventReader<TextChanged>,
+        editors: Query<(&Text, &Children)>,
+        mut spans: Query<&mut EditorSpan>,
+    ) {
+        for &TextChanged { entity } in text_changed.read() {
+            let Ok((text, children)) = editors.get(entity) else {
                 continue;
             };
-            let Some(camera_entity) = camera.map(TargetCamera::entity).or(default_ui_camera.get())
-            else {
+            let span_entities: Vec<Entity> = children
+                .iter()
+                .copied()
+                .filter(|child| spans.contains(*child))
+                .collect();
+            if span_entities.is_empty() {
+                continue;
+            }
+            for (i, section) in text.sections.iter().enumerate() {
+                match span_entities.get(i) {
+                    Some(span_entity) => {
+                        let mut span = spans.get_mut(*span_entity).unwrap();
+                        if span.value != section.value {
+                            span.value.clone_from(&section.value);
+                        }
+                    }
+                    None => {
+                        let span = EditorSpan::new(section.value.clone(), section.style.clone());
+                        commands.spawn(span).set_parent(entity);
+                    }
+                }
+            }
+            for span_entity in span_entities.iter().skip(text.sections.len()) {
+                let mut span = spans.get_mut(*span_entity).unwrap();
+                if !span.value.is_empty() {
+                    span.value.clear();
+                }
+            }
+        }
+    }
+
+    /// Binds the text of an editor to a `String` kept in the resource `R`, e.g. a field of a form
+    ///
+    /// Edits are written to the resource with `set`. When the resource changes otherwise, the text
+    /// of the editor is replaced with what `get` reads from it, and the cursor stays where it was,
+    /// clamped to the new text. Add a [`TextBindingPlugin<R>`] for each bound resource type.
+    ///
+    /// ```ignore
+    /// TextBinding::<MyForm>::new(|form| &form.name, |form, name| form.name = name)
+    /// ```
+    #[derive(Component)]
+    pub struct TextBinding<R: Resource> {
+        pub get: fn(&R) -> &str,
+        pub set: fn(&mut R, String),
+    }
+
+    impl<R: Resource> TextBinding<R> {
+        pub fn new(get: fn(&R) -> &str, set: fn(&mut R, String)) -> Self {
+            Self { get, set }
+        }
+    }
+
+    impl<R: Resource> Clone for TextBinding<R> {
+        fn clone(&self) -> Self {
+            *self
+        }
+    }
+
+    impl<R: Resource> Copy for TextBinding<R> {}
+
+    /// Keeps editors with a [`TextBinding<R>`] in sync with the resource `R`
+    pub struct TextBindingPlugin<R: Resource>(PhantomData<R>);
+
+    impl<R: Resource> Default for TextBindingPlugin<R> {
+        fn default() -> Self {
+            Self(PhantomData)
+        }
+    }
+
+    impl<R: Resource> Plugin for TextBindingPlugin<R> {
+        fn build(&self, app: &mut App) {
+            app.add_systems(
+                PostUpdate,
+                sync_text_binding::<R>
+                    .before(update_wrap)
+                    .before(bevy::ui::widget::text_system)
+                    .before(bevy::text::update_text2d_layout),
+            );
+        }
+    }
+
+    /// Writes the edits of editors with a [`TextBinding<R>`] to the resource `R`, then replaces
+    /// their text with the resource's value where that has changed since
+    pub fn sync_text_binding<R: Resource>(
+        mut text_changed: EventReader<TextChanged>,
+        resource: Option<ResMut<R>>,
+        mut query: Query<(&mut Text, Ref<TextBinding<R>>)>,
+    ) {
+        let Some(mut resource) = resource else {
+            text_changed.clear();
+            return;
+        };
+        let text_value = |text: &Text| -> String {
+            text.sections
+                .iter()
+                .map(|section| section.value.as_str())
+                .collect()
+        };
+
+        for event in text_changed.read() {
+            let Ok((text, binding)) = query.get(event.entity) else {
                 continue;
             };
+            let value = text_value(text);
+            if (binding.get)(&*resource) != value {
+                (binding.set)(&mut *resource, value);
+            }
+        }
 
-            // Skip if not visible or if size is set to zero (e.g. when a parent is set to `Display::None`)
-            if !view_visibility.get() || uinode.size().x == 0. || uinode.size().y == 0. {
+        for (mut text, binding) in &mut query {
+            if !resource.is_changed() && !binding.is_added() {
+                continue;
+            }
+            let value = (binding.get)(&*resource);
+            if text_value(&text) == value {
                 continue;
             }
+            // the buffer is laid out again from the text, and the editor state clamped to it
+            let style = text
+                .sections
+                .first()
+                .map(|section| section.style.clone())
+                .unwrap_or_default();
+            text.sections = vec![TextSection::new(value, style)];
+        }
+    }
 
-            let scale_factor = camera_query
-                .get(camera_entity)
-                .ok()
-                .and_then(|(_, c)| c.target_scaling_factor())
-                .unwrap_or(1.0)
-                * ui_scale.0;
-            let inverse_scale_factor = scale_factor.recip();
+    /// Repeats the held key while it's held down, for platforms that don't repeat key presses
+    ///
+    /// Key repeat is opt-in: insert this resource to enable it. Where the platform already repeats
+    /// key presses, this would repeat them twice as often.
+    #[derive(Resource, Clone, Copy, Debug)]
+    pub struct KeyRepeatConfig {
+        /// How long a key is held before it starts repeating
+        pub initial_delay: Duration,
+        /// How long between repeats
+        pub rate: Duration,
+    }
 
-            // Align the text to the nearest physical pixel:
-            // * Translate by minus the text node's half-size
-            //      (The transform translates to the center of the node but the text coordinates are relative to the node's top left corner)
-            // * Multiply the logical coordinates by the scale factor to get its position in physical coordinates
-            // * Round the physical position to the nearest physical pixel
-            // * Multiply by the rounded physical position by the inverse scale factor to return to logical coordinates
+    impl Default for KeyRepeatConfig {
+        fn default() -> Self {
+            Self {
+                initial_delay: Duration::from_millis(500),
+                rate: Duration::from_millis(33),
+            }
+        }
+    }
 
-            let logical_top_left = -0.5 * uinode.size();
+    /// The key being repeated, see [`KeyRepeatConfig`]
+    #[derive(Debug)]
+    pub struct HeldKey {
+        pub key: Key,
+        pub timer: Timer,
+    }
 
-            let mut transform = global_transform.affine()
-                * bevy::math::Affine3A::from_translation(logical_top_left.extend(0.));
+    /// Lets gamepads move the cursor of this editor while it's [`Focused`]
+    ///
+    /// The D-pad and left stick move the cursor, repeating while held if there's a
+    /// [`KeyRepeatConfig`]. The South button sends [`VirtualKeyboardRequested`].
+    #[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct GamepadEditing;
+
+    /// Sent when the South button of a gamepad is pressed while an editor with [`GamepadEditing`]
+    /// is focused, so that an on-screen keyboard can be shown
+    ///
+    /// The crate doesn't draw a keyboard itself.
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct VirtualKeyboardRequested {
+        pub entity: Entity,
+    }
+
+    /// How far the left stick is pushed before it moves the cursor
+    const STICK_THRESHOLD: f32 = 0.5;
+
+    /// Moves the cursor of the [`Focused`] editor with gamepads, if it has [`GamepadEditing`]
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn gamepad_navigation(
+        gamepads: Res<Gamepads>,
+        buttons: Res<ButtonInput<GamepadButton>>,
+        axes: Res<Axis<GamepadAxis>>,
+        time: Res<Time>,
+        key_repeat_config: Option<Res<KeyRepeatConfig>>,
+        mut held: Local<Option<(Motion, Timer)>>,
+        mut query: Query<
+            (Entity, &mut CosmicBuffer, &mut EditorState),
+            (With<Focused>, With<GamepadEditing>),
+        >,
+        mut text_pipeline: ResMut<bevy::text::TextPipeline>,
+        mut virtual_keyboard_requested: EventWriter<VirtualKeyboardRequested>,
+    ) {
+        let dpad = [
+            (GamepadButtonType::DPadLeft, Motion::Left),
+            (GamepadButtonType::DPadRight, Motion::Right),
+            (GamepadButtonType::DPadUp, Motion::Up),
+            (GamepadButtonType::DPadDown, Motion::Down),
+        ];
+        // the direction held on any gamepad, from the D-pad or else the left stick
+        let held_motion = gamepads.iter().find_map(|gamepad| {
+            let pressed = dpad.iter().find(|(button_type, _)| {
+                buttons.pressed(GamepadButton::new(gamepad, *button_type))
+            });
+            if let Some((_, motion)) = pressed {
+                return Some(*motion);
+            }
+            let axis = |axis_type| {
+                axes.get(GamepadAxis::new(gamepad, axis_type))
+                    .unwrap_or(0.0)
+            };
+            let (x, y) = (
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            );
+            if x.abs().max(y.abs()) < STICK_THRESHOLD {
+                None
+            } else if x.abs() >= y.abs() {
+                Some(if x > 0.0 { Motion::Right } else { Motion::Left })
+            } else {
+                Some(if y > 0.0 { Motion::Up } else { Motion::Down })
+            }
+        });
+
+        // move once when a direction is first held, then repeat like a held key
+        let mut motions = Vec::new();
+        match (held_motion, held.as_mut()) {
+            (Some(motion), Some((held_motion, timer))) if *held_motion == motion => {
+                if let Some(config) = &key_repeat_config {
+                    timer.tick(time.delta());
+                    for _ in 0..timer.times_finished_this_tick() {
+                        motions.push(motion);
+                    }
+                    if timer.mode() == TimerMode::Once && timer.finished() {
+                        *timer = Timer::new(config.rate, TimerMode::Repeating);
+                    }
+                }
+            }
+            (Some(motion), _) => {
+                motions.push(motion);
+                let initial_delay = key_repeat_config
+                    .as_ref()
+                    .map_or(Duration::ZERO, |config| config.initial_delay);
+                *held = Some((motion, Timer::new(initial_delay, TimerMode::Once)));
+            }
+            (None, _) => *held = None,
+        }
+
+        let virtual_keyboard = gamepads.iter().any(|gamepad| {
+            buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South))
+        });
+
+        for (entity, mut buf, mut editor_state) in &mut query {
+            if virtual_keyboard {
+                virtual_keyboard_requested.send(VirtualKeyboardRequested { entity });
+            }
+            if motions.is_empty() {
+                continue;
+            }
+            let mut preferred_x = editor_state.preferred_x;
+            let mut vertical = false;
+            editor_state.resume(&mut buf).with_editor_mut(|editor| {
+                let font_system = text_pipeline.font_system_mut();
+                for &motion in &motions {
+                    vertical = matches!(motion, Motion::Up | Motion::Down);
+                    if vertical {
+                        preferred_x =
+                            apply_vertical_motion(editor, font_system, motion, false, preferred_x);
+                    } else {
+                        apply_motion(editor, font_system, motion, false);
+                        preferred_x = None;
+                    }
+                }
+                // scroll to keep the cursor in view
+                editor.shape_as_needed(font_system, false);
+            });
+            if vertical {
+                editor_state.preferred_x = preferred_x;
+            }
+        }
+    }
+
+    /// Modifier keys currently held down, tracked from [`KeyboardInput`] press/release events
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Modifiers {
+        pub control: bool,
+        pub shift: bool,
+    }
+
+    impl Modifiers {
+        pub const NONE: Self = Self {
+            control: false,
+            shift: false,
+        };
+        pub const CONTROL: Self = Self {
+            control: true,
+            shift: false,
+        };
+        pub const SHIFT: Self = Self {
+            control: false,
+            shift: true,
+        };
+        pub const CONTROL_SHIFT: Self = Self {
+            control: true,
+            shift: true,
+        };
+    }
+
+    /// What a key press does in an editor, see [`KeyBindings`]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum EditorCommand {
+        /// Move the cursor, extending the selection if `select` is true
+        Move {
+            motion: Motion,
+            select: bool,
+        },
+        SelectAll,
+        Copy,
+        Cut,
+        Paste,
+        /// Type this character
+        Insert(char),
+        /// Insert a new line
+        Enter,
+        Backspace,
+        Delete,
+        /// Delete back to the start of the word
+        DeleteWordBackward,
+        /// Delete forward to the end of the word
+        DeleteWordForward,
+        /// Indent, following the [`TabBehavior`]: a multi-line selection is indented, otherwise
+        /// the indent is inserted
+        Indent,
+        /// Outdent the selected lines, following the [`TabBehavior`]
+        Outdent,
+        /// Toggle overwrite mode
+        ToggleOverwrite,
+        /// Delete to the end of the line (or the line ending, at the end of the line), pushing
+        /// the deleted text onto the [`KillRing`]
+        KillLine,
+        /// Insert the text last pushed onto the [`KillRing`]
+        Yank,
+        /// Undo the last edit, see [`UndoHistory`]
+        Undo,
+        /// Redo the last undone edit
+        Redo,
+    }
+
+    impl EditorCommand {
+        /// Whether the command changes the text, rather than moving the cursor or selecting
+        pub fn is_edit(&self) -> bool {
+            !matches!(
+                self,
+                Self::Move { .. } | Self::SelectAll | Self::Copy | Self::ToggleOverwrite
+            )
+        }
+    }
+
+    /// The [`EditorCommand`] of each key press in editors
+    ///
+    /// Character keys are matched case-insensitively. A key press with Shift held that isn't
+    /// bound falls back to the binding without Shift. Unbound characters pressed without Ctrl are
+    /// typed.
+    #[derive(Resource, Clone, Debug)]
+    pub struct KeyBindings {
+        pub bindings: HashMap<(Key, Modifiers), EditorCommand>,
+    }
+
+    impl Default for KeyBindings {
+        fn default() -> Self {
+            let mut key_bindings = Self::empty()
+                .bind(
+                    Key::Character("a".into()),
+                    Modifiers::CONTROL,
+                    EditorCommand::SelectAll,
+                )
+                .bind(
+                    Key::Character("c".into()),
+                    Modifiers::CONTROL,
+                    EditorCommand::Copy,
+                )
+                .bind(
+                    Key::Character("x".into()),
+                    Modifiers::CONTROL,
+                    EditorCommand::Cut,
+                )
+                .bind(
+                    Key::Character("v".into()),
+                    Modifiers::CONTROL,
+                    EditorCommand::Paste,
+                )
+                .bind(Key::Space, Modifiers::NONE, EditorCommand::Insert(' '))
+                .bind(Key::Enter, Modifiers::NONE, EditorCommand::Enter)
+                .bind(Key::Backspace, Modifiers::NONE, EditorCommand::Backspace)
+                .bind(Key::Delete, Modifiers::NONE, EditorCommand::Delete)
+                .bind(
+                    Key::Backspace,
+                    Modifiers::CONTROL,
+                    EditorCommand::DeleteWordBackward,
+                )
+                .bind(
+                    Key::Delete,
+                    Modifiers::CONTROL,
+                    EditorCommand::DeleteWordForward,
+                )
+                .bind(Key::Tab, Modifiers::NONE, EditorCommand::Indent)
+                .bind(Key::Tab, Modifiers::SHIFT, EditorCommand::Outdent)
+                .bind(Key::Insert, Modifiers::NONE, EditorCommand::ToggleOverwrite)
+                .bind(
+                    Key::Character("k".into()),
+                    Modifiers::CONTROL,
+                    EditorCommand::KillLine,
+                )
+                .bind(
+                    Key::Character("y".into()),
+                    Modifiers::CONTROL,
+                    EditorCommand::Yank,
+                )
+                .bind(
+                    Key::Character("z".into()),
+                    Modifiers::CONTROL,
+                    EditorCommand::Undo,
+                )
+                .bind(
+                    Key::Character("z".into()),
+                    Modifiers::CONTROL_SHIFT,
+                    EditorCommand::Redo,
+                );
+            // (key, motion, motion with Ctrl)
+            let motions = [
+                (Key::ArrowLeft, Motion::Left, Motion::LeftWord),
+                (Key::ArrowRight, Motion::Right, Motion::RightWord),
+                (Key::ArrowUp, Motion::Up, Motion::Up),
+                (Key::ArrowDown, Motion::Down, Motion::Down),
+                (Key::Home, Motion::Home, Motion::BufferStart),
+                (Key::End, Motion::End, Motion::BufferEnd),
+                (Key::PageUp, Motion::PageUp, Motion::PageUp),
+                (Key::PageDown, Motion::PageDown, Motion::PageDown),
+            ];
+            for (key, motion, control_motion) in motions {
+                for (modifiers, motion) in [
+                    (Modifiers::NONE, motion),
+                    (Modifiers::SHIFT, motion),
+                    (Modifiers::CONTROL, control_motion),
+                    (Modifiers::CONTROL_SHIFT, control_motion),
+                ] {
+                    let select = modifiers.shift;
+                    key_bindings.insert(
+                        key.clone(),
+                        modifiers,
+                        EditorCommand::Move { motion, select },
+                    );
+                }
+            }
+            key_bindings
+        }
+    }
+
+    impl KeyBindings {
+        /// No bindings, so that only typing works
+        pub fn empty() -> Self {
+            Self {
+                bindings: HashMap::new(),
+            }
+        }
+
+        /// Returns these bindings with `key` pressed with `modifiers` bound to `command`.
+        pub fn bind(mut self, key: Key, modifiers: Modifiers, command: EditorCommand) -> Self {
+            self.insert(key, modifiers, command);
+            self
+        }
+
+        /// Binds `key` pressed with `modifiers` to `command`, returning the previous command
+        pub fn insert(
+            &mut self,
+            key: Key,
+            modifiers: Modifiers,
+            command: EditorCommand,
+        ) -> Option<EditorCommand> {
+            self.bindings
+                .insert((normalize_key(key), modifiers), command)
+        }
+
+        /// Unbinds `key` pressed with `modifiers`, returning its command
+        pub fn remove(&mut self, key: Key, modifiers: Modifiers) -> Option<EditorCommand> {
+            self.bindings.remove(&(normalize_key(key), modifiers))
+        }
+
+        /// The command of `key` pressed with `modifiers`, if it's bound
+        pub fn command(&self, key: &Key, modifiers: Modifiers) -> Option<EditorCommand> {
+            let key = normalize_key(key.clone());
+            self.bindings
+                .get(&(key.clone(), modifiers))
+                .or_else(|| {
+                    // Shift only changes the binding if it's bound with Shift
+                    modifiers.shift.then(|| {
+                        let modifiers = Modifiers {
+                            shift: false,
+                            ..modifiers
+                        };
+                        self.bindings.get(&(key, modifiers))
+                    })?
+                })
+                .copied()
+        }
+    }
+
+    /// The text and editor state before edits, for [`EditorCommand::Undo`] and
+    /// [`EditorCommand::Redo`]
+    ///
+    /// Consecutive typing is undone in one step. Typing over a selection is one step that restores
+    /// the selected text and the selection. Text set by other systems isn't recorded.
+    #[derive(Component, Clone, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct UndoHistory {
+        pub undo: Vec<UndoEntry>,
+        pub redo: Vec<UndoEntry>,
+        /// How many steps can be undone
+        pub limit: usize,
+        /// Whether the next typed character joins the last step
+        coalescing: bool,
+    }
+
+    impl Default for UndoHistory {
+        fn default() -> Self {
+            Self {
+                undo: Vec::new(),
+                redo: Vec::new(),
+                limit: 100,
+                coalescing: false,
+            }
+        }
+    }
+
+    impl UndoHistory {
+        /// Records the text before an edit, clearing the steps that can be redone
+        pub fn record(&mut self, text: &Text, editor_state: &EditorState) {
+            self.undo.push(UndoEntry::new(text, editor_state));
+            if self.undo.len() > self.limit {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+    }
+
+    /// One step of an [`UndoHistory`]
+    #[derive(Clone, Debug, Reflect)]
+    pub struct UndoEntry {
+        pub sections: Vec<TextSection>,
+        pub editor_state: EditorState,
+    }
+
+    impl UndoEntry {
+        pub fn new(text: &Text, editor_state: &EditorState) -> Self {
+            Self {
+                sections: text.sections.clone(),
+                editor_state: editor_state.clone(),
+            }
+        }
+
+        /// Whether `text` has the same value as this entry, regardless of styles
+        fn has_value_of(&self, text: &Text) -> bool {
+            self.sections
+                .iter()
+                .flat_map(|section| section.value.chars())
+                .eq(text
+                    .sections
+                    .iter()
+                    .flat_map(|section| section.value.chars()))
+        }
+    }
+
+    /// Text deleted by [`EditorCommand::KillLine`], shared by all editors, most recent first
+    #[derive(Resource, Clone, Debug)]
+    pub struct KillRing {
+        pub entries: VecDeque<String>,
+        /// How many entries are kept
+        pub capacity: usize,
+    }
+
+    impl Default for KillRing {
+        fn default() -> Self {
+            Self {
+                entries: VecDeque::new(),
+                capacity: 16,
+            }
+        }
+    }
+
+    impl KillRing {
+        /// Pushes killed text, dropping the oldest entries past the capacity
+        pub fn push(&mut self, text: String) {
+            self.entries.push_front(text);
+            self.entries.truncate(self.capacity);
+        }
+
+        /// The most recently killed text, which [`EditorCommand::Yank`] inserts
+        pub fn latest(&self) -> Option<&str> {
+            self.entries.front().map(String::as_str)
+        }
+    }
+
+    /// Lowercases character keys, so that bindings match with or without Shift or Caps Lock
+    fn normalize_key(key: Key) -> Key {
+        match key {
+            Key::Character(character) => Key::Character(character.to_lowercase().into()),
+            key => key,
+        }
+    }
+
+    // the section scratch is an implementation detail, which callers never need to name
+    #[allow(
+        clippy::too_many_arguments,
+        clippy::type_complexity,
+        private_interfaces
+    )]
+    pub fn listen_keyboard_input_events(
+        mut events: EventReader<KeyboardInput>,
+        mut modifiers: Local<Modifiers>,
+        mut buffer: Query<
+            (
+                Entity,
+                &mut CosmicBuffer,
+                &mut Text,
+                &mut EditorState,
+                Option<&TabBehavior>,
+                Option<&LineEnding>,
+                Option<&MaxLength>,
+                Option<&TextMask>,
+                Option<&ReadOnly>,
+                Option<&EditorFontConfig>,
+                Option<&ClipboardMode>,
+                Option<&SmartHome>,
+                Option<&mut UndoHistory>,
+                (
+                    Option<&EditorCapabilities>,
+                    Option<&ShiftEnterNewline>,
+                    Option<&InputFilter>,
+                    Option<&Children>,
+                    Ref<Focused>,
+                ),
+            ),
+            With<Focused>,
+        >,
+        spans: Query<(), With<EditorSpan>>,
+        mut text_pipeline: ResMut<bevy::text::TextPipeline>,
+        mut text_changed: EventWriter<TextChanged>,
+        mut scratch_spans_for_update: Local<SectionScratch>,
+        time: Res<Time>,
+        key_repeat_config: Option<Res<KeyRepeatConfig>>,
+        mut held_key: Local<Option<HeldKey>>,
+        mut rich_clipboard: ResMut<RichClipboard>,
+        mut clipboard: ResMut<EditorClipboard>,
+        key_bindings: Res<KeyBindings>,
+        mut kill_ring: ResMut<KillRing>,
+        mut pending_presses: Local<Vec<(Key, Modifiers)>>,
+        (mut submit, mut input_rejected): (EventWriter<Submit>, EventWriter<InputRejected>),
+    ) {
+        // the keys to act on, with the modifiers held when each was pressed
+        let mut presses = std::mem::take(&mut *pending_presses);
+        for event in events.read() {
+            // Modifiers are tracked on both press and release.
+            match event.logical_key {
+                Key::Control => modifiers.control = event.state == ButtonState::Pressed,
+                Key::Shift => modifiers.shift = event.state == ButtonState::Pressed,
+                _ => {}
+            }
+
+            // Only trigger changes when the key is first pressed.
+            if event.state == ButtonState::Released {
+                if held_key
+                    .as_ref()
+                    .is_some_and(|held_key| held_key.key == event.logical_key)
+                {
+                    *held_key = None;
+                }
+                continue;
+            }
+
+            if let Some(config) = &key_repeat_config {
+                if !matches!(event.logical_key, Key::Control | Key::Shift) {
+                    *held_key = Some(HeldKey {
+                        key: event.logical_key.clone(),
+                        timer: Timer::new(config.initial_delay, TimerMode::Once),
+                    });
+                }
+            }
+            presses.push((event.logical_key.clone(), *modifiers));
+        }
+
+        // repeat the held key, first after the initial delay and then at the repeat rate
+        if let (Some(config), Some(held_key)) = (&key_repeat_config, held_key.as_mut()) {
+            held_key.timer.tick(time.delta());
+            for _ in 0..held_key.timer.times_finished_this_tick() {
+                presses.push((held_key.key.clone(), *modifiers));
+            }
+            if held_key.timer.mode() == TimerMode::Once && held_key.timer.finished() {
+                held_key.timer = Timer::new(config.rate, TimerMode::Repeating);
+            }
+        }
+
+        // the command of each press, and whether it types its key
+        let mut presses: Vec<_> = presses
+            .into_iter()
+            .map(|(key, modifiers)| {
+                let command = key_bindings.command(&key, modifiers);
+                // unbound characters are typed, unless Ctrl is held
+                let typed = matches!(key, Key::Character(_)) && !modifiers.control;
+                (key, modifiers, command, typed)
+            })
+            .collect();
+        // undo and redo replace the text, which the buffer is only laid out from later, so the
+        // presses after them wait until the next frame
+        if let Some(i) = presses.iter().position(|(_, _, command, _)| {
+            matches!(command, Some(EditorCommand::Undo | EditorCommand::Redo))
+        }) {
+            pending_presses.extend(
+                presses
+                    .drain(i.max(1)..)
+                    .map(|(key, modifiers, _, _)| (key, modifiers)),
+            );
+        }
+        if presses.is_empty() {
+            return;
+        }
+
+        // all of the presses are applied to each focused editor at once, then its text is written
+        // back
+        for (
+            entity,
+            mut buf,
+            mut text,
+            mut editor_state,
+            tab_behavior,
+            line_ending,
+            max_length,
+            mask,
+            read_only,
+            font_config,
+            clipboard_mode,
+            smart_home,
+            mut undo_history,
+            (capabilities, shift_enter_newline, input_filter, children, focused),
+        ) in &mut buffer
+        {
+            let has_spans = children
+                .is_some_and(|children| children.iter().any(|child| spans.contains(*child)));
+            let is_edit = |command: Option<EditorCommand>, typed: bool| {
+                command.map_or(typed, |command| command.is_edit())
+            };
+            // read-only editors ignore edits, and other editors the actions they don't allow
+            let read_only = read_only.is_some_and(|read_only| read_only.0);
+            let capabilities = capabilities.copied().unwrap_or_default();
+            let shift_enter_newline = shift_enter_newline.is_some_and(|shift| shift.0);
+            // Tab moves focus instead, see `traverse_focus`. an editor that was just focused ignores
+            // it too, as the same press may have moved focus to it
+            let traverses_focus =
+                matches!(tab_behavior, Some(TabBehavior::FocusTraversal(_))) || focused.is_added();
+            let presses: Vec<_> = presses
+                .iter()
+                .filter(|(_, modifiers, command, typed)| {
+                    if read_only && is_edit(*command, *typed) {
+                        input_rejected.send(InputRejected {
+                            entity,
+                            reason: RejectReason::ReadOnly,
+                        });
+                        return false;
+                    }
+                    if traverses_focus
+                        && matches!(
+                            command,
+                            Some(EditorCommand::Indent | EditorCommand::Outdent)
+                        )
+                    {
+                        return false;
+                    }
+                    if capabilities.allows(*command, *typed) {
+                        return true;
+                    }
+                    if *command == Some(EditorCommand::Enter) {
+                        if modifiers.shift && shift_enter_newline {
+                            return true;
+                        }
+                        submit.send(Submit {
+                            entity,
+                            value: text
+                                .sections
+                                .iter()
+                                .map(|section| section.value.as_str())
+                                .collect(),
+                        });
+                        return false;
+                    }
+                    input_rejected.send(InputRejected {
+                        entity,
+                        reason: RejectReason::CapabilityDisabled,
+                    });
+                    false
+                })
+                .collect();
+            if presses.is_empty() {
+                continue;
+            }
+
+            // whether the text before these edits was recorded, to be undone
+            let mut recorded = false;
+            if let Some(history) = undo_history.as_deref_mut() {
+                if let [&(_, _, Some(command @ (EditorCommand::Undo | EditorCommand::Redo)), _)] =
+                    presses[..]
+                {
+                    let entry = if command == EditorCommand::Undo {
+                        history.undo.pop()
+                    } else {
+                        history.redo.pop()
+                    };
+                    if let Some(entry) = entry {
+                        let current = UndoEntry::new(&text, &editor_state);
+                        if command == EditorCommand::Undo {
+                            history.redo.push(current);
+                        } else {
+                            history.undo.push(current);
+                        }
+                        text.sections = entry.sections;
+                        *editor_state = EditorState {
+                            overwrite: editor_state.overwrite,
+                            ..entry.editor_state
+                        };
+                        text_changed.send(TextChanged { entity });
+                    }
+                    history.coalescing = false;
+                    // the buffer is laid out from the restored text later
+                    continue;
+                }
+                for &(_, _, command, typed) in &presses {
+                    if !is_edit(*command, *typed) {
+                        history.coalescing = false;
+                        continue;
+                    }
+                    // typing is undone in one step, but typing over a selection starts a new
+                    // step, so that undoing it restores the selection
+                    let typing = command.map_or(*typed, |command| {
+                        matches!(command, EditorCommand::Insert(_))
+                    });
+                    // the text is only written back after all of the presses, so they're
+                    // undone together
+                    if !recorded
+                        && !(typing
+                            && history.coalescing
+                            && editor_state.selection_bounds.is_none())
+                    {
+                        history.record(&text, &editor_state);
+                        recorded = true;
+                    }
+                    history.coalescing = typing;
+                }
+            }
+            let max_length = max_length.and_then(|max_length| max_length.0);
+            // masked text can't be copied out of the editor
+            let masked = mask.is_some_and(|mask| mask.0.is_some());
+
+            // temporary hack:
+            // see https://github.com/pop-os/cosmic-text/issues/290
+            // sets the metadata of each line's default attrs to the span index of that line's ending
+            //
+            // only the lines the presses can reach are set: each press moves the cursors or edits
+            // at most one line past them, unless it jumps, e.g. to the end of the buffer
+            let jumps = presses.iter().any(|(_, _, command, _)| match command {
+                Some(EditorCommand::Move { motion, .. }) => !matches!(
+                    motion,
+                    Motion::Previous
+                        | Motion::Next
+                        | Motion::Left
+                        | Motion::Right
+                        | Motion::Up
+                        | Motion::Down
+                        | Motion::Home
+                        | Motion::SoftHome
+                        | Motion::End
+                        | Motion::ParagraphStart
+                        | Motion::ParagraphEnd
+                        | Motion::PreviousWord
+                        | Motion::NextWord
+                        | Motion::LeftWord
+                        | Motion::RightWord
+                ),
+                Some(EditorCommand::SelectAll) => true,
+                _ => false,
+            });
+            let lines = if jumps {
+                0..=usize::MAX
+            } else {
+                let state: &EditorState = &editor_state;
+                let cursor_lines = move || {
+                    let bounds = state.selection_bounds.iter();
+                    state
+                        .cursor
+                        .iter()
+                        .chain(bounds.flat_map(|(start, end)| [start, end]))
+                        .chain(&state.secondary_cursors)
+                        .map(|cursor| cursor.line)
+                };
+                let first = cursor_lines().min().unwrap_or(0);
+                let last = cursor_lines().max().unwrap_or(0);
+                first.saturating_sub(presses.len())..=last + presses.len()
+            };
+            assign_line_sections(&mut buf, &text, lines);
+            // /temporary hack
+
+            let mut overwrite = editor_state.overwrite;
+            let was_empty = buffer_is_empty(&buf);
+            let rich = clipboard_mode == Some(&ClipboardMode::Rich);
+            // the styles of sections pasted from the rich clipboard, indexed from the end of
+            // the existing sections
+            let section_count = text.sections.len();
+            let mut pasted_styles = Vec::new();
+            // the column vertical motion aims for
+            let mut preferred_x = editor_state.preferred_x;
+            let mut secondary_cursors = std::mem::take(&mut editor_state.secondary_cursors);
+            // the lines edited by the presses, as they are afterwards
+            let mut edited_lines = None;
+
+            editor_state.resume(&mut buf).with_editor_mut(|editor| {
+                let font_system = text_pipeline.font_system_mut();
+                let mut reject = |reason| input_rejected.send(InputRejected { entity, reason });
+                editor.start_change();
+                for &(key, _, command, typed) in &presses {
+                    let (command, typed) = (*command, *typed);
+                    let cursor = editor.cursor();
+                    let vertical = matches!(
+                        command,
+                        Some(EditorCommand::Move {
+                            motion: Motion::Up | Motion::Down | Motion::PageUp | Motion::PageDown,
+                            ..
+                        })
+                    );
+                    if !secondary_cursors.is_empty() {
+                        let multi_cursor = editor.selection() == Selection::None
+                            && match command {
+                                Some(
+                                    EditorCommand::Insert(_)
+                                    | EditorCommand::Backspace
+                                    | EditorCommand::Delete
+                                    | EditorCommand::Enter,
+                                ) => true,
+                                None => typed,
+                                _ => false,
+                            };
+                        if multi_cursor {
+                            let chars: Vec<char> = match (command, key) {
+                                (Some(EditorCommand::Insert(c)), _) => vec![c],
+                                (None, Key::Character(character)) => character.chars().collect(),
+                                _ => Vec::new(),
+                            };
+                            // each cursor is checked against the length left by the edits before
+                            // it, but each rejection is only sent once
+                            let (mut filtered, mut too_long) = (false, false);
+                            secondary_cursors = edit_at_cursors(
+                                editor,
+                                &secondary_cursors,
+                                |editor| match command {
+                                    Some(EditorCommand::Backspace) => {
+                                        backspace(editor, font_system)
+                                    }
+                                    Some(EditorCommand::Delete) => {
+                                        editor.action(font_system, Action::Delete)
+                                    }
+                                    Some(EditorCommand::Enter) => {
+                                        if fit_max_length(editor, max_length, "\n").is_empty() {
+                                            too_long = true;
+                                        } else {
+                                            editor.action(font_system, Action::Enter)
+                                        }
+                                    }
+                                    _ => {
+                                        for c in &chars {
+                                            if !input_filter
+                                                .map_or(true, |filter| filter.allows(*c))
+                                            {
+                                                filtered = true;
+                                            } else if !insert_char(
+                                                editor,
+                                                font_system,
+                                                *c,
+                                                overwrite,
+                                                max_length,
+                                            ) {
+                                                too_long = true;
+                                                break;
+                                            }
+                                        }
+                                    }
+                                },
+                            );
+                            if filtered {
+                                reject(RejectReason::InputFilter);
+                            }
+                            if too_long {
+                                reject(RejectReason::MaxLength);
+                            }
+                            preferred_x = None;
+                            continue;
+                        }
+                        if !matches!(
+                            command,
+                            Some(EditorCommand::Copy | EditorCommand::ToggleOverwrite) | None
+                        ) {
+                            secondary_cursors.clear();
+                        }
+                    }
+                    // info!("Before: {:?}", editor.cursor());
+                    match command {
+                        Some(EditorCommand::Move { motion, select }) => match motion {
+                            Motion::Up | Motion::Down | Motion::PageUp | Motion::PageDown => {
+                                preferred_x = apply_vertical_motion(
+                                    editor,
+                                    font_system,
+                                    motion,
+                                    select,
+                                    preferred_x,
+                                );
+                            }
+                            Motion::Home if smart_home.is_some_and(|smart_home| smart_home.0) => {
+                                let cursor = editor.cursor();
+                                let index = editor.with_buffer(|buffer| {
+                                    let text = buffer
+                                        .lines
+                                        .get(cursor.line)
+                                        .map_or("", |line| line.text());
+                                    let indent = text.len() - text.trim_start().len();
+                                    // toggle between the first non-whitespace character and the line start
+                                    if cursor.index == indent {
+                                        0
+                                    } else {
+                                        indent
+                                    }
+                                });
+                                move_cursor(editor, Cursor::new(cursor.line, index), select);
+                            }
+                            _ => apply_motion(editor, font_system, motion, select),
+                        },
+                        Some(EditorCommand::SelectAll) => {
+                            editor.action(font_system, Action::Motion(Motion::BufferStart));
+                            editor.set_selection(Selection::Normal(editor.cursor()));
+                            editor.action(font_system, Action::Motion(Motion::BufferEnd));
+                        }
+                        Some(command @ (EditorCommand::Copy | EditorCommand::Cut)) if !masked => {
+                            if let Some(copied) = editor.copy_selection() {
+                                clipboard.0.set(&copied);
+                                if let Some((start, end)) =
+                                    editor.selection_bounds().filter(|_| rich)
+                                {
+                                    rich_clipboard.sections = editor.with_buffer(|buffer| {
+                                        sections_between(&text, buffer, start, end)
+                                    });
+                                }
+                                if command == EditorCommand::Cut {
+                                    editor.delete_selection();
+                                }
+                            }
+                        }
+                        Some(EditorCommand::Paste) => {
+                            if let Some(text) = clipboard.0.get() {
+                                let text = LineEnding::normalize(&text);
+                                let text = capabilities.fit_lines(&text);
+                                let filtered = filter_input(input_filter, &text);
+                                if filtered.len() < text.len() {
+                                    reject(RejectReason::InputFilter);
+                                }
+                                let text = filtered;
+                                let fitted = fit_max_length(editor, max_length, &text);
+                                if fitted.len() < text.len() {
+                                    reject(RejectReason::MaxLength);
+                                }
+                                // the clipboard still holds what was copied in the app,
+                                // so paste it with its styles
+                                let attrs_list = (rich
+                                    && fitted.len() == text.len()
+                                    && rich_clipboard.matches(&text))
+                                .then(|| {
+                                    let first_index = section_count + pasted_styles.len();
+                                    pasted_styles.extend(
+                                        rich_clipboard
+                                            .sections
+                                            .iter()
+                                            .map(|section| section.style.clone()),
+                                    );
+                                    rich_clipboard.attrs_list(first_index)
+                                });
+                                insert_lines(editor, font_system, fitted, attrs_list);
+                            }
+                        }
+                        Some(EditorCommand::Insert(c)) => {
+                            if !input_filter.map_or(true, |filter| filter.allows(c)) {
+                                reject(RejectReason::InputFilter);
+                            } else if !insert_char(editor, font_system, c, overwrite, max_length) {
+                                reject(RejectReason::MaxLength);
+                            }
+                        }
+                        Some(EditorCommand::Enter) => {
+                            if fit_max_length(editor, max_length, "\n").is_empty() {
+                                reject(RejectReason::MaxLength);
+                            } else {
+                                editor.action(font_system, Action::Enter)
+                            }
+                        }
+                        Some(EditorCommand::Backspace) => backspace(editor, font_system),
+                        Some(EditorCommand::Delete) => editor.action(font_system, Action::Delete),
+                        Some(EditorCommand::DeleteWordBackward) => {
+                            delete_word(editor, font_system, Motion::PreviousWord)
+                        }
+                        Some(EditorCommand::DeleteWordForward) => {
+                            delete_word(editor, font_system, Motion::NextWord)
+                        }
+                        Some(EditorCommand::Indent) => {
+                            if let Some(indent) = tab_behavior.copied().unwrap_or_default().indent()
+                            {
+                                let multi_line = editor
+                                    .selection_bounds()
+                                    .is_some_and(|(start, end)| start.line != end.line);
+                                if multi_line {
+                                    indent_lines(editor, &indent, false);
+                                } else {
+                                    let fitted = fit_max_length(editor, max_length, &indent);
+                                    if fitted.len() < indent.len() {
+                                        reject(RejectReason::MaxLength);
+                                    }
+                                    for c in fitted.chars() {
+                                        editor.action(font_system, Action::Insert(c));
+                                    }
+                                }
+                            }
+                        }
+                        Some(EditorCommand::Outdent) => {
+                            if let Some(indent) = tab_behavior.copied().unwrap_or_default().indent()
+                            {
+                                indent_lines(editor, &indent, true);
+                            }
+                        }
+                        Some(EditorCommand::KillLine) => {
+                            let cursor = editor.cursor();
+                            let end = editor.with_buffer(|buffer| {
+                                let len = buffer.lines.get(cursor.line)?.text().len();
+                                if cursor.index < len {
+                                    Some(Cursor::new(cursor.line, len))
+                                } else {
+                                    // join the next line
+                                    buffer.lines.get(cursor.line + 1)?;
+                                    Some(Cursor::new(cursor.line + 1, 0))
+                                }
+                            });
+                            if let Some(end) = end {
+                                editor.set_selection(Selection::Normal(cursor));
+                                editor.set_cursor(end);
+                                let killed = editor.copy_selection();
+                                editor.delete_selection();
+                                // masked text can't be yanked out of the editor
+                                if let Some(killed) = killed.filter(|_| !masked) {
+                                    kill_ring.push(killed);
+                                }
+                            }
+                        }
+                        Some(EditorCommand::Yank) => {
+                            if let Some(text) = kill_ring.latest() {
+                                let text = capabilities.fit_lines(text);
+                                let filtered = filter_input(input_filter, &text);
+                                if filtered.len() < text.len() {
+                                    reject(RejectReason::InputFilter);
+                                }
+                                let fitted = fit_max_length(editor, max_length, &filtered);
+                                if fitted.len() < filtered.len() {
+                                    reject(RejectReason::MaxLength);
+                                }
+                                insert_lines(editor, font_system, fitted, None);
+                            }
+                        }
+                        Some(EditorCommand::ToggleOverwrite) => overwrite = !overwrite,
+                        // handled before resuming the editor
+                        Some(EditorCommand::Undo | EditorCommand::Redo) => {}
+                        // masked
+                        Some(EditorCommand::Copy | EditorCommand::Cut) => {}
+                        // typing
+                        None if typed => {
+                            if let Key::Character(character) = key {
+                                for c in character.chars() {
+                                    if !input_filter.map_or(true, |filter| filter.allows(c)) {
+                                        reject(RejectReason::InputFilter);
+                                    } else if !insert_char(
+                                        editor,
+                                        font_system,
+                                        c,
+                                        overwrite,
+                                        max_length,
+                                    ) {
+                                        reject(RejectReason::MaxLength);
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        None => {}
+                    }
+                    // vertical motion keeps aiming for the same column
+                    if !vertical && editor.cursor() != cursor {
+                        preferred_x = None;
+                    }
+                }
+                edited_lines = editor
+                    .finish_change()
+                    .and_then(|change| changed_lines(&change));
+                // scroll to keep the cursor in view
+                editor.shape_as_needed(font_system, false);
+            });
+            editor_state.overwrite = overwrite;
+            editor_state.preferred_x = preferred_x;
+            editor_state.secondary_cursors = secondary_cursors;
+
+            let line_ending = line_ending.copied().unwrap_or_default();
+            // only the edited lines are written back, unless that can't be done in place
+            // (see `splice_sections`), e.g. when line endings are rewritten or sections are
+            // added or emptied
+            let in_place = line_ending == LineEnding::Preserve
+                && !was_empty
+                && pasted_styles.is_empty()
+                && !buf.lines.is_empty();
+            let write_all = match edited_lines {
+                // nothing to write back, e.g. after moving the cursor
+                None => false,
+                Some(edited_lines) if in_place => {
+                    let last_line = buf.lines.len() - 1;
+                    let (first, last) = (
+                        (*edited_lines.start()).min(last_line),
+                        (*edited_lines.end()).min(last_line),
+                    );
+                    // finding the section before the edited lines and the length of the text around
+                    // them walks the other lines without copying their text, like assigning the
+                    // sections of the lines before the edit, so an edit costs O(lines) rather than
+                    // O(text)
+                    //
+                    // unstyled text continues the section of the styled text before it
+                    let mut bevy_span_index = buf.lines[..first]
+                        .iter()
+                        .rev()
+                        .find_map(|line| {
+                            line.attrs_list()
+                                .spans()
+                                .last()
+                                .map(|(_, attrs)| attrs.metadata)
+                        })
+                        .unwrap_or(0);
+                    for line in &buf.lines[first..=last] {
+                        write_line(
+                            line,
+                            line_ending,
+                            &mut bevy_span_index,
+                            &mut scratch_spans_for_update,
+                        );
+                    }
+                    // the text is unchanged before and after the edited lines
+                    let written_len =
+                        |line: &BufferLine| line.text().len() + line.ending().as_str().len();
+                    let before: usize = buf.lines[..first].iter().map(written_len).sum();
+                    let after: usize = buf.lines[last + 1..].iter().map(written_len).sum();
+                    let spliced =
+                        splice_sections(&mut text, before, after, &scratch_spans_for_update);
+                    scratch_spans_for_update.clear();
+                    match spliced {
+                        Some(changed) => {
+                            if changed {
+                                text_changed.send(TextChanged { entity });
+                            }
+                            false
+                        }
+                        None => true,
+                    }
+                }
+                Some(_) => true,
+            };
+
+            // rebuild the text from scratch (writeback)
+            // this still isn't quite right
+            if write_all {
+                let mut bevy_span_index = 0;
+                for line in &buf.lines {
+                    write_line(
+                        line,
+                        line_ending,
+                        &mut bevy_span_index,
+                        &mut scratch_spans_for_update,
+                    );
+                }
+
+                // it may just be easier and less error-prone to reconstruct the entire text component
+
+                // the sections of an editor with `EditorSpan` children are kept even when they're
+                // emptied, so that each stays paired with its child, see `forward_edits_to_spans`
+                if has_spans {
+                    for i in 0..section_count {
+                        scratch_spans_for_update.entry(i);
+                    }
+                }
+                let spans: Vec<(usize, &str)> = scratch_spans_for_update.iter().collect();
+
+                // only write to the text component (triggering change detection) if something changed
+                let same_sections = spans.len() == text.sections.len()
+                    && spans
+                        .iter()
+                        .enumerate()
+                        .all(|(i, (span_index, _))| i == *span_index);
+                // text typed into an empty editor takes the editor's font, rather than the style of
+                // whichever section was left over
+                let font_config = font_config.filter(|_| {
+                    was_empty
+                        && !has_spans
+                        && pasted_styles.is_empty()
+                        && spans.iter().any(|(_, value)| !value.is_empty())
+                });
+                if let Some(font_config) = font_config {
+                    let value: String = spans.iter().map(|(_, value)| *value).collect();
+                    text.sections = vec![TextSection::new(value, font_config.style.clone())];
+                    text_changed.send(TextChanged { entity });
+                } else if same_sections {
+                    if spans
+                        .iter()
+                        .zip(&text.sections)
+                        .any(|((_, value), section)| *value != section.value)
+                    {
+                        for ((_, value), section) in spans.iter().zip(&mut text.sections) {
+                            if section.value != *value {
+                                // reuses the section's allocation
+                                section.value.clear();
+                                section.value.push_str(value);
+                            }
+                        }
+                        text_changed.send(TextChanged { entity });
+                    }
+                } else {
+                    text.sections = spans
+                        .iter()
+                        .map(|&(i, s)| {
+                            let style = match text.sections.get(i) {
+                                Some(section) => section.style.clone(),
+                                None => pasted_styles[i - section_count].clone(),
+                            };
+                            TextSection::new(s, style)
+                        })
+                        .collect();
+                    text_changed.send(TextChanged { entity });
+                }
+                scratch_spans_for_update.clear();
+            }
+
+            // nothing to undo if the edit didn't change the text, e.g. Backspace at the start
+            if let Some(history) = undo_history.as_deref_mut().filter(|_| recorded) {
+                if history
+                    .undo
+                    .last()
+                    .is_some_and(|entry| entry.has_value_of(&text))
+                {
+                    history.undo.pop();
+                }
+            }
+        }
+    }
+
+    /// Applies `motion` to the editor
+    ///
+    /// If `extend` is true, the selection is extended from the current cursor (starting a new
+    /// selection if there isn't one), otherwise any selection is cleared. Left and Right without
+    /// `extend` collapse a selection to its start or end, rather than moving past it.
+    fn apply_motion(
+        editor: &mut Editor,
+        font_system: &mut FontSystem,
+        motion: Motion,
+        extend: bool,
+    ) {
+        if !extend {
+            let bounds = editor
+                .selection_bounds()
+                .filter(|(start, end)| (start.line, start.index) != (end.line, end.index));
+            editor.set_selection(Selection::None);
+            if let (Motion::Left | Motion::Right, Some((start, end))) = (motion, bounds) {
+                editor.set_cursor(if motion == Motion::Left { start } else { end });
+                return;
+            }
+        } else if editor.selection() == Selection::None {
+            editor.set_selection(Selection::Normal(editor.cursor()));
+        }
+        editor.action(font_system, Action::Motion(motion));
+    }
+
+    /// Applies a vertical `motion` like [`apply_motion`], aiming for `preferred_x` (e.g. from
+    /// earlier vertical motions) rather than the cursor's current x position
+    ///
+    /// Returns the x position to keep aiming for.
+    fn apply_vertical_motion(
+        editor: &mut Editor,
+        font_system: &mut FontSystem,
+        motion: Motion,
+        extend: bool,
+        preferred_x: Option<i32>,
+    ) -> Option<i32> {
+        let cursor = editor.cursor();
+        let Some((moved, x)) = editor.with_buffer_mut(|buffer| {
+            buffer.cursor_motion(font_system, cursor, preferred_x, motion)
+        }) else {
+            return preferred_x;
+        };
+        move_cursor(editor, moved, extend);
+        x
+    }
+
+    /// Moves the cursor to `cursor`, extending the selection like [`apply_motion`]
+    fn move_cursor(editor: &mut Editor, cursor: Cursor, extend: bool) {
+        if !extend {
+            editor.set_selection(Selection::None);
+        } else if editor.selection() == Selection::None {
+            editor.set_selection(Selection::Normal(editor.cursor()));
+        }
+        editor.set_cursor(cursor);
+    }
+
+    /// Types `c`, replacing the character after the cursor in overwrite mode
+    ///
+    /// Returns false if `c` doesn't fit within `max_length`.
+    fn insert_char(
+        editor: &mut Editor,
+        font_system: &mut FontSystem,
+        c: char,
+        overwrite: bool,
+        max_length: Option<usize>,
+    ) -> bool {
+        if overwrite {
+            select_overwritten(editor, font_system);
+        }
+        if fit_max_length(editor, max_length, c.encode_utf8(&mut [0; 4])).is_empty() {
+            return false;
+        }
+        editor.action(font_system, Action::Insert(c));
+        true
+    }
+
+    /// The start of `text` that can be inserted (replacing any selection) without going over
+    /// `max_length` characters
+    fn fit_max_length<'a>(editor: &Editor, max_length: Option<usize>, text: &'a str) -> &'a str {
+        let Some(max_length) = max_length else {
+            return text;
+        };
+        let length = editor.with_buffer(buffer_char_count);
+        let selected = editor
+            .copy_selection()
+            .map_or(0, |selected| selected.chars().count());
+        let room = (max_length + selected).saturating_sub(length);
+        match text.char_indices().nth(room) {
+            Some((index, _)) => &text[..index],
+            None => text,
+        }
+    }
+
+    /// The text written back to each section of an editor, reused between edits so that the
+    /// strings keep their allocations
+    ///
+    /// Sections are indexed by the metadata of the buffer's attributes, which are usually dense,
+    /// but sections that weren't written to are skipped, so sparse indices still work.
+    #[derive(Debug, Default)]
+    pub(crate) struct SectionScratch {
+        values: Vec<String>,
+        /// Whether each section has been written to since the last clear, as its text may be
+        /// empty
+        written: Vec<bool>,
+    }
+
+    impl SectionScratch {
+        /// The text of section `index`, to append to
+        fn entry(&mut self, index: usize) -> &mut String {
+            if index >= self.values.len() {
+                self.values.resize_with(index + 1, String::new);
+                self.written.resize(index + 1, false);
+            }
+            self.written[index] = true;
+            &mut self.values[index]
+        }
+
+        /// The text of section `index`, if it was written to
+        fn get(&self, index: usize) -> Option<&str> {
+            self.written
+                .get(index)
+                .copied()
+                .unwrap_or(false)
+                .then(|| self.values[index].as_str())
+        }
+
+        /// The sections that were written to, in order
+        fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+            self.values
+                .iter()
+                .zip(&self.written)
+                .enumerate()
+                .filter(|(_, (_, written))| **written)
+                .map(|(i, (value, _))| (i, value.as_str()))
+        }
+
+        /// Empties every section, keeping their allocations
+        fn clear(&mut self) {
+            for value in &mut self.values {
+                value.clear();
+            }
+            self.written.fill(false);
+        }
+    }
+
+    /// Sets the metadata of the default attributes of each line of `buffer` to the index of the
+    /// section of `text` that the line's ending is in, or of the last section for the last line,
+    /// for the lines in `lines`
+    ///
+    /// The lines are matched to the text by their lengths, so this follows however cosmic-text split
+    /// them (at `\r\n`, `\n` or a lone `\r`), as long as the buffer was laid out from `text`. Lines
+    /// past the end of the text take the last section.
+    ///
+    /// The lines before `lines` are only measured, and the lines after them aren't visited, so this
+    /// doesn't walk the text itself.
+    fn assign_line_sections(
+        buffer: &mut Buffer,
+        text: &Text,
+        lines: std::ops::RangeInclusive<usize>,
+    ) {
+        let last_section = text.sections.len().saturating_sub(1);
+        let line_count = buffer.lines.len();
+        // the section that `offset` is in, and the offset it starts at
+        let (mut section, mut section_start) = (0, 0);
+        let mut offset = 0;
+        for (line_i, line) in buffer.lines.iter_mut().enumerate() {
+            if line_i > *lines.end() {
+                break;
+            }
+            offset += line.text().len();
+            let mut ending_section = last_section;
+            if line_i + 1 < line_count {
+                while section < last_section
+                    && section_start + text.sections[section].value.len() <= offset
+                {
+                    section_start += text.sections[section].value.len();
+                    section += 1;
+                }
+                ending_section = section;
+                // the line ending may continue into the next section
+                let mut ending = text.sections[section..]
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, s)| {
+                        let from = if i == 0 { offset - section_start } else { 0 };
+                        s.value.get(from..).unwrap_or_default().chars()
+                    });
+                offset += match (ending.next(), ending.next()) {
+                    (Some('\r'), Some('\n')) => 2,
+                    (Some(c), _) => c.len_utf8(),
+                    (None, _) => 0,
+                };
+            }
+            let defaults = line.attrs_list().defaults();
+            // rebuilding the attributes resets the line's shaping, so only do it when they change
+            if lines.contains(&line_i) && defaults.metadata != ending_section {
+                let mut attrs_list = AttrsList::new(defaults.metadata(ending_section));
+                for (range, attrs) in line.attrs_list().spans() {
+                    attrs_list.add_span(range.clone(), attrs.as_attrs());
+                }
+                line.set_attrs_list(attrs_list);
+            }
+        }
+    }
+
+    /// Appends the text of `line` and its line ending to the sections they're styled with, keyed by
+    /// section index
+    ///
+    /// `span_index` is the section of the last styled text, which unstyled text at the end of the
+    /// line is appended to.
+    fn write_line(
+        line: &BufferLine,
+        line_ending: LineEnding,
+        span_index: &mut usize,
+        sections: &mut SectionScratch,
+    ) {
+        let line_text = line.text();
+        let len = line_text.len();
+        let ending = line_ending.write(line.ending());
+        let spans = line.attrs_list().spans();
+        let default_attrs = line.attrs_list().defaults();
+        // NOTE: cosmic-text allows for "unstyled" (default-styled) spans/ranges
+        //       this means not all `spans` actually have styles
+        //       so imagine a line with 21 characters (full range 0..21)
+        //       the `spans` iterator can yield for example 2..7, 9..12, 12..13, 13..16, 17..19
+        //       so 0..2, 7..9, 16..17, 19..21 are unstyled, and we have to specially handle these
+        //       in this case, we will style
+        //       0..2 like 2..7 (unstyled span will be styled like next styled span)
+        //       7..9 like 9..12 (unstyled span will be styled like next styled span)
+        //       16..17 like 17..19 (unstyled span will be styled like next styled span)
+        //       19..21 like 17..19 (final part of line, unstyled span will be styled like previous styled span)
+        let mut current_pos = 0;
+        if spans.is_empty() {
+            // the default metadata is set by the hack in `listen_keyboard_input_events`
+            let s = sections.entry(default_attrs.metadata);
+            // push the line ending
+            s.push_str(ending);
+        } else {
+            for (range, attrs) in spans.into_iter() {
+                *span_index = attrs.metadata;
+                let s = sections.entry(*span_index);
+                // "unstyled" spans will take the following range's attrs
+                if current_pos < range.start {
+                    s.push_str(&line_text[current_pos..range.start]);
+                }
+                // push the styled span
+                s.push_str(&line_text[range.clone()]);
+                current_pos = range.end;
+                // push the line ending if we've reached the end of the line
+                if current_pos == len {
+                    s.push_str(ending);
+                }
+            }
+        }
+        // final part of the line
+        if current_pos < len {
+            let s = sections.entry(*span_index);
+            // push the styled span
+            s.push_str(&line_text[current_pos..len]);
+            // push the line ending since we've reached the end of the line
+            s.push_str(ending);
+        }
+    }
+
+    /// The lines of the buffer edited by `change`, as they are after the change, or `None` if
+    /// nothing was edited
+    ///
+    /// This may include lines that weren't edited, e.g. lines moved down by an insertion.
+    fn changed_lines(change: &Change) -> Option<std::ops::RangeInclusive<usize>> {
+        let mut lines: Option<(usize, usize)> = None;
+        for item in &change.items {
+            let (start, end) = (item.start.line, item.end.line);
+            let (first, last) = lines.unwrap_or((start, start));
+            // the edited lines so far, moved by this edit
+            let last = if item.insert {
+                if last >= start {
+                    last + (end - start)
+                } else {
+                    last
+                }
+            } else if last >= end {
+                last - (end - start)
+            } else {
+                last.min(start)
+            };
+            let edited = if item.insert { end } else { start };
+            lines = Some((first.min(start), last.max(edited)));
+        }
+        lines.map(|(first, last)| first..=last)
+    }
+
+    /// Replaces the text of `text` between the first `before` and the last `after` bytes with
+    /// `sections`, the rewritten text keyed by section index, returning whether it changed
+    ///
+    /// Each section's text is inserted where the replaced text starts within that section, so
+    /// the section being edited grows or shrinks in place. Returns `None` if the text can't be
+    /// replaced in place, i.e. when `sections` has text for a section that doesn't exist, or when
+    /// a section would be left empty, which rewriting all of the text drops.
+    fn splice_sections(
+        text: &mut Mut<Text>,
+        before: usize,
+        after: usize,
+        sections: &SectionScratch,
+    ) -> Option<bool> {
+        if sections.iter().any(|(i, _)| i >= text.sections.len()) {
+            return None;
+        }
+        let len: usize = text
+            .sections
+            .iter()
+            .map(|section| section.value.len())
+            .sum();
+        let replaced = before..len.checked_sub(after).filter(|end| *end >= before)?;
+        // the replaced range of each section, with its new text
+        let mut offset = 0;
+        let mut splices = Vec::with_capacity(text.sections.len());
+        for (i, section) in text.sections.iter().enumerate() {
+            let section_len = section.value.len();
+            let start = replaced.start.clamp(offset, offset + section_len) - offset;
+            let end = replaced.end.clamp(offset, offset + section_len) - offset;
+            let value = sections.get(i).unwrap_or("");
+            if section_len - (end - start) + value.len() == 0 {
+                return None;
+            }
+            splices.push((start..end, value));
+            offset += section_len;
+        }
+
+        let changed = splices
+            .iter()
+            .zip(&text.sections)
+            .any(|((range, value), section)| section.value[range.clone()] != *value);
+        if changed {
+            for ((range, value), section) in splices.into_iter().zip(&mut text.sections) {
+                if section.value[range.clone()] != value {
+                    section.value.replace_range(range, value);
+                }
+            }
+        }
+        Some(changed)
+    }
+
+    /// The text between `start` and `end`, as sections styled like the sections of `text` that it
+    /// came from
+    pub fn sections_between(
+        text: &Text,
+        buffer: &Buffer,
+        start: Cursor,
+        end: Cursor,
+    ) -> Vec<TextSection> {
+        // (section index, value)
+        let mut sections: Vec<(usize, String)> = Vec::new();
+        let mut push = |section_index: usize, value: &str| match sections.last_mut() {
+            Some((last_index, last_value)) if *last_index == section_index => {
+                last_value.push_str(value);
+            }
+            _ => sections.push((section_index, value.to_owned())),
+        };
+        for line_i in start.line..=end.line {
+            let Some(line) = buffer.lines.get(line_i) else {
+                break;
+            };
+            let line_text = line.text();
+            let attrs_list = line.attrs_list();
+            let from = if line_i == start.line { start.index } else { 0 };
+            let to = if line_i == end.line {
+                end.index
+            } else {
+                line_text.len()
+            };
+            for (index, c) in line_text.get(from..to).unwrap_or_default().char_indices() {
+                let index = from + index;
+                push(
+                    attrs_list.get_span(index).metadata,
+                    &line_text[index..index + c.len_utf8()],
+                );
+            }
+            if line_i != end.line {
+                // the line ending belongs to the section of the end of the line
+                let section_index = attrs_list
+                    .get_span(line_text.len().saturating_sub(1))
+                    .metadata;
+                push(section_index, line.ending().as_str());
+            }
+        }
+        sections
+            .into_iter()
+            .filter_map(|(section_index, value)| {
+                let style = text.sections.get(section_index)?.style.clone();
+                Some(TextSection::new(value, style))
+            })
+            .collect()
+    }
+
+    /// Inserts `text` at the cursor, replacing the selection, with an [`Action::Enter`] for each
+    /// `\n`, so that each line of `text` becomes a line of the buffer
+    ///
+    /// Like with [`Edit::insert_string`], `attrs_list` covers the whole of `text`.
+    fn insert_lines(
+        editor: &mut Editor,
+        font_system: &mut FontSystem,
+        text: &str,
+        mut attrs_list: Option<AttrsList>,
+    ) {
+        editor.delete_selection();
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                editor.action(font_system, Action::Enter);
+            }
+            // the attributes of the following lines, after this line's `\n`
+            let rest = attrs_list
+                .as_mut()
+                .map(|attrs_list| attrs_list.split_off(line.len() + 1));
+            if !line.is_empty() {
+                editor.insert_string(line, attrs_list);
+            }
+            attrs_list = rest;
+        }
+    }
+
+    /// Selects the grapheme after the cursor, so that inserting replaces it
+    ///
+    /// Does nothing if there is already a selection, or at the end of a line.
+    fn select_overwritten(editor: &mut Editor, font_system: &mut FontSystem) {
+        if editor.selection() != Selection::None {
+            return;
+        }
+        let cursor = editor.cursor();
+        let at_line_end = editor.with_buffer(|buffer| {
+            buffer
+                .lines
+                .get(cursor.line)
+                .map_or(true, |line| cursor.index >= line.text().len())
+        });
+        if !at_line_end {
+            editor.set_selection(Selection::Normal(cursor));
+            editor.action(font_system, Action::Motion(Motion::Next));
+        }
+    }
+
+    /// Deletes the grapheme before the cursor, or the selection if there is one
+    ///
+    /// cosmic-text's Backspace deletes a single character, which would leave the rest of e.g. a
+    /// flag or a family emoji behind.
+    fn backspace(editor: &mut Editor, font_system: &mut FontSystem) {
+        let end = editor.cursor();
+        if editor.selection() != Selection::None || end.index == 0 {
+            editor.action(font_system, Action::Backspace);
+            return;
+        }
+        let index = editor.with_buffer(|buffer| {
+            buffer.lines[end.line].text()[..end.index]
+                .grapheme_indices(true)
+                .next_back()
+                .map_or(0, |(i, _)| i)
+        });
+        let start = Cursor::new(end.line, index);
+        editor.set_cursor(start);
+        editor.delete_range(start, end);
+    }
+
+    /// Deletes from the cursor to the word boundary in the direction of `motion`, or the selection if
+    /// there is one
+    fn delete_word(editor: &mut Editor, font_system: &mut FontSystem, motion: Motion) {
+        if editor.selection() == Selection::None {
+            apply_motion(editor, font_system, motion, true);
+        }
+        editor.delete_selection();
+        editor.set_selection(Selection::None);
+    }
+
+    /// Indents every line touched by the selection (or the cursor's line if there is no
+    /// selection) with `indent`, or if `outdent` is true, removes one level of indentation
+    ///
+    /// The cursor and selection are kept on the same text.
+    fn indent_lines(editor: &mut Editor, indent: &str, outdent: bool) {
+        let mut cursor = editor.cursor();
+        let mut selection = editor.selection();
+        let (start, end) = editor.selection_bounds().unwrap_or((cursor, cursor));
+
+        for line_i in start.line..=end.line {
+            // the change in length of the line's indentation
+            let (removed, added) = if outdent {
+                let removed = editor.with_buffer(|buffer| {
+                    let text = buffer.lines[line_i].text();
+                    if text.starts_with('\t') {
+                        1
+                    } else {
+                        text.bytes()
+                            .take(indent.len())
+                            .take_while(|b| *b == b' ')
+                            .count()
+                    }
+                });
+                if removed > 0 {
+                    editor.delete_range(Cursor::new(line_i, 0), Cursor::new(line_i, removed));
+                }
+                (removed, 0)
+            } else {
+                editor.insert_at(Cursor::new(line_i, 0), indent, None);
+                (0, indent.len())
+            };
+
+            let shift = |cursor: &mut Cursor| {
+                if cursor.line == line_i {
+                    cursor.index = cursor.index.saturating_sub(removed) + added;
+                }
+            };
+            shift(&mut cursor);
+            match &mut selection {
+                Selection::None => {}
+                Selection::Normal(select) | Selection::Line(select) | Selection::Word(select) => {
+                    shift(select)
+                }
+            }
+        }
+
+        editor.set_cursor(cursor);
+        editor.set_selection(selection);
+    }
+
+    /// Adapted from `bevy_ui::extract_uinode_text` and `bevy_ui::extract_uinode_background_colors`
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "render")]
+    pub fn extract_selection(
+        mut commands: Commands,
+        mut extracted_uinodes: ResMut<ExtractedUiNodes>,
+        camera_query: Extract<Query<(Entity, &Camera)>>,
+        default_ui_camera: Extract<DefaultUiCamera>,
+        ui_scale: Extract<Res<UiScale>>,
+        // TODO: the selection should be its own entity!?
+        uinode_query: Extract<
+            Query<
+                (
+                    &Node,
+                    &GlobalTransform,
+                    &ViewVisibility,
+                    Option<&CalculatedClip>,
+                    Option<&TargetCamera>,
+                    Option<&SelectionConfig>,
+                    Option<&FindHighlights>,
+                    &ComputedEditorVisuals,
+                ),
+                With<Text>,
+            >,
+        >,
+    ) {
+        for (
+            uinode,
+            global_transform,
+            view_visibility,
+            clip,
+            camera,
+            selection_config,
+            find_highlights,
+            computed_visuals,
+        ) in &uinode_query
+        {
+            if computed_visuals.has_no_highlights() {
+                continue;
+            };
+            let Some(camera_entity) = camera.map(TargetCamera::entity).or(default_ui_camera.get())
+            else {
+                continue;
+            };
+
+            // Skip if not visible or if size is set to zero (e.g. when a parent is set to `Display::None`)
+            if !view_visibility.get() || uinode.size().x == 0. || uinode.size().y == 0. {
+                continue;
+            }
+
+            let scale_factor = camera_query
+                .get(camera_entity)
+                .ok()
+                .and_then(|(_, c)| c.target_scaling_factor())
+                .unwrap_or(1.0)
+                * ui_scale.0;
+            let inverse_scale_factor = scale_factor.recip();
+
+            // Align the text to the nearest physical pixel:
+            // * Translate by minus the text node's half-size
+            //      (The transform translates to the center of the node but the text coordinates are relative to the node's top left corner)
+            // * Multiply the logical coordinates by the scale factor to get its position in physical coordinates
+            // * Round the physical position to the nearest physical pixel
+            // * Multiply by the rounded physical position by the inverse scale factor to return to logical coordinates
+
+            let logical_top_left = -0.5 * uinode.size();
+
+            let mut transform = global_transform.affine()
+                * bevy::math::Affine3A::from_translation(logical_top_left.extend(0.));
+
+            transform.translation *= scale_factor;
+            transform.translation = transform.translation.round();
+            transform.translation *= inverse_scale_factor;
+
+            let selection_config = match selection_config {
+                Some(c) => *c,
+                None => Default::default(),
+            };
+            let color = selection_config.color.into();
+            let (find_color, active_color) = FindHighlights::colors(find_highlights);
+
+            // highlight up to the edge of the node
+            let max_x = uinode.size().x * scale_factor;
+            let selection_rects = selection_config.padded_rects(
+                &computed_visuals.selection_rects,
+                scale_factor,
+                max_x,
+            );
+            // the selection is drawn over the find highlights, which are drawn over the layers
+            let highlights = computed_visuals
+                .layer_rects
+                .iter()
+                .map(|(rect, color)| (*rect, LinearRgba::from(*color)))
+                .chain(
+                    computed_visuals
+                        .find_rects
+                        .iter()
+                        .map(|rect| (*rect, find_color)),
+                )
+                .chain(
+                    computed_visuals
+                        .active_find_rects
+                        .iter()
+                        .map(|rect| (*rect, active_color)),
+                )
+                .chain(selection_rects.map(|rect| (rect, color)));
+            for (selection_rect, color) in highlights {
+                let selection_rect = snap_to_pixels(selection_rect);
+                let position = selection_rect.center();
+                let size = selection_rect.size() * inverse_scale_factor;
+                let corner_radius = selection_config
+                    .corner_radius
+                    .min(size.min_element() / 2.0)
+                    .max(0.0);
+                extracted_uinodes.uinodes.insert(
+                    commands.spawn_empty().id(),
+                    ExtractedUiNode {
+                        stack_index: uinode.stack_index(),
+                        transform: transform
+                            * Mat4::from_translation(position.extend(0.) * inverse_scale_factor),
+                        color,
+                        // the buffer is laid out in physical pixels, but the node is drawn in
+                        // logical pixels
+                        rect: Rect {
+                            min: Vec2::ZERO,
+                            max: size,
+                        },
+                        image: AssetId::default(),
+                        atlas_size: None,
+                        clip: clip.map(|clip| clip.clip),
+                        flip_x: false,
+                        flip_y: false,
+                        camera_entity,
+                        border: [0.; 4],
+                        border_radius: [corner_radius; 4],
+                        node_type: NodeType::Rect,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Adapted from `bevy_ui::extract_uinode_text`
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "render")]
+    pub fn extract_placeholder(
+        mut commands: Commands,
+        mut extracted_uinodes: ResMut<ExtractedUiNodes>,
+        camera_query: Extract<Query<(Entity, &Camera)>>,
+        default_ui_camera: Extract<DefaultUiCamera>,
+        texture_atlases: Extract<Res<Assets<TextureAtlasLayout>>>,
+        ui_scale: Extract<Res<UiScale>>,
+        uinode_query: Extract<
+            Query<
+                (
+                    &Node,
+                    &GlobalTransform,
+                    &ViewVisibility,
+                    Option<&CalculatedClip>,
+                    Option<&TargetCamera>,
+                    &Placeholder,
+                    &PlaceholderLayout,
+                    &CosmicBuffer,
+                ),
+                With<Text>,
+            >,
+        >,
+    ) {
+        for (
+            uinode,
+            global_transform,
+            view_visibility,
+            clip,
+            camera,
+            placeholder,
+            placeholder_layout,
+            buffer,
+        ) in &uinode_query
+        {
+            if !buffer_is_empty(buffer) {
+                continue;
+            }
+
+            let Some(camera_entity) = camera.map(TargetCamera::entity).or(default_ui_camera.get())
+            else {
+                continue;
+            };
+
+            // Skip if not visible or if size is set to zero (e.g. when a parent is set to `Display::None`)
+            if !view_visibility.get() || uinode.size().x == 0. || uinode.size().y == 0. {
+                continue;
+            }
+
+            let scale_factor = camera_query
+                .get(camera_entity)
+                .ok()
+                .and_then(|(_, c)| c.target_scaling_factor())
+                .unwrap_or(1.0)
+                * ui_scale.0;
+            let inverse_scale_factor = scale_factor.recip();
+
+            // Align the text to the nearest physical pixel:
+            // * Translate by minus the text node's half-size
+            //      (The transform translates to the center of the node but the text coordinates are relative to the node's top left corner)
+            // * Multiply the logical coordinates by the scale factor to get its position in physical coordinates
+            // * Round the physical position to the nearest physical pixel
+            // * Multiply by the rounded physical position by the inverse scale factor to return to logical coordinates
+
+            let logical_top_left = -0.5 * uinode.size();
+
+            let mut transform = global_transform.affine()
+                * bevy::math::Affine3A::from_translation(logical_top_left.extend(0.));
+
+            transform.translation *= scale_factor;
+            transform.translation = transform.translation.round();
+            transform.translation *= inverse_scale_factor;
+
+            let color = placeholder.style.color.into();
+
+            for PositionedGlyph {
+                position,
+                atlas_info,
+                ..
+            } in &placeholder_layout.info.glyphs
+            {
+                let Some(atlas) = texture_atlases.get(&atlas_info.texture_atlas) else {
+                    continue;
+                };
+
+                let mut rect = atlas.textures[atlas_info.glyph_index].as_rect();
+                rect.min *= inverse_scale_factor;
+                rect.max *= inverse_scale_factor;
+                extracted_uinodes.uinodes.insert(
+                    commands.spawn_empty().id(),
+                    ExtractedUiNode {
+                        stack_index: uinode.stack_index(),
+                        transform: transform
+                            * Mat4::from_translation(position.extend(0.) * inverse_scale_factor),
+                        color,
+                        rect,
+                        image: atlas_info.texture.id(),
+                        atlas_size: Some(atlas.size.as_vec2() * inverse_scale_factor),
+                        clip: clip.map(|clip| clip.clip),
+                        flip_x: false,
+                        flip_y: false,
+                        camera_entity,
+                        border: [0.; 4],
+                        border_radius: [0.; 4],
+                        node_type: NodeType::Rect,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Adapted from `bevy_ui::extract_uinode_text`
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "render")]
+    pub fn extract_line_numbers(
+        mut commands: Commands,
+        mut extracted_uinodes: ResMut<ExtractedUiNodes>,
+        camera_query: Extract<Query<(Entity, &Camera)>>,
+        default_ui_camera: Extract<DefaultUiCamera>,
+        texture_atlases: Extract<Res<Assets<TextureAtlasLayout>>>,
+        ui_scale: Extract<Res<UiScale>>,
+        uinode_query: Extract<
+            Query<
+                (
+                    &Node,
+                    &GlobalTransform,
+                    &ViewVisibility,
+                    Option<&CalculatedClip>,
+                    Option<&TargetCamera>,
+                    &LineNumbers,
+                    &LineNumbersLayout,
+                ),
+                With<Text>,
+            >,
+        >,
+    ) {
+        for (uinode, global_transform, view_visibility, clip, camera, line_numbers, layout) in
+            &uinode_query
+        {
+            if !line_numbers.enabled {
+                continue;
+            }
+
+            let Some(camera_entity) = camera.map(TargetCamera::entity).or(default_ui_camera.get())
+            else {
+                continue;
+            };
+
+            // Skip if not visible or if size is set to zero (e.g. when a parent is set to `Display::None`)
+            if !view_visibility.get() || uinode.size().x == 0. || uinode.size().y == 0. {
+                continue;
+            }
+
+            let scale_factor = camera_query
+                .get(camera_entity)
+                .ok()
+                .and_then(|(_, c)| c.target_scaling_factor())
+                .unwrap_or(1.0)
+                * ui_scale.0;
+            let inverse_scale_factor = scale_factor.recip();
+
+            // the gutter is to the left of the node, aligned to the nearest physical pixel like the
+            // text
+            let logical_top_left = -0.5 * uinode.size() - Vec2::X * line_numbers.width;
+
+            let mut transform = global_transform.affine()
+                * bevy::math::Affine3A::from_translation(logical_top_left.extend(0.));
+
+            transform.translation *= scale_factor;
+            transform.translation = transform.translation.round();
+            transform.translation *= inverse_scale_factor;
+
+            // rows scrolled partly out of the editor are cut off at its top and bottom
+            let center = global_transform.translation().truncate();
+            let gutter =
+                Rect::from_corners(center + logical_top_left, center + 0.5 * uinode.size());
+            let clip = clip.map_or(gutter, |clip| clip.clip.intersect(gutter));
+
+            let color = line_numbers.color.into();
+
+            for (row_top, info) in &layout.rows {
+                for PositionedGlyph {
+                    position,
+                    atlas_info,
+                    ..
+                } in &info.glyphs
+                {
+                    let Some(atlas) = texture_atlases.get(&atlas_info.texture_atlas) else {
+                        continue;
+                    };
+
+                    let position = *position + Vec2::Y * *row_top;
+                    let mut rect = atlas.textures[atlas_info.glyph_index].as_rect();
+                    rect.min *= inverse_scale_factor;
+                    rect.max *= inverse_scale_factor;
+                    extracted_uinodes.uinodes.insert(
+                        commands.spawn_empty().id(),
+                        ExtractedUiNode {
+                            stack_index: uinode.stack_index(),
+                            transform: transform
+                                * Mat4::from_translation(
+                                    position.extend(0.) * inverse_scale_factor,
+                                ),
+                            color,
+                            rect,
+                            image: atlas_info.texture.id(),
+                            atlas_size: Some(atlas.size.as_vec2() * inverse_scale_factor),
+                            clip: Some(clip),
+                            flip_x: false,
+                            flip_y: false,
+                            camera_entity,
+                            border: [0.; 4],
+                            border_radius: [0.; 4],
+                            node_type: NodeType::Rect,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Adapted from `bevy_ui::extract_uinode_background_colors`
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "render")]
+    pub fn extract_scrollbar(
+        mut commands: Commands,
+        mut extracted_uinodes: ResMut<ExtractedUiNodes>,
+        camera_query: Extract<Query<(Entity, &Camera)>>,
+        default_ui_camera: Extract<DefaultUiCamera>,
+        ui_scale: Extract<Res<UiScale>>,
+        uinode_query: Extract<
+            Query<(
+                &Node,
+                &GlobalTransform,
+                &ViewVisibility,
+                Option<&CalculatedClip>,
+                Option<&TargetCamera>,
+                &CosmicBuffer,
+                &EditorState,
+                &Scrollbar,
+            )>,
+        >,
+    ) {
+        for (
+            uinode,
+            global_transform,
+            view_visibility,
+            clip,
+            camera,
+            buffer,
+            editor_state,
+            scrollbar,
+        ) in &uinode_query
+        {
+            let Some(camera_entity) = camera.map(TargetCamera::entity).or(default_ui_camera.get())
+            else {
+                continue;
+            };
+
+            // Skip if not visible or if size is set to zero (e.g. when a parent is set to `Display::None`)
+            if !view_visibility.get() || uinode.size().x == 0. || uinode.size().y == 0. {
+                continue;
+            }
+
+            let scale_factor = camera_query
+                .get(camera_entity)
+                .ok()
+                .and_then(|(_, c)| c.target_scaling_factor())
+                .unwrap_or(1.0)
+                * ui_scale.0;
+
+            let Some((track, thumb)) =
+                scrollbar.rects(buffer, editor_state.scroll, uinode.size(), scale_factor)
+            else {
+                continue;
+            };
+
+            let transform = global_transform.compute_matrix()
+                * Mat4::from_translation((-0.5 * uinode.size()).extend(0.));
+
+            for (rect, color) in [
+                (track, scrollbar.track_color),
+                (thumb, scrollbar.thumb_color),
+            ] {
+                extracted_uinodes.uinodes.insert(
+                    commands.spawn_empty().id(),
+                    ExtractedUiNode {
+                        stack_index: uinode.stack_index(),
+                        transform: transform * Mat4::from_translation(rect.center().extend(0.)),
+                        color: color.into(),
+                        rect: Rect {
+                            min: Vec2::ZERO,
+                            max: rect.size(),
+                        },
+                        image: AssetId::default(),
+                        atlas_size: None,
+                        clip: clip.map(|clip| clip.clip),
+                        flip_x: false,
+                        flip_y: false,
+                        camera_entity,
+                        border: [0.; 4],
+                        border_radius: [rect.width() / 2.0; 4],
+                        node_type: NodeType::Rect,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Adapted from `bevy_text::extract_text2d_sprite`
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "render")]
+    pub fn extract_cursor_2d(
+        mut commands: Commands,
+        mut extracted_sprites: ResMut<ExtractedSprites>,
+        windows: Extract<Query<&Window, With<PrimaryWindow>>>,
+        text2d_query: Extract<
+            Query<
+                (
+                    Entity,
+                    &ViewVisibility,
+                    &Anchor,
+                    &GlobalTransform,
+                    Option<&CursorConfig>,
+                    Option<&CursorBlink>,
+                    &ComputedEditorVisuals,
+                    &EditorState,
+                ),
+                (With<Text>, Without<Node>),
+            >,
+        >,
+    ) {
+        let scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0);
+        let scaling = GlobalTransform::from_scale(Vec2::splat(scale_factor.recip()).extend(1.));
+
+        for (
+            original_entity,
+            view_visibility,
+            anchor,
+            global_transform,
+            cursor_config,
+            cursor_blink,
+            computed_visuals,
+            editor_state,
+        ) in &text2d_query
+        {
+            if computed_visuals.cursor_rect.is_none()
+                || !view_visibility.get()
+                || cursor_blink.is_some_and(|blink| !blink.visible)
+            {
+                continue;
+            }
+
+            // the buffer is laid out in physical pixels
+            let size = computed_visuals.buffer_size;
+            let text_anchor = -(anchor.as_vec() + 0.5);
+            let alignment_translation = size * scale_factor.recip() * text_anchor;
+            let transform = *global_transform
+                * GlobalTransform::from_translation(alignment_translation.extend(0.))
+                * scaling;
+
+            let cursor_config = cursor_config.copied().unwrap_or_default();
+            let color = cursor_config.current_color(editor_state.overwrite).into();
+
+            let cursor_boxes = computed_visuals
+                .cursor_rect
+                .map(|rect| (rect, computed_visuals.cursor_baseline))
+                .into_iter()
+                .chain(computed_visuals.secondary_cursor_boxes.iter().copied());
+            for (cursor_rect, cursor_baseline) in cursor_boxes {
+                let cursor_rect = snap_caret_to_pixels(cursor_config.cursor_rect(
+                    cursor_rect,
+                    cursor_baseline,
+                    editor_state.overwrite,
+                    scale_factor,
+                ));
+                let cursor_size = cursor_rect.size();
+                // centre of the cursor, flipped so that +Y is up
+                let position = Vec2::new(cursor_rect.center().x, size.y - cursor_rect.center().y);
+                extracted_sprites.sprites.insert(
+                    commands.spawn_empty().id(),
+                    ExtractedSprite {
+                        // just in front of the text
+                        transform: transform
+                            * GlobalTransform::from_translation(position.extend(0.001)),
+                        color,
+                        rect: None,
+                        custom_size: Some(cursor_size),
+                        image_handle_id: AssetId::default(),
+                        flip_x: false,
+                        flip_y: false,
+                        anchor: Anchor::Center.as_vec(),
+                        original_entity: Some(original_entity),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Adapted from `bevy_text::extract_text2d_sprite`
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "render")]
+    pub fn extract_selection_2d(
+        mut commands: Commands,
+        mut extracted_sprites: ResMut<ExtractedSprites>,
+        windows: Extract<Query<&Window, With<PrimaryWindow>>>,
+        text2d_query: Extract<
+            Query<
+                (
+                    Entity,
+                    &ViewVisibility,
+                    &Anchor,
+                    &GlobalTransform,
+                    Option<&SelectionConfig>,
+                    Option<&FindHighlights>,
+                    &ComputedEditorVisuals,
+                ),
+                (With<Text>, Without<Node>),
+            >,
+        >,
+    ) {
+        let scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0);
+        let scaling = GlobalTransform::from_scale(Vec2::splat(scale_factor.recip()).extend(1.));
+
+        for (
+            original_entity,
+            view_visibility,
+            anchor,
+            global_transform,
+            selection_config,
+            find_highlights,
+            computed_visuals,
+        ) in &text2d_query
+        {
+            if computed_visuals.has_no_highlights() || !view_visibility.get() {
+                continue;
+            }
+
+            // the buffer is laid out in physical pixels
+            let size = computed_visuals.buffer_size;
+            let text_anchor = -(anchor.as_vec() + 0.5);
+            let alignment_translation = size * scale_factor.recip() * text_anchor;
+            let transform = *global_transform
+                * GlobalTransform::from_translation(alignment_translation.extend(0.))
+                * scaling;
+
+            let selection_config = selection_config.copied().unwrap_or_default();
+            let color = selection_config.color.into();
+            let (find_color, active_color) = FindHighlights::colors(find_highlights);
+
+            let selection_rects = selection_config.padded_rects(
+                &computed_visuals.selection_rects,
+                scale_factor,
+                size.x,
+            );
+            // the selection is drawn over the find highlights, which are drawn over the layers (each
+            // layer over the last)
+            let layer_count = computed_visuals.layer_rects.len().max(1) as f32;
+            let highlights = computed_visuals
+                .layer_rects
+                .iter()
+                .enumerate()
+                .map(|(i, (rect, color))| {
+                    (
+                        *rect,
+                        LinearRgba::from(*color),
+                        -0.003 + 0.0005 * i as f32 / layer_count,
+                    )
+                })
+                .chain(
+                    computed_visuals
+                        .find_rects
+                        .iter()
+                        .map(|rect| (*rect, find_color, -0.002)),
+                )
+                .chain(
+                    computed_visuals
+                        .active_find_rects
+                        .iter()
+                        .map(|rect| (*rect, active_color, -0.0015)),
+                )
+                .chain(selection_rects.map(|rect| (rect, color, -0.001)));
+            for (selection_rect, color, z) in highlights {
+                let selection_rect = snap_to_pixels(selection_rect);
+                let highlight_size = selection_rect.size();
+                // centre of the highlight, flipped so that +Y is up
+                let position = Vec2::new(
+                    selection_rect.center().x,
+                    size.y - selection_rect.center().y,
+                );
+                extracted_sprites.sprites.insert(
+                    commands.spawn_empty().id(),
+                    ExtractedSprite {
+                        // just behind the text
+                        transform: transform
+                            * GlobalTransform::from_translation(position.extend(z)),
+                        color,
+                        rect: None,
+                        custom_size: Some(highlight_size),
+                        image_handle_id: AssetId::default(),
+                        flip_x: false,
+                        flip_y: false,
+                        anchor: Anchor::Center.as_vec(),
+                        original_entity: Some(original_entity),
+                    },
+                );
+            }
+        }
+    }
+
+    /// `rect`, in physical pixels, with its corners moved to the nearest pixel, so that it's drawn
+    /// crisply at fractional scale factors and neighbouring highlights still meet
+    #[cfg(feature = "render")]
+    fn snap_to_pixels(rect: Rect) -> Rect {
+        Rect::from_corners(rect.min.round(), rect.max.round())
+    }
+
+    /// The caret `rect`, in physical pixels, moved to the nearest pixel and sized in whole pixels
+    ///
+    /// Unlike [`snap_to_pixels`], the size doesn't depend on the position, so the caret doesn't
+    /// shimmer as it moves.
+    fn snap_caret_to_pixels(rect: Rect) -> Rect {
+        let min = rect.min.round();
+        Rect::from_corners(min, min + rect.size().round().max(Vec2::ONE))
+    }
+
+    /// Where the caret `cursor_rect` (in the buffer's physical pixels) of a UI node whose top left
+    /// corner is at `top_left` is drawn, in logical pixels
+    ///
+    /// Both the node and the caret are aligned to the nearest physical pixel, like the text.
+    fn ui_caret_rect(top_left: Vec2, cursor_rect: Rect, scale_factor: f32) -> Rect {
+        let cursor_rect = snap_caret_to_pixels(cursor_rect);
+        let min = ((top_left * scale_factor).round() + cursor_rect.min) / scale_factor;
+        Rect::from_corners(min, min + cursor_rect.size() / scale_factor)
+    }
+
+    /// The size of the laid out text, in the buffer's coordinates
+    pub fn buffer_dimensions(buffer: &Buffer) -> Vec2 {
+        let (width, height) = buffer
+            .layout_runs()
+            .fold((0.0f32, 0.0f32), |(width, height), run| {
+                (width.max(run.line_w), height + run.line_height)
+            });
+        Vec2::new(width, height)
+    }
+
+    /// Whether the buffer has no text in it
+    pub fn buffer_is_empty(buffer: &Buffer) -> bool {
+        buffer.lines.iter().all(|line| line.text().is_empty())
+    }
+
+    /// The number of characters in the buffer, including line endings
+    pub fn buffer_char_count(buffer: &Buffer) -> usize {
+        buffer
+            .lines
+            .iter()
+            .map(|line| line.text().chars().count() + line.ending().as_str().chars().count())
+            .sum()
+    }
+
+    /// The cursor at the end of the last line of the buffer
+    pub fn buffer_end(buffer: &Buffer) -> Cursor {
+        match buffer.lines.last() {
+            Some(line) => Cursor::new(buffer.lines.len() - 1, line.text().len()),
+            None => Cursor::new(0, 0),
+        }
+    }
+
+    /// The byte offset of `cursor` in the text of `buffer`, counting line endings
+    fn text_offset(buffer: &Buffer, cursor: Cursor) -> usize {
+        buffer.lines[..cursor.line]
+            .iter()
+            .map(|line| line.text().len() + line.ending().as_str().len())
+            .sum::<usize>()
+            + cursor.index
+    }
+
+    /// The cursor `offset` bytes into the text of `buffer` (see [`text_offset`]), clamped to the
+    /// end of the buffer
+    fn cursor_at_offset(buffer: &Buffer, mut offset: usize) -> Cursor {
+        for (line_index, line) in buffer.lines.iter().enumerate() {
+            let text = line.text();
+            if offset <= text.len() {
+                let index = (0..=offset)
+                    .rev()
+                    .find(|index| text.is_char_boundary(*index))
+                    .unwrap_or(0);
+                return Cursor::new(line_index, index);
+            }
+            let line_len = text.len() + line.ending().as_str().len();
+            if offset < line_len {
+                return Cursor::new(line_index, text.len());
+            }
+            offset -= line_len;
+        }
+        buffer_end(buffer)
+    }
+
+    /// Applies `edit` at the cursor of `editor` and at each of `secondary`, returning where the
+    /// secondary cursors end up, see [`EditorState::secondary_cursors`]
+    ///
+    /// The cursors are edited from the last to the first, so that the earlier ones stay valid, and
+    /// each edit moves the cursors after it by however much it changed the length of the text.
+    /// Cursors that meet are merged.
+    fn edit_at_cursors(
+        editor: &mut Editor,
+        secondary: &[Cursor],
+        mut edit: impl FnMut(&mut Editor),
+    ) -> Vec<Cursor> {
+        let primary = editor.cursor();
+        let offset = |editor: &Editor| {
+            let cursor = editor.cursor();
+            editor.with_buffer(|buffer| text_offset(buffer, cursor))
+        };
+        let len =
+            |editor: &Editor| editor.with_buffer(|buffer| text_offset(buffer, buffer_end(buffer)));
+        let mut cursors: Vec<Cursor> = secondary.iter().copied().chain([primary]).collect();
+        cursors.sort_by_key(|cursor| (cursor.line, cursor.index));
+        cursors.dedup_by_key(|cursor| (cursor.line, cursor.index));
+
+        // where each cursor ends up, from the last
+        let mut offsets: Vec<(bool, usize)> = Vec::with_capacity(cursors.len());
+        for cursor in cursors.into_iter().rev() {
+            let len_before = len(editor);
+            editor.set_cursor(cursor);
+            edit(editor);
+            let edited = offset(editor);
+            let len_after = len(editor);
+            for (_, later) in &mut offsets {
+                // text deleted up to a later cursor moves it back to this one
+                *later = (*later + len_after).saturating_sub(len_before).max(edited);
+            }
+            let is_primary = (cursor.line, cursor.index) == (primary.line, primary.index);
+            offsets.push((is_primary, edited));
+        }
+
+        let primary_offset = offsets
+            .iter()
+            .find_map(|(is_primary, offset)| is_primary.then_some(*offset))
+            .unwrap_or_default();
+        let mut secondary: Vec<usize> = offsets
+            .into_iter()
+            .rev()
+            .filter(|(is_primary, offset)| !is_primary && *offset != primary_offset)
+            .map(|(_, offset)| offset)
+            .collect();
+        secondary.dedup();
+        let primary = editor.with_buffer(|buffer| cursor_at_offset(buffer, primary_offset));
+        editor.set_cursor(primary);
+        editor.with_buffer(|buffer| {
+            secondary
+                .into_iter()
+                .map(|offset| cursor_at_offset(buffer, offset))
+                .collect()
+        })
+    }
+
+    /// The start and end of each match of `query` in the buffer, in order and not overlapping
+    ///
+    /// The lines of the buffer are joined with `\n`, so a query with line endings matches across
+    /// lines. Each match can be used as [`EditorState::selection_bounds`], e.g. to select it, or
+    /// drawn with [`FindHighlights`].
+    pub fn find_all(buffer: &Buffer, query: &str, case_sensitive: bool) -> Vec<(Cursor, Cursor)> {
+        let query = LineEnding::normalize(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut text = String::new();
+        // the offset of each line in `text`
+        let mut line_starts = Vec::with_capacity(buffer.lines.len());
+        for (i, line) in buffer.lines.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            line_starts.push(text.len());
+            text.push_str(line.text());
+        }
+        let to_cursor = |offset: usize| {
+            let line = line_starts.partition_point(|start| *start <= offset) - 1;
+            Cursor::new(line, offset - line_starts[line])
+        };
+
+        let mut matches = Vec::new();
+        let mut offset = 0;
+        while let Some(c) = text[offset..].chars().next() {
+            match match_len(&text[offset..], &query, case_sensitive) {
+                Some(len) => {
+                    matches.push((to_cursor(offset), to_cursor(offset + len)));
+                    offset += len;
+                }
+                None => offset += c.len_utf8(),
+            }
+        }
+        matches
+    }
+
+    /// The length in bytes of the match of `query` at the start of `text`, if there is one
+    fn match_len(text: &str, query: &str, case_sensitive: bool) -> Option<usize> {
+        if case_sensitive {
+            return text.starts_with(query).then_some(query.len());
+        }
+        let mut chars = text.chars();
+        let mut len = 0;
+        for query_char in query.chars() {
+            let c = chars.next()?;
+            if !c.to_lowercase().eq(query_char.to_lowercase()) {
+                return None;
+            }
+            len += c.len_utf8();
+        }
+        Some(len)
+    }
+
+    /// Replaces each match of `query` (see [`find_all`]) with `replacement`, returning the number
+    /// of matches replaced
+    ///
+    /// Like [`EditorState::set_text`], both the [`Text`] and the buffer are updated. Each
+    /// replacement takes the style of the section its match starts in.
+    pub fn replace_all(
+        buffer: &mut CosmicBuffer,
+        text: &mut Text,
+        pipeline: &mut TextPipeline,
+        query: &str,
+        replacement: &str,
+        case_sensitive: bool,
+    ) -> usize {
+        let matches = find_all(buffer, query, case_sensitive);
+        if matches.is_empty() {
+            return 0;
+        }
+        let replacement = LineEnding::normalize(replacement);
+
+        // the offset of each line in the text, which keeps its line endings
+        let line_starts: Vec<usize> = buffer
+            .lines
+            .iter()
+            .scan(0, |offset, line| {
+                let start = *offset;
+                *offset += line.text().len() + line.ending().as_str().len();
+                Some(start)
+            })
+            .collect();
+        let to_offset = |cursor: Cursor| line_starts[cursor.line] + cursor.index;
+        // replaced from the end, so that the offsets of earlier matches stay valid
+        for (start, end) in matches.iter().rev() {
+            replace_in_sections(
+                &mut text.sections,
+                to_offset(*start)..to_offset(*end),
+                &replacement,
+            );
+        }
+
+        let font_system = pipeline.font_system_mut();
+        let mut editor = Editor::new(&mut **buffer);
+        for (start, end) in matches.iter().rev() {
+            editor.set_cursor(*start);
+            editor.set_selection(Selection::Normal(*end));
+            insert_lines(&mut editor, font_system, &replacement, None);
+        }
+        editor.shape_as_needed(font_system, false);
+        matches.len()
+    }
+
+    /// Replaces the bytes in `range` of the text of `sections` with `replacement`, which goes into
+    /// the section that `range` starts in
+    fn replace_in_sections(
+        sections: &mut [TextSection],
+        range: std::ops::Range<usize>,
+        replacement: &str,
+    ) {
+        let last = sections.len().saturating_sub(1);
+        let mut offset = 0;
+        let mut replaced = false;
+        for (i, section) in sections.iter_mut().enumerate() {
+            let len = section.value.len();
+            let start = range.start.clamp(offset, offset + len) - offset;
+            let end = range.end.clamp(offset, offset + len) - offset;
+            if !replaced && (range.start < offset + len || i == last) {
+                section.value.replace_range(start..end, replacement);
+                replaced = true;
+            } else if start < end {
+                section.value.replace_range(start..end, "");
+            }
+            offset += len;
+        }
+    }
+
+    /// The start and end of the word at `cursor`, found with `config` or the default
+    /// [`WordBoundaryConfig`]
+    pub fn word_bounds(
+        buffer: &Buffer,
+        cursor: Cursor,
+        config: Option<&WordBoundaryConfig>,
+    ) -> (Cursor, Cursor) {
+        let default_config = WordBoundaryConfig::default();
+        let config = config.unwrap_or(&default_config);
+        let word = buffer
+            .lines
+            .get(cursor.line)
+            .map_or(cursor.index..cursor.index, |line| {
+                config.word_at(line.text(), cursor.index)
+            });
+        (
+            Cursor::new(cursor.line, word.start),
+            Cursor::new(cursor.line, word.end),
+        )
+    }
+
+    /// The start and end of the logical `line`, excluding its line ending
+    pub fn logical_line_bounds(buffer: &Buffer, line: usize) -> (Cursor, Cursor) {
+        let len = buffer.lines.get(line).map_or(0, |line| line.text().len());
+        (Cursor::new(line, 0), Cursor::new(line, len))
+    }
+
+    /// The start and end of the laid out (visual) line containing `cursor`
+    ///
+    /// Falls back to the logical line if the line isn't laid out.
+    pub fn visual_line_bounds(buffer: &Buffer, cursor: Cursor) -> (Cursor, Cursor) {
+        let mut runs = buffer
+            .layout_runs()
+            .filter(|run| run.line_i == cursor.line)
+            .peekable();
+        while let Some(run) = runs.next() {
+            let (Some(first), Some(last)) = (run.glyphs.first(), run.glyphs.last()) else {
+                continue;
+            };
+            let (start, end) = (first.start.min(last.start), first.end.max(last.end));
+            // the cursor at the end of a wrapped run is at the start of the next run
+            let is_last = runs.peek().is_none();
+            if cursor.index >= start && (cursor.index < end || (is_last && cursor.index == end)) {
+                return (
+                    Cursor::new(cursor.line, start),
+                    Cursor::new(cursor.line, end),
+                );
+            }
+        }
+        logical_line_bounds(buffer, cursor.line)
+    }
+
+    /// The start and end of the paragraph containing `line`, i.e. the block of lines between blank
+    /// lines
+    pub fn paragraph_bounds(buffer: &Buffer, line: usize) -> (Cursor, Cursor) {
+        let is_blank = |i: usize| buffer.lines[i].text().trim().is_empty();
+        if buffer.lines.is_empty() {
+            return (Cursor::new(0, 0), Cursor::new(0, 0));
+        }
+        let line = line.min(buffer.lines.len() - 1);
+        if is_blank(line) {
+            return (
+                Cursor::new(line, 0),
+                Cursor::new(line, buffer.lines[line].text().len()),
+            );
+        }
+        let first = (0..line)
+            .rev()
+            .take_while(|i| !is_blank(*i))
+            .last()
+            .unwrap_or(line);
+        let last = (line + 1..buffer.lines.len())
+            .take_while(|i| !is_blank(*i))
+            .last()
+            .unwrap_or(line);
+        (
+            Cursor::new(first, 0),
+            Cursor::new(last, buffer.lines[last].text().len()),
+        )
+    }
+
+    // from cosmic-text/src/edit/editor.rs:66
+    pub fn cursor_position(
+        cursor: &Cursor,
+        run: &LayoutRun,
+        buffer: &Buffer,
+    ) -> Option<(i32, i32)> {
+        let (cursor_glyph, cursor_glyph_offset) = cursor_glyph_opt(cursor, run)?;
+        let x = match run.glyphs.get(cursor_glyph) {
+            Some(glyph) => {
+                // Start of detected glyph
+                if glyph.level.is_rtl() {
+                    (glyph.x + glyph.w - cursor_glyph_offset) as i32
+                } else {
+                    (glyph.x + cursor_glyph_offset) as i32
+                }
+            }
+            None => match run.glyphs.last() {
+                Some(glyph) => {
+                    // End of last glyph
+                    if glyph.level.is_rtl() {
+                        glyph.x as i32
+                    } else {
+                        (glyph.x + glyph.w) as i32
+                    }
+                }
+                None => empty_line_x(buffer, run),
+            },
+        };
+
+        Some((x, run.line_top as i32))
+    }
+
+    /// Where the cursor is on an empty line, following the alignment of the line (from the
+    /// [`JustifyText`] of the [`Text`]) and the direction of the run
+    ///
+    /// Lines are aligned within the width of the buffer, or of its widest line if it has none.
+    fn empty_line_x(buffer: &Buffer, run: &LayoutRun) -> i32 {
+        let width = buffer
+            .size()
+            .0
+            .unwrap_or_else(|| buffer_dimensions(buffer).x);
+        let right = match buffer.lines.get(run.line_i).and_then(BufferLine::align) {
+            Some(Align::Left) => false,
+            Some(Align::Right) => true,
+            Some(Align::Center) => return (width / 2.0) as i32,
+            Some(Align::End) => !run.rtl,
+            Some(Align::Justified) | None => run.rtl,
+        };
+        if right {
+            width as i32
+        } else {
+            0
+        }
+    }
+
+    // adapted from cosmic-text/src/edit/editor.rs:?
+    pub fn highlight_selection(
+        selection_bounds: Option<(Cursor, Cursor)>,
+        buffer_width: Option<f32>,
+        empty_line_style: EmptyLineSelection,
+        buffer: &Buffer,
+        run: &LayoutRun,
+    ) -> Option<(i32, i32, u32)> {
+        let line_i = run.line_i;
+        let line_top = run.line_top;
+
+        // Highlight selection
+        if let Some((start, end)) = selection_bounds {
+            if line_i >= start.line && line_i <= end.line {
+                let mut range_opt = None;
+                for glyph in run.glyphs.iter() {
+                    // Guess x offset based on characters
+                    let cluster = &run.text[glyph.start..glyph.end];
+                    let total = cluster.grapheme_indices(true).count();
+                    let mut c_x = glyph.x;
+                    let c_w = glyph.w / total as f32;
+                    for (i, c) in cluster.grapheme_indices(true) {
+                        let c_start = glyph.start + i;
+                        let c_end = glyph.start + i + c.len();
+                        if (start.line != line_i || c_end > start.index)
+                            && (end.line != line_i || c_start < end.index)
+                        {
+                            range_opt = match range_opt.take() {
+                                Some((min, max)) => Some((
+                                    cmp::min(min, c_x as i32),
+                                    cmp::max(max, (c_x + c_w) as i32),
+                                )),
+                                None => Some((c_x as i32, (c_x + c_w) as i32)),
+                            };
+                        } else if let Some((min, max)) = range_opt.take() {
+                            return Some((min, line_top as i32, cmp::max(0, max - min) as u32));
+                        }
+                        c_x += c_w;
+                    }
+                }
+
+                if run.glyphs.is_empty() && end.line > line_i {
+                    match empty_line_style {
+                        // Highlight all of internal empty lines
+                        EmptyLineSelection::FullWidth => {
+                            range_opt = Some((0, buffer_width.unwrap_or(0.0) as i32));
+                        }
+                        // Or just where the cursor would be, kept inside the line
+                        EmptyLineSelection::Marker => {
+                            let width = (run.line_height / 4.0).ceil() as i32;
+                            let x = empty_line_x(buffer, run);
+                            let max_x = buffer
+                                .size()
+                                .0
+                                .unwrap_or_else(|| buffer_dimensions(buffer).x)
+                                as i32;
+                            let min = (x - width / 2).clamp(0, cmp::max(0, max_x - width));
+                            return Some((min, line_top as i32, width as u32));
+                        }
+                    }
+                }
+
+                if let Some((mut min, mut max)) = range_opt.take() {
+                    if end.line > line_i {
+                        // Draw to end of line
+                        if run.rtl {
+                            min = 0;
+                        } else {
+                            max = buffer_width.unwrap_or(0.0) as i32;
+                        }
+                    }
+                    return Some((min, line_top as i32, cmp::max(0, max - min) as u32));
+                }
+            }
+        }
+        None
+    }
+
+    /// One highlight per run of the text between `bounds`, highlighted up to `buffer_width` where
+    /// the range continues onto the next line, in the buffer's coordinates
+    fn highlight_boxes(
+        buffer: &Buffer,
+        bounds: (Cursor, Cursor),
+        buffer_width: Option<f32>,
+        empty_line_style: EmptyLineSelection,
+        scroll: Scroll,
+    ) -> Vec<Rect> {
+        // drawn where the scrolled glyphs are
+        let horizontal = Vec2::X * scroll.horizontal;
+        buffer
+            .layout_runs()
+            .filter_map(|run| {
+                let (x, y, width) = highlight_selection(
+                    Some(bounds),
+                    buffer_width,
+                    empty_line_style,
+                    buffer,
+                    &run,
+                )?;
+                let min = Vec2::new(x as f32, y as f32) - horizontal;
+                Some(Rect::from_corners(
+                    min,
+                    min + Vec2::new(width as f32, run.line_height),
+                ))
+            })
+            .collect()
+    }
+
+    /// The width of the grapheme after the cursor, or 0 at the end of the line
+    pub fn cursor_grapheme_width(cursor: &Cursor, run: &LayoutRun) -> f32 {
+        let Some(glyph) =
+            cursor_glyph_opt(cursor, run).and_then(|(glyph_i, _)| run.glyphs.get(glyph_i))
+        else {
+            return 0.0;
+        };
+        let total = run.text[glyph.start..glyph.end].graphemes(true).count();
+        glyph.w / total.max(1) as f32
+    }
+
+    // from cosmic-text/src/edit/editor.rs:30
+    /// The glyph the cursor is before in `run`, and how far into that glyph the cursor is
+    ///
+    /// Where one glyph is shaped from several graphemes (such as a ligature), cosmic-text doesn't
+    /// say where each grapheme is drawn, so the offset is guessed by dividing the glyph's width
+    /// evenly between its graphemes. This can misplace the caret inside ligatures and complex
+    /// scripts. Graphemes are extended grapheme clusters, so a flag (🇦🇺) or a ZWJ sequence
+    /// (👨‍👩‍👧) shaped as one glyph is a single grapheme, and a cursor inside one is placed
+    /// before it rather than in the middle of the emoji.
+    pub fn cursor_glyph_opt(cursor: &Cursor, run: &LayoutRun) -> Option<(usize, f32)> {
+        if cursor.line == run.line_i {
+            for (glyph_i, glyph) in run.glyphs.iter().enumerate() {
+                if cursor.index == glyph.start {
+                    return Some((glyph_i, 0.0));
+                } else if cursor.index > glyph.start && cursor.index < glyph.end {
+                    // Guess x offset based on characters
+                    let mut before = 0;
+                    let mut total = 0;
+
+                    let cluster = &run.text[glyph.start..glyph.end];
+                    for (i, grapheme) in cluster.grapheme_indices(true) {
+                        // only whole graphemes are before the cursor, so it never lands inside one
+                        if glyph.start + i + grapheme.len() <= cursor.index {
+                            before += 1;
+                        }
+                        total += 1;
+                    }
+
+                    let offset = glyph.w * (before as f32) / (total as f32);
+                    return Some((glyph_i, offset));
+                }
+            }
+            match run.glyphs.last() {
+                Some(glyph) => {
+                    if cursor.index == glyph.end {
+                        return Some((run.glyphs.len(), 0.0));
+                    }
+                }
+                None => {
+                    return Some((0, 0.0));
+                }
+            }
+        }
+        None
+    }
+
+    /// The state of an editor that's kept between frames
+    ///
+    /// cosmic-text's [`Editor`] borrows the buffer, so it can't be kept in a component or
+    /// resource. Instead, an [`Editor`] is made for each action from this state (see
+    /// [`TempEditor`]), and this state is read back from it afterwards.
+    ///
+    /// cosmic-text's types aren't reflectable, so only the other fields are reflected.
+    #[derive(Component, Clone, Debug, PartialEq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct EditorState {
+        /// The cursor, including its affinity (which side of a line wrap or bidi boundary it's on)
+        ///
+        /// The cursor and selection are only changed through the editor (see
+        /// [`EditorState::set_cursor`] and [`EditorState::set_selection`]), which keeps them
+        /// consistent with each other and valid in the buffer.
+        #[reflect(ignore)]
+        cursor: Option<Cursor>,
+        #[reflect(ignore)]
+        selection: Selection,
+        #[reflect(ignore)]
+        selection_bounds: Option<(Cursor, Cursor)>,
+        /// Persisted separately, as the buffer's scroll is reset whenever the text is laid out
+        #[reflect(ignore)]
+        pub scroll: Scroll,
+        /// Whether typed characters replace the character after the cursor, toggled by Insert
+        pub overwrite: bool,
+        /// The x position that moving up and down aims for, so that moving through a shorter line
+        /// returns to the same column
+        ///
+        /// This is kept by vertical motions and cleared whenever the cursor moves otherwise.
+        pub preferred_x: Option<i32>,
+        #[reflect(ignore)]
+        secondary_cursors: Vec<Cursor>,
+        #[reflect(ignore)]
+        drop_cursor: Option<Cursor>,
+    }
+
+    /// The text, cursor and selection of an editor, from [`EditorState::snapshot`]
+    ///
+    /// Serializable with the `serde` feature. Cursors are stored as the line and the byte index in
+    /// that line.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct EditorSnapshot {
+        /// The value of each section of the text
+        pub sections: Vec<String>,
+        pub cursor: Option<(usize, usize)>,
+        pub selection_bounds: Option<((usize, usize), (usize, usize))>,
+    }
+
+    impl Default for EditorState {
+        fn default() -> Self {
+            Self {
+                cursor: None,
+                selection: Selection::None,
+                selection_bounds: None,
+                scroll: Scroll::default(),
+                overwrite: false,
+                preferred_x: None,
+                secondary_cursors: Vec::new(),
+                drop_cursor: None,
+            }
+        }
+    }
+
+    impl EditorState {
+        fn resume<'es, 'buf>(&'es mut self, buffer: &'buf mut Buffer) -> TempEditor<'es, 'buf> {
+            TempEditor::new(self, buffer)
+        }
+
+        /// The cursor, or `None` while the editor hasn't been edited
+        pub fn cursor(&self) -> Option<Cursor> {
+            self.cursor
+        }
+
+        /// Where the selection is anchored, and whether it selects by character, word or line
+        pub fn selection(&self) -> Selection {
+            self.selection
+        }
+
+        /// The start and end of the selected text, or `None` if nothing is selected
+        pub fn selection_bounds(&self) -> Option<(Cursor, Cursor)> {
+            self.selection_bounds
+        }
+
+        /// Moves the cursor to `cursor`, clamped to the buffer, clearing the selection
+        pub fn set_cursor(&mut self, buffer: &mut CosmicBuffer, cursor: Cursor) {
+            let cursor = clamp_cursor(buffer, cursor);
+            self.place_cursor(buffer, cursor);
+        }
+
+        /// Selects the text from `anchor` to `cursor`, both clamped to the buffer, with the cursor
+        /// at `cursor`
+        pub fn set_selection(&mut self, buffer: &mut CosmicBuffer, anchor: Cursor, cursor: Cursor) {
+            let (anchor, cursor) = (clamp_cursor(buffer, anchor), clamp_cursor(buffer, cursor));
+            self.resume(buffer).with_editor_mut(|editor| {
+                editor.set_cursor(cursor);
+                editor.set_selection(if anchor == cursor {
+                    Selection::None
+                } else {
+                    Selection::Normal(anchor)
+                });
+            });
+            self.keep_cursor_visible(buffer, cursor);
+        }
+
+        /// More cursors, each added with Ctrl+click, in order
+        ///
+        /// Typing, Backspace, Delete and Enter edit at every cursor, while there's no selection.
+        /// Anything else that moves the cursor or edits the text clears them. Unlike the cursor,
+        /// these don't have selections of their own, so there's no block selection (e.g. with
+        /// Alt+drag) yet.
+        pub fn secondary_cursors(&self) -> &[Cursor] {
+            &self.secondary_cursors
+        }
+
+        /// Replaces the secondary cursors with `cursors`, each clamped to the buffer
+        ///
+        /// Cursors at the cursor or at another of `cursors` are dropped.
+        pub fn set_secondary_cursors(
+            &mut self,
+            buffer: &CosmicBuffer,
+            cursors: impl IntoIterator<Item = Cursor>,
+        ) {
+            let position = |cursor: &Cursor| (cursor.line, cursor.index);
+            let primary = self.cursor.as_ref().map(position);
+            let mut cursors: Vec<Cursor> = cursors
+                .into_iter()
+                .map(|cursor| clamp_cursor(buffer, cursor))
+                .filter(|cursor| Some(position(cursor)) != primary)
+                .collect();
+            cursors.sort_by_key(position);
+            cursors.dedup_by_key(|cursor| position(cursor));
+            self.secondary_cursors = cursors;
+        }
+
+        /// Where the selection would be dropped while it's dragged, see [`TextDrag`]
+        pub fn drop_cursor(&self) -> Option<Cursor> {
+            self.drop_cursor
+        }
+
+        /// Moves where the selection would be dropped to `cursor`, clamped to the buffer
+        pub fn set_drop_cursor(&mut self, buffer: &CosmicBuffer, cursor: Option<Cursor>) {
+            self.drop_cursor = cursor.map(|cursor| clamp_cursor(buffer, cursor));
+        }
+
+        /// Replaces the text of the editor with `value`, styled like the first section
+        ///
+        /// Both the [`Text`] and the buffer are updated, the cursor is moved to the end of the new
+        /// text, and the selection is cleared.
+        pub fn set_text(
+            &mut self,
+            buffer: &mut CosmicBuffer,
+            text: &mut Text,
+            pipeline: &mut TextPipeline,
+            value: &str,
+        ) {
+            let style = text
+                .sections
+                .first()
+                .map(|section| section.style.clone())
+                .unwrap_or_default();
+            text.sections = vec![TextSection::new(value, style)];
+            self.secondary_cursors.clear();
+
+            let font_system = pipeline.font_system_mut();
+            self.resume(buffer).with_editor_mut(|editor| {
+                // the current cursor may not be valid, so select everything explicitly
+                let has_lines = editor.with_buffer(|buffer| !buffer.lines.is_empty());
+                editor.set_cursor(Cursor::new(0, 0));
+                if has_lines {
+                    editor.set_selection(Selection::Normal(editor.with_buffer(buffer_end)));
+                    editor.delete_selection();
+                }
+                editor.insert_string(value, None);
+                editor.shape_as_needed(font_system, false);
+            });
+        }
+
+        /// The text, cursor and selection of the editor, e.g. to save a draft and
+        /// [restore](EditorState::restore) it later
+        pub fn snapshot(&self, text: &Text) -> EditorSnapshot {
+            let line_index = |cursor: Cursor| (cursor.line, cursor.index);
+            EditorSnapshot {
+                sections: text
+                    .sections
+                    .iter()
+                    .map(|section| section.value.clone())
+                    .collect(),
+                cursor: self.cursor.map(line_index),
+                selection_bounds: self
+                    .selection_bounds
+                    .map(|(start, end)| (line_index(start), line_index(end))),
+            }
+        }
+
+        /// Replaces the text of the editor with the text of `snapshot`, and restores its cursor and
+        /// selection
+        ///
+        /// Each section keeps the style of the section at the same index, or of the last section.
+        /// The cursor and selection are clamped to the restored text, in case the snapshot was
+        /// edited or is from an older version.
+        pub fn restore(
+            &mut self,
+            snapshot: &EditorSnapshot,
+            buffer: &mut CosmicBuffer,
+            text: &mut Text,
+            pipeline: &mut TextPipeline,
+        ) {
+            let value: String = snapshot.sections.concat();
+            let styles: Vec<TextStyle> = text
+                .sections
+                .iter()
+                .map(|section| section.style.clone())
+                .collect();
+            self.set_text(buffer, text, pipeline, &value);
+            text.sections = snapshot
+                .sections
+                .iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    let style = styles.get(i).or(styles.last()).cloned();
+                    TextSection::new(value.clone(), style.unwrap_or_default())
+                })
+                .collect();
+
+            let cursor = |(line, index)| Cursor::new(line, index);
+            match (snapshot.cursor.map(cursor), snapshot.selection_bounds) {
+                (Some(cursor_at), Some((start, end))) => {
+                    let (start, end) = (cursor(start), cursor(end));
+                    // the selection is anchored at whichever end the cursor isn't at
+                    let anchor = if cursor_at == start { end } else { start };
+                    self.set_selection(buffer, anchor, cursor_at);
+                }
+                (Some(cursor_at), None) => self.set_cursor(buffer, cursor_at),
+                (None, _) => {}
+            }
+        }
+
+        /// Moves the cursor to grapheme `col` of `line` (both 0-based), clearing the selection
+        ///
+        /// Positions past the end of the buffer or line are clamped. Returns whether the position was
+        /// clamped.
+        pub fn set_cursor_line_col(
+            &mut self,
+            buffer: &mut CosmicBuffer,
+            line: usize,
+            col: usize,
+        ) -> bool {
+            let Some(last_line) = buffer.lines.len().checked_sub(1) else {
+                self.place_cursor(buffer, Cursor::new(0, 0));
+                return line != 0 || col != 0;
+            };
+            let line_i = line.min(last_line);
+            let text = buffer.lines[line_i].text();
+            let (index, col_clamped) = match text.grapheme_indices(true).nth(col) {
+                Some((index, _)) => (index, false),
+                None => (text.len(), col > text.graphemes(true).count()),
+            };
+            self.place_cursor(buffer, Cursor::new(line_i, index));
+            line_i != line || col_clamped
+        }
+
+        /// Moves the cursor to byte `offset` of the text (including line endings), clearing the
+        /// selection
+        ///
+        /// Offsets inside a grapheme are clamped to its start, offsets inside a line ending to the end
+        /// of that line, and offsets past the end to the end of the buffer. Returns whether the offset
+        /// was clamped.
+        pub fn move_to_byte_offset(&mut self, buffer: &mut CosmicBuffer, offset: usize) -> bool {
+            let mut target = None;
+            let mut line_start = 0;
+            for (line_i, line) in buffer.lines.iter().enumerate() {
+                let text = line.text();
+                let line_end = line_start + text.len();
+                if offset <= line_end {
+                    let index = offset - line_start;
+                    let boundary = text
+                        .grapheme_indices(true)
+                        .map(|(i, _)| i)
+                        .chain(std::iter::once(text.len()))
+                        .take_while(|i| *i <= index)
+                        .last()
+                        .unwrap_or(0);
+                    target = Some((Cursor::new(line_i, boundary), boundary != index));
+                    break;
+                }
+                let next_line_start = line_end + line.ending().as_str().len();
+                if offset < next_line_start {
+                    target = Some((Cursor::new(line_i, text.len()), true));
+                    break;
+                }
+                line_start = next_line_start;
+            }
+            let (cursor, clamped) = target.unwrap_or_else(|| (buffer_end(buffer), true));
+            self.place_cursor(buffer, cursor);
+            clamped
+        }
+
+        /// Scrolls the editor so that logical `line` (0-based) is at the top, center or bottom of
+        /// the editor, e.g. to go to a line or a search result
+        ///
+        /// Lines past the end of the buffer are clamped, and the editor isn't scrolled past the
+        /// top or bottom of its text. The cursor isn't moved, and the buffer is shaped up to the
+        /// new scroll by [`restore_scroll`].
+        pub fn scroll_to_line(
+            &mut self,
+            buffer: &mut CosmicBuffer,
+            line: usize,
+            align: ScrollAlign,
+        ) {
+            let line = line.min(buffer.lines.len().saturating_sub(1));
+            // editors without a height can only be scrolled to the top
+            let height = buffer.size().1.unwrap_or(0.0);
+            let line_top = lines_height(buffer, 0..line);
+            let line_height = lines_height(buffer, line..line + 1);
+            let top = match align {
+                ScrollAlign::Top => line_top,
+                ScrollAlign::Center => line_top + (line_height - height) / 2.0,
+                ScrollAlign::Bottom => line_top + line_height - height,
+            };
+            let from_top = Scroll {
+                line: 0,
+                vertical: 0.0,
+                ..self.scroll
+            };
+            self.scroll = scroll_vertically(buffer, from_top, top, height);
+            buffer.set_scroll(self.scroll);
+        }
+
+        /// The number of characters in the text, including line endings, e.g. for a "12/100"
+        /// counter
+        ///
+        /// Characters are counted the way [`MaxLength`] counts them, see [`buffer_char_count`].
+        pub fn char_count(&self, buffer: &CosmicBuffer) -> usize {
+            buffer_char_count(buffer)
+        }
+
+        /// The number of lines in the text
+        pub fn line_count(&self, buffer: &CosmicBuffer) -> usize {
+            buffer.lines.len()
+        }
+
+        /// Whether the text is empty
+        pub fn is_empty(&self, buffer: &CosmicBuffer) -> bool {
+            buffer_is_empty(buffer)
+        }
+
+        /// The 1-based line and column of the cursor, e.g. for a "Ln 12, Col 4" readout
+        ///
+        /// Both are relative to the logical line, i.e. soft-wrapped lines don't count as new lines.
+        /// The column is counted in graphemes, with tabs advancing to the next tab stop of the
+        /// buffer's tab width.
+        pub fn line_col(&self, buffer: &CosmicBuffer) -> (usize, usize) {
+            let cursor = self.cursor.unwrap_or_default();
+            let tab_width = (buffer.tab_width() as usize).max(1);
+            let text = buffer.lines.get(cursor.line).map_or("", |line| line.text());
+            let before = text.get(..cursor.index).unwrap_or(text);
+            let column = before.graphemes(true).fold(0, |column, grapheme| {
+                if grapheme == "\t" {
+                    (column / tab_width + 1) * tab_width
+                } else {
+                    column + 1
+                }
+            });
+            (cursor.line + 1, column + 1)
+        }
+
+        /// The selected text, including the line endings between selected lines, or `None` if
+        /// nothing is selected
+        pub fn selected_text(&self, buffer: &CosmicBuffer) -> Option<String> {
+            if self.selection == Selection::None {
+                return None;
+            }
+            let (start, end) = self.selection_bounds?;
+            let mut selected = String::new();
+            for line_i in start.line..=end.line {
+                let line = buffer.lines.get(line_i)?;
+                let text = line.text();
+                let from = if line_i == start.line { start.index } else { 0 };
+                let to = if line_i == end.line {
+                    end.index
+                } else {
+                    text.len()
+                };
+                selected.push_str(text.get(from..to)?);
+                if line_i != end.line {
+                    selected.push_str(line.ending().as_str());
+                }
+            }
+            Some(selected)
+        }
+
+        /// The selected text split into sections, each styled like the section of `text` it was
+        /// selected from, or empty if nothing is selected
+        ///
+        /// Adjacent text from the same section is merged into one section.
+        pub fn selected_sections(&self, text: &Text, buffer: &CosmicBuffer) -> Vec<TextSection> {
+            match self.selection_bounds {
+                Some((start, end)) if self.selection != Selection::None => {
+                    sections_between(text, buffer, start, end)
+                }
+                _ => Vec::new(),
+            }
+        }
+
+        /// The lines (as separated by line endings) at least partly shown in an editor of
+        /// `node_height`, from the [`EditorState::scroll`]
+        ///
+        /// Lines that aren't laid out yet are counted as one line high.
+        pub fn visible_line_range(
+            &self,
+            buffer: &CosmicBuffer,
+            node_height: f32,
+        ) -> std::ops::Range<usize> {
+            let len = buffer.lines.len();
+            let mut start = self.scroll.line.min(len);
+            // the top of the next line, from the top of the editor
+            let mut top = -self.scroll.vertical;
+            // skip lines scrolled past the top
+            while start < len {
+                let bottom = top + lines_height(buffer, start..start + 1);
+                if bottom > 0.0 {
+                    break;
+                }
+                top = bottom;
+                start += 1;
+            }
+            let mut end = start;
+            while end < len && top < node_height {
+                top += lines_height(buffer, end..end + 1);
+                end += 1;
+            }
+            start..end
+        }
+
+        /// The box of the character after the cursor and the baseline of its line, as in
+        /// [`ComputedEditorVisuals`], or `None` if there's no cursor or its line isn't laid out
+        fn cursor_box(&self, buffer: &Buffer) -> Option<(Rect, f32)> {
+            self.cursor_box_at(buffer, self.cursor?)
+        }
+
+        /// Like [`EditorState::cursor_box`], for `cursor` rather than the editor's cursor
+        fn cursor_box_at(&self, buffer: &Buffer, cursor: Cursor) -> Option<(Rect, f32)> {
+            // drawn where the scrolled glyphs are
+            let horizontal = Vec2::X * self.scroll.horizontal;
+            buffer.layout_runs().fold(None, |cursor_box, run| {
+                let Some((x, y)) = cursor_position(&cursor, &run, buffer) else {
+                    return cursor_box;
+                };
+                let min = Vec2::new(x as f32, y as f32) - horizontal;
+                let size = Vec2::new(cursor_grapheme_width(&cursor, &run), run.line_height);
+                Some((Rect::from_corners(min, min + size), run.line_y))
+            })
+        }
+
+        /// Where the cursor of a UI editor is drawn with `cursor_config`, in logical pixels from
+        /// the top left of the window, e.g. to anchor a popup to it
+        ///
+        /// `transform` is the editor's [`GlobalTransform`] and `scale_factor` is the UI scale
+        /// factor its buffer is laid out at. This is the rect of the [`CursorVisual`], even while
+        /// it's blinked off or scrolled out of view. Returns `None` if there's no cursor.
+        pub fn caret_screen_rect(
+            &self,
+            buffer: &CosmicBuffer,
+            transform: &GlobalTransform,
+            scale_factor: f32,
+            cursor_config: &CursorConfig,
+        ) -> Option<Rect> {
+            let (cursor_box, baseline) = self.cursor_box(buffer)?;
+            let cursor_rect =
+                cursor_config.cursor_rect(cursor_box, baseline, self.overwrite, scale_factor);
+            let top_left = ui_buffer_top_left(buffer, transform, scale_factor)?;
+            Some(ui_caret_rect(top_left, cursor_rect, scale_factor))
+        }
+
+        /// One highlight per selected run, highlighted up to `buffer_width` where the selection
+        /// continues onto the next line, as in [`ComputedEditorVisuals`]
+        fn selection_boxes(
+            &self,
+            buffer: &Buffer,
+            buffer_width: Option<f32>,
+            empty_line_style: EmptyLineSelection,
+        ) -> Vec<Rect> {
+            match self.selection_bounds {
+                Some(bounds) if self.selection != Selection::None => {
+                    highlight_boxes(buffer, bounds, buffer_width, empty_line_style, self.scroll)
+                }
+                _ => Vec::new(),
+            }
+        }
+
+        /// The selection highlights of a UI editor as drawn with `selection_config`, one per
+        /// selected run, in logical pixels from the top left of the window
+        ///
+        /// As with [`EditorState::caret_screen_rect`], `transform` is the editor's
+        /// [`GlobalTransform`] and `scale_factor` is the UI scale factor its buffer is laid out
+        /// at. Runs before the end of the selection are highlighted up to the edge of the editor.
+        pub fn selection_screen_rects(
+            &self,
+            buffer: &CosmicBuffer,
+            transform: &GlobalTransform,
+            scale_factor: f32,
+            selection_config: &SelectionConfig,
+        ) -> Vec<Rect> {
+            let Some(top_left) = ui_buffer_top_left(buffer, transform, scale_factor) else {
+                return Vec::new();
+            };
+            // aligned to the nearest physical pixel, like the text
+            let top_left = (top_left * scale_factor).round();
+            let buffer_width = buffer.size().0;
+            let boxes =
+                self.selection_boxes(buffer, buffer_width, selection_config.empty_line_style);
+            selection_config
+                .padded_rects(&boxes, scale_factor, buffer_width.unwrap_or(0.0))
+                .map(|rect| {
+                    Rect::from_corners(
+                        (top_left + rect.min) / scale_factor,
+                        (top_left + rect.max) / scale_factor,
+                    )
+                })
+                .collect()
+        }
+
+        /// The size of all of the laid out text, in the buffer's coordinates (physical pixels)
+        ///
+        /// This is the width of the widest visual line by the height of every line, including
+        /// those scrolled out of view, so it reflects the current wrap width. Lines that aren't
+        /// laid out yet are counted as one line high and no wider than the rest.
+        pub fn content_size(&self, buffer: &CosmicBuffer) -> Vec2 {
+            let width = buffer
+                .lines
+                .iter()
+                .filter_map(|line| line.layout_opt())
+                .flatten()
+                .fold(0.0f32, |width, layout_line| width.max(layout_line.w));
+            Vec2::new(width, lines_height(buffer, 0..buffer.lines.len()))
+        }
+
+        /// Sets how the text wraps, reshaping the buffer and re-placing the cursor in the new
+        /// layout
+        ///
+        /// Also sets [`Text::linebreak_behavior`], which can be changed directly instead.
+        pub fn set_wrap(
+            &mut self,
+            buffer: &mut CosmicBuffer,
+            text: &mut Text,
+            pipeline: &mut TextPipeline,
+            linebreak_behavior: BreakLineOn,
+        ) {
+            if text.linebreak_behavior != linebreak_behavior {
+                text.linebreak_behavior = linebreak_behavior;
+            }
+            self.rewrap(buffer, pipeline.font_system_mut(), linebreak_behavior);
+        }
+
+        fn rewrap(
+            &mut self,
+            buffer: &mut Buffer,
+            font_system: &mut FontSystem,
+            linebreak_behavior: BreakLineOn,
+        ) {
+            buffer.set_wrap(font_system, cosmic_wrap(linebreak_behavior));
+            // wrapped text fits the width of the editor, so is never scrolled horizontally
+            if linebreak_behavior != BreakLineOn::NoWrap {
+                self.scroll.horizontal = 0.0;
+            }
+            buffer.set_scroll(self.scroll);
+            buffer.shape_until_scroll(font_system, false);
+            self.resume(buffer).with_editor_mut(|editor| {
+                let cursor = editor.cursor();
+                let clamped = editor.with_buffer(|buffer| clamp_cursor(buffer, cursor));
+                if clamped != cursor {
+                    editor.set_cursor(clamped);
+                }
+            });
+        }
+
+        fn place_cursor(&mut self, buffer: &mut Buffer, cursor: Cursor) {
+            self.resume(buffer).with_editor_mut(|editor| {
+                editor.set_selection(Selection::None);
+                editor.set_cursor(cursor);
+            });
+            self.keep_cursor_visible(buffer, cursor);
+        }
+
+        fn keep_cursor_visible(&mut self, buffer: &Buffer, cursor: Cursor) {
+            let size = buffer.size();
+            let node_size = Vec2::new(
+                size.0.unwrap_or(f32::INFINITY),
+                size.1.unwrap_or(f32::INFINITY),
+            );
+            self.scroll = ensure_cursor_visible(buffer, cursor, node_size, self.scroll);
+        }
+    }
+
+    /// An [`Editor`] made from an [`EditorState`] for one action
+    ///
+    /// The cursor, selection, scroll and preferred x position of vertical motions are restored
+    /// from the [`EditorState`] and written back to it. cosmic-text's other editor state, i.e.
+    /// auto-indent (unused), change tracking (for undo) and whether the cursor moved since the
+    /// buffer was last shaped, isn't kept between actions.
+    pub struct TempEditor<'es, 'buf> {
+        editor: Editor<'buf>,
+        editor_state: &'es mut EditorState,
+    }
+
+    impl<'es, 'buf> TempEditor<'es, 'buf> {
+        fn new(editor_state: &'es mut EditorState, buffer: &'buf mut Buffer) -> Self {
+            // the buffer's scroll is reset whenever the text is laid out
+            if buffer.scroll() != editor_state.scroll {
+                buffer.set_scroll(editor_state.scroll);
+            }
+            let mut editor = Editor::new(buffer);
+            if let Some(cursor) = editor_state.cursor {
+                editor.set_cursor(cursor);
+                editor.set_selection(editor_state.selection);
+            }
+            Self {
+                editor,
+                editor_state,
+            }
+        }
+
+        pub fn with_editor_mut(mut self, func: impl FnOnce(&mut Editor)) -> Self {
+            let cursor = self.editor.cursor();
+            func(&mut self.editor);
+            if self.editor.cursor() != cursor {
+                self.editor_state.preferred_x = None;
+            }
+            self.editor_state.cursor = Some(self.editor.cursor());
+            self.editor_state.selection = self.editor.selection();
+            self.editor_state.selection_bounds = self.editor.selection_bounds();
+            self.editor_state.scroll = self.editor.with_buffer(|buffer| {
+                let mut scroll = buffer.scroll();
+                // never scroll past the last line
+                scroll.line = scroll.line.min(buffer.lines.len().saturating_sub(1));
+                scroll
+            });
+            self
+        }
+    }
+
+    /// Clamps the cursor and selection of editors whose [`Text`] changed into the new text
+    ///
+    /// This keeps the [`EditorState`] valid when the [`Text`] is changed by something other than
+    /// the editor, e.g. shortened by another system.
+    pub fn clamp_editor_state(mut query: Query<(&CosmicBuffer, &mut EditorState), Changed<Text>>) {
+        for (buffer, mut editor_state) in &mut query {
+            let clamp = |cursor: Cursor| clamp_cursor(buffer, cursor);
+            let selection = match editor_state.selection {
+                Selection::None => Selection::None,
+                Selection::Normal(cursor) => Selection::Normal(clamp(cursor)),
+                Selection::Line(cursor) => Selection::Line(clamp(cursor)),
+                Selection::Word(cursor) => Selection::Word(clamp(cursor)),
+            };
+            let cursor = editor_state.cursor.map(clamp);
+            let mut secondary_cursors: Vec<Cursor> = editor_state
+                .secondary_cursors
+                .iter()
+                .map(|cursor| clamp(*cursor))
+                .filter(|secondary| Some(*secondary) != cursor)
+                .collect();
+            secondary_cursors.dedup();
+            let clamped = EditorState {
+                cursor,
+                secondary_cursors,
+                drop_cursor: editor_state.drop_cursor.map(clamp),
+                selection,
+                selection_bounds: editor_state
+                    .selection_bounds
+                    .map(|(start, end)| (clamp(start), clamp(end)))
+                    .filter(|(start, end)| start != end),
+                ..editor_state.clone()
+            };
+            editor_state.set_if_neq(clamped);
+        }
+    }
+
+    /// Re-wraps the buffers of editors whose [`Text::linebreak_behavior`] changed, before the text
+    /// is laid out again
+    pub fn update_wrap(
+        mut query: Query<(&Text, &mut CosmicBuffer, &mut EditorState), Changed<Text>>,
+        mut text_pipeline: ResMut<TextPipeline>,
+    ) {
+        for (text, mut buffer, mut editor_state) in &mut query {
+            if buffer.wrap() != cosmic_wrap(text.linebreak_behavior) {
+                editor_state.rewrap(
+                    &mut buffer,
+                    text_pipeline.font_system_mut(),
+                    text.linebreak_behavior,
+                );
+            }
+        }
+    }
+
+    /// The width UI editors wrap their text at, in logical pixels, instead of the width of the node
+    ///
+    /// With `Some` width the text can wrap wider or narrower than the node, e.g. at 80 columns for
+    /// a preview, and the node can be sized independently of it. Set it to `None` to wrap at the
+    /// node again, rather than removing the component. 2d editors wrap at their [`Text2dBounds`].
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct WrapWidth(pub Option<f32>);
+
+    /// Lays out the text of UI editors with a [`WrapWidth`] again at that width, after the text
+    /// system has laid it out at the width of the node
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn apply_wrap_width(
+        fonts: Res<Assets<Font>>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        ui_scale: Res<UiScale>,
+        mut text_pipeline: ResMut<TextPipeline>,
+        mut font_atlas_sets: ResMut<FontAtlasSets>,
+        mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
+        mut textures: ResMut<Assets<Image>>,
+        mut query: Query<(
+            &Node,
+            &mut Text,
+            &mut CosmicBuffer,
+            &mut TextLayoutInfo,
+            &mut EditorState,
+            Ref<WrapWidth>,
+        )>,
+    ) {
+        let scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0)
+            * ui_scale.0;
+
+        for (node, mut text, mut buffer, mut info, mut editor_state, wrap_width) in &mut query {
+            let Some(width) = wrap_width.0 else {
+                // lay the text out at the width of the node again
+                if wrap_width.is_changed() && !wrap_width.is_added() {
+                    text.set_changed();
+                }
+                continue;
+            };
+            let bounds = Vec2::new(width, node.size().y) * scale_factor;
+            if buffer.size() == (Some(bounds.x), Some(bounds.y)) {
+                continue;
+            }
+            match text_pipeline.queue_text(
+                &fonts,
+                &text.sections,
+                scale_factor.into(),
+                text.justify,
+                text.linebreak_behavior,
+                bounds,
+                &mut font_atlas_sets,
+                &mut texture_atlases,
+                &mut textures,
+                YAxisOrientation::TopToBottom,
+                &mut buffer,
+            ) {
+                Ok(new_info) => {
+                    *info = new_info;
+                    // wrapped text fits the wrap width, so is never scrolled horizontally
+                    if text.linebreak_behavior != BreakLineOn::NoWrap {
+                        editor_state.scroll.horizontal = 0.0;
+                    }
+                    let clamp = |cursor: Cursor| clamp_cursor(&buffer, cursor);
+                    let cursor = editor_state.cursor.map(clamp);
+                    if editor_state.cursor != cursor {
+                        editor_state.cursor = cursor;
+                    }
+                }
+                // the font hasn't loaded yet, try again next frame
+                Err(TextError::NoSuchFont) => {}
+                Err(error) => warn!("Could not lay out text at its wrap width: {error}"),
+            }
+        }
+    }
+
+    /// Marks a UI editor whose height follows its text, so that it never needs to scroll
+    /// vertically
+    ///
+    /// The [`Style::height`] of the editor is set to the height of its
+    /// [content](EditorState::content_size) each frame, which is laid out a frame later.
+    #[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct GrowToFitContent;
+
+    /// Sets the height of editors with [`GrowToFitContent`] to the height of their text
+    pub fn grow_to_fit_content(
+        windows: Query<&Window, With<PrimaryWindow>>,
+        ui_scale: Res<UiScale>,
+        mut query: Query<(&CosmicBuffer, &EditorState, &mut Style), With<GrowToFitContent>>,
+    ) {
+        let scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0)
+            * ui_scale.0;
+
+        for (buffer, editor_state, mut style) in &mut query {
+            let height = Val::Px(editor_state.content_size(buffer).y / scale_factor);
+            if style.height != height {
+                style.height = height;
+            }
+        }
+    }
+
+    /// Sizes a UI editor to fit its text between `min_lines` and `max_lines` lines high, e.g. a
+    /// chat input that grows as you type
+    ///
+    /// Like [`GrowToFitContent`], but past `max_lines` the editor stops growing and scrolls
+    /// instead. Lines are as high as the line height of the buffer.
+    #[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct AutoHeight {
+        pub min_lines: u16,
+        pub max_lines: u16,
+    }
+
+    impl Default for AutoHeight {
+        fn default() -> Self {
+            Self {
+                min_lines: 1,
+                max_lines: 5,
+            }
+        }
+    }
+
+    /// Sets the height of editors with [`AutoHeight`] to fit their text, before the UI is laid out
+    pub fn auto_height(
+        windows: Query<&Window, With<PrimaryWindow>>,
+        ui_scale: Res<UiScale>,
+        mut query: Query<(&CosmicBuffer, &EditorState, &AutoHeight, &mut Style)>,
+    ) {
+        let scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0)
+            * ui_scale.0;
+
+        for (buffer, editor_state, auto_height, mut style) in &mut query {
+            let line_height = buffer.metrics().line_height;
+            let min = f32::from(auto_height.min_lines) * line_height;
+            let max = f32::from(auto_height.max_lines.max(auto_height.min_lines)) * line_height;
+            let content_height = editor_state.content_size(buffer).y;
+            let height = Val::Px(content_height.clamp(min, max) / scale_factor);
+            if style.height != height {
+                style.height = height;
+            }
+        }
+    }
+
+    /// The cosmic-text [`Wrap`] for `linebreak_behavior`
+    fn cosmic_wrap(linebreak_behavior: BreakLineOn) -> Wrap {
+        match linebreak_behavior {
+            BreakLineOn::WordBoundary => Wrap::Word,
+            BreakLineOn::AnyCharacter => Wrap::Glyph,
+            BreakLineOn::WordOrCharacter => Wrap::WordOrGlyph,
+            BreakLineOn::NoWrap => Wrap::None,
+        }
+    }
+
+    /// The nearest valid position to `cursor` in the buffer
+    pub fn clamp_cursor(buffer: &Buffer, cursor: Cursor) -> Cursor {
+        let Some(last_line) = buffer.lines.len().checked_sub(1) else {
+            return Cursor::new(0, 0);
+        };
+        if cursor.line > last_line {
+            return buffer_end(buffer);
+        }
+        let text = buffer.lines[cursor.line].text();
+        let mut index = cursor.index.min(text.len());
+        while !text.is_char_boundary(index) {
+            index -= 1;
+        }
+        Cursor { index, ..cursor }
+    }
+
+    /// Where [`EditorState::scroll_to_line`] scrolls the line to
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum ScrollAlign {
+        #[default]
+        Top,
+        Center,
+        Bottom,
+    }
+
+    /// Restores the [`EditorState::scroll`] of editors, after the text systems lay out the
+    /// buffer (resetting its scroll)
+    pub fn restore_scroll(
+        mut query: Query<(&mut CosmicBuffer, &EditorState)>,
+        mut text_pipeline: ResMut<TextPipeline>,
+    ) {
+        for (mut buffer, editor_state) in &mut query {
+            if buffer.scroll() != editor_state.scroll {
+                buffer.set_scroll(editor_state.scroll);
+                buffer.shape_until_scroll(text_pipeline.font_system_mut(), false);
+            }
+        }
+    }
+
+    /// Scrolls editors to keep the cursor within the editor after it's moved or the editor is
+    /// resized
+    ///
+    /// Editors that don't wrap are also scrolled horizontally. The text is scrolled past the edges
+    /// of the editor, so put the editor in a node with [`Overflow::clip`] to hide it. While the
+    /// cursor stays put, the editor can be scrolled away from it, e.g. with a [`Scrollbar`].
+    #[allow(clippy::type_complexity)]
+    pub fn scroll_to_cursor(
+        mut last_cursors: Local<HashMap<Entity, (Cursor, Vec2)>>,
+        mut query: Query<
+            (Entity, &CosmicBuffer, &mut EditorState),
+            Or<(Changed<CosmicBuffer>, Changed<EditorState>)>,
+        >,
+    ) {
+        for (entity, buffer, mut editor_state) in &mut query {
+            let Some(cursor) = editor_state.cursor else {
+                last_cursors.remove(&entity);
+                continue;
+            };
+            let Some(width) = buffer.size().0 else {
+                continue;
+            };
+            let node_size = Vec2::new(width, buffer.size().1.unwrap_or(f32::INFINITY));
+            if last_cursors.insert(entity, (cursor, node_size)) == Some((cursor, node_size)) {
+                continue;
+            }
+            let scroll = ensure_cursor_visible(buffer, cursor, node_size, editor_state.scroll);
+            if editor_state.scroll != scroll {
+                editor_state.scroll = scroll;
+            }
+        }
+    }
+
+    /// The scroll, starting from `scroll`, that shows `cursor` within an editor of `node_size`
+    ///
+    /// When a selection doesn't fit in the editor, this shows the cursor, i.e. the end of the
+    /// selection that last moved. Horizontal scroll only changes when the buffer doesn't wrap, and
+    /// only when the cursor's line is laid out.
+    pub fn ensure_cursor_visible(
+        buffer: &Buffer,
+        cursor: Cursor,
+        node_size: Vec2,
+        mut scroll: Scroll,
+    ) -> Scroll {
+        let line_height = buffer.metrics().line_height;
+        let layout_line_height = |layout_line: &bevy::text::cosmic_text::LayoutLine| {
+            layout_line.line_height_opt.unwrap_or(line_height)
+        };
+
+        // the top and height of the cursor's visual line, from the top of its logical line
+        let (run_top, run_height) = buffer
+            .lines
+            .get(cursor.line)
+            .and_then(|line| line.layout_opt())
+            .map_or((0.0, line_height), |layout| {
+                let mut top = 0.0;
+                for (i, layout_line) in layout.iter().enumerate() {
+                    let end = layout_line.glyphs.iter().map(|glyph| glyph.end).max();
+                    if i + 1 == layout.len() || end.is_some_and(|end| cursor.index < end) {
+                        return (top, layout_line_height(layout_line));
+                    }
+                    top += layout_line_height(layout_line);
+                }
+                (0.0, line_height)
+            });
+
+        // the top of the cursor's visual line, from the top of the editor
+        let top = lines_height(buffer, 0..cursor.line) + run_top - scroll_top(buffer, scroll);
+        if lines_height(buffer, 0..buffer.lines.len()) <= node_size.y {
+            // everything fits, e.g. after the editor grew to fit its text
+            scroll.line = 0;
+            scroll.vertical = 0.0;
+        } else if top < 0.0 {
+            scroll = scroll_vertically(buffer, scroll, top, node_size.y);
+        } else if top + run_height > node_size.y {
+            scroll = scroll_vertically(buffer, scroll, top + run_height - node_size.y, node_size.y);
+        }
+
+        if buffer.wrap() == Wrap::None {
+            if let Some((x, line_w)) = buffer.layout_runs().find_map(|run| {
+                let (x, _) = cursor_position(&cursor, &run, buffer)?;
+                Some((x as f32, run.line_w))
+            }) {
+                let width = node_size.x;
+                let mut horizontal = scroll.horizontal;
+                if x < horizontal {
+                    horizontal = x;
+                } else if x > horizontal + width {
+                    horizontal = x - width;
+                }
+                scroll.horizontal = horizontal.clamp(0.0, (line_w - width).max(0.0));
+            }
+        }
+        scroll
+    }
+
+    /// The height of `lines` of the buffer, as laid out
+    ///
+    /// Lines that aren't laid out yet are counted as one line high.
+    fn lines_height(buffer: &Buffer, lines: std::ops::Range<usize>) -> f32 {
+        let line_height = buffer.metrics().line_height;
+        buffer
+            .lines
+            .get(lines.start.min(buffer.lines.len())..lines.end.min(buffer.lines.len()))
+            .unwrap_or_default()
+            .iter()
+            .map(|line| {
+                line.layout_opt().map_or(line_height, |layout| {
+                    layout
+                        .iter()
+                        .map(|layout_line| layout_line.line_height_opt.unwrap_or(line_height))
+                        .sum()
+                })
+            })
+            .sum()
+    }
+
+    /// How far `scroll` is from the top of the buffer, vertically
+    fn scroll_top(buffer: &Buffer, scroll: Scroll) -> f32 {
+        lines_height(buffer, 0..scroll.line) + scroll.vertical
+    }
+
+    /// `scroll` moved down by `delta` (or up, when negative), without scrolling past the top or
+    /// bottom of the text in an editor of `height`
+    pub fn scroll_vertically(buffer: &Buffer, scroll: Scroll, delta: f32, height: f32) -> Scroll {
+        let max_top = (lines_height(buffer, 0..buffer.lines.len()) - height).max(0.0);
+        let mut remaining = (scroll_top(buffer, scroll) + delta).clamp(0.0, max_top);
+        // the first line still (partly) shown
+        let mut line = 0;
+        while line + 1 < buffer.lines.len() {
+            let height = lines_height(buffer, line..line + 1);
+            if remaining < height {
+                break;
+            }
+            remaining -= height;
+            line += 1;
+        }
+        Scroll {
+            line,
+            vertical: remaining,
+            ..scroll
+        }
+    }
+
+    /// Moves the laid out glyphs of each editor by its horizontal scroll
+    pub fn offset_scrolled_glyphs(
+        mut applied: Local<HashMap<Entity, f32>>,
+        mut query: Query<(Entity, &EditorState, &mut TextLayoutInfo)>,
+    ) {
+        applied.retain(|entity, _| query.contains(*entity));
+        for (entity, editor_state, mut info) in &mut query {
+            // freshly laid out glyphs aren't scrolled
+            let previous = if info.is_changed() {
+                0.0
+            } else {
+                applied.get(&entity).copied().unwrap_or(0.0)
+            };
+            let horizontal = editor_state.scroll.horizontal;
+            if horizontal != previous {
+                // don't trigger change detection, so that this can tell when the text is laid out
+                for glyph in &mut info.bypass_change_detection().glyphs {
+                    glyph.position.x -= horizontal - previous;
+                }
+            }
+            if horizontal == 0.0 {
+                applied.remove(&entity);
+            } else {
+                applied.insert(entity, horizontal);
+            }
+        }
+    }
+
+    #[derive(Component, Clone, Copy, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct CursorConfig {
+        pub color: Color,
+        pub width: f32,
+        /// The shape of the cursor
+        pub shape: CursorShape,
+        /// The shape of the cursor in overwrite mode, see [`EditorState::overwrite`]
+        pub overwrite_shape: CursorShape,
+        /// Whether the cursor blinks, see [`CursorBlink`]
+        pub blink: bool,
+    }
+
+    impl Default for CursorConfig {
+        fn default() -> Self {
+            Self {
+                color: Color::LinearRgba(LinearRgba::WHITE),
+                width: 1.0,
+                shape: CursorShape::Bar,
+                overwrite_shape: CursorShape::Block,
+                blink: true,
+            }
+        }
+    }
+
+    impl CursorConfig {
+        /// The shape of the cursor, depending on whether the editor is in overwrite mode
+        pub fn current_shape(&self, overwrite: bool) -> CursorShape {
+            if overwrite {
+                self.overwrite_shape
+            } else {
+                self.shape
+            }
+        }
+
+        /// The color of the cursor: a block is semi-transparent, so the character shows through
+        pub fn current_color(&self, overwrite: bool) -> Color {
+            match self.current_shape(overwrite) {
+                CursorShape::Block => self.color.with_alpha(self.color.alpha() * 0.5),
+                CursorShape::Bar | CursorShape::Underline => self.color,
+            }
+        }
+
+        /// Where the cursor is drawn, in the buffer's coordinates, given the
+        /// [`ComputedEditorVisuals::cursor_rect`] and [`ComputedEditorVisuals::cursor_baseline`]
+        /// and the scale factor the buffer is laid out at
+        pub fn cursor_rect(
+            &self,
+            cursor_rect: Rect,
+            baseline: f32,
+            overwrite: bool,
+            scale_factor: f32,
+        ) -> Rect {
+            let Rect { min, max } = cursor_rect;
+            let width = self.width * scale_factor;
+            // at the end of a line, there's no character to cover
+            let character_width = cursor_rect.width().max(cursor_rect.height() / 2.0);
+            match self.current_shape(overwrite) {
+                CursorShape::Bar => Rect::new(min.x, min.y, min.x + width, max.y),
+                CursorShape::Block => Rect::new(min.x, min.y, min.x + character_width, max.y),
+                CursorShape::Underline => {
+                    Rect::new(min.x, baseline, min.x + character_width, baseline + width)
+                }
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    pub enum CursorShape {
+        /// A vertical bar before the character after the cursor, [`CursorConfig::width`] wide
+        #[default]
+        Bar,
+        /// A semi-transparent box covering the character after the cursor
+        Block,
+        /// A horizontal bar under the character after the cursor, [`CursorConfig::width`] thick
+        Underline,
+    }
+
+    /// The geometry of the cursor and selection of an editor, in the buffer's coordinates (physical
+    /// pixels from the top left of the text)
+    ///
+    /// This is computed in the main world, so that the extract systems only have to read it.
+    #[derive(Component, Clone, Debug, Default, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct ComputedEditorVisuals {
+        /// The box of the character after the cursor, which has no width at the end of a line:
+        /// see [`CursorConfig::cursor_rect`] for where the cursor is drawn
+        pub cursor_rect: Option<Rect>,
+        /// The baseline of the line the cursor is on
+        pub cursor_baseline: f32,
+        /// The box and baseline of each of the [`EditorState::secondary_cursors`] (or of the
+        /// [`EditorState::drop_cursor`] while the selection is dragged), like `cursor_rect` and
+        /// `cursor_baseline`
+        pub secondary_cursor_boxes: Vec<(Rect, f32)>,
+        /// One highlight per selected run
+        pub selection_rects: Vec<Rect>,
+        /// One highlight per run of each range of the [`FindHighlights`], except the active one
+        pub find_rects: Vec<Rect>,
+        /// One highlight per run of the active range of the [`FindHighlights`]
+        pub active_find_rects: Vec<Rect>,
+        /// One highlight per run of each of the [`SelectionLayers`], then of the brackets of the
+        /// [`BracketMatch`], in the order they're drawn
+        pub layer_rects: Vec<(Rect, Color)>,
+        /// The size of the laid out text, see [`buffer_dimensions`]
+        pub buffer_size: Vec2,
+    }
+
+    impl ComputedEditorVisuals {
+        /// Whether there's nothing selected or highlighted
+        pub fn has_no_highlights(&self) -> bool {
+            self.selection_rects.is_empty()
+                && self.find_rects.is_empty()
+                && self.active_find_rects.is_empty()
+                && self.layer_rects.is_empty()
+        }
+    }
+
+    /// Computes the [`ComputedEditorVisuals`] of each editor whose buffer or state has changed
+    #[allow(clippy::type_complexity)]
+    pub fn compute_editor_visuals(
+        mut query: Query<
+            (
+                &CosmicBuffer,
+                &EditorState,
+                Has<Node>,
+                Option<&FindHighlights>,
+                Option<&SelectionConfig>,
+                Option<&SelectionLayers>,
+                Option<&BracketMatch>,
+                &mut ComputedEditorVisuals,
+            ),
+            Or<(
+                Changed<CosmicBuffer>,
+                Changed<EditorState>,
+                Changed<FindHighlights>,
+                Changed<SelectionConfig>,
+                Changed<SelectionLayers>,
+                Changed<BracketMatch>,
+            )>,
+        >,
+    ) {
+        for (
+            buffer,
+            editor_state,
+            is_ui,
+            find_highlights,
+            selection_config,
+            selection_layers,
+            bracket_match,
+            mut computed_visuals,
+        ) in &mut query
+        {
+            let empty_line_style =
+                selection_config.map_or_else(Default::default, |config| config.empty_line_style);
+            let buffer_size = buffer_dimensions(buffer);
+            // UI editors highlight to the edge of the node, 2d editors to the edge of the text
+            let buffer_width = if is_ui {
+                buffer.size().0
+            } else {
+                Some(buffer_size.x)
+            };
+
+            let mut find_rects = Vec::new();
+            let mut active_find_rects = Vec::new();
+            if let Some(find_highlights) = find_highlights {
+                for (i, bounds) in find_highlights.ranges.iter().enumerate() {
+                    let rects = if Some(i) == find_highlights.active {
+                        &mut active_find_rects
+                    } else {
+                        &mut find_rects
+                    };
+                    rects.extend(highlight_boxes(
+                        buffer,
+                        *bounds,
+                        buffer_width,
+                        empty_line_style,
+                        editor_state.scroll,
+                    ));
+                }
+            }
+
+            // the brackets are highlighted over the layers
+            let bracket_layers = bracket_match
+                .and_then(|bracket_match| {
+                    let (bracket, matched) = bracket_match.brackets?;
+                    let color = if matched.is_some() {
+                        bracket_match.color
+                    } else {
+                        bracket_match.unmatched_color
+                    };
+                    Some(std::iter::once(bracket).chain(matched).map(move |cursor| {
+                        SelectionLayer {
+                            range: (cursor, Cursor::new(cursor.line, cursor.index + 1)),
+                            color,
+                        }
+                    }))
+                })
+                .into_iter()
+                .flatten();
+            let layer_rects = selection_layers
+                .map(|selection_layers| selection_layers.layers.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .copied()
+                .chain(bracket_layers)
+                .flat_map(|layer| {
+                    highlight_boxes(
+                        buffer,
+                        layer.range,
+                        buffer_width,
+                        empty_line_style,
+                        editor_state.scroll,
+                    )
+                    .into_iter()
+                    .map(|rect| (rect, layer.color))
+                })
+                .collect();
+
+            let cursor_box = editor_state.cursor_box(buffer);
+            *computed_visuals = ComputedEditorVisuals {
+                cursor_rect: cursor_box.map(|(rect, _)| rect),
+                cursor_baseline: cursor_box.map_or(0.0, |(_, baseline)| baseline),
+                secondary_cursor_boxes: match editor_state.drop_cursor {
+                    Some(drop_cursor) => std::slice::from_ref(&drop_cursor),
+                    None => editor_state.secondary_cursors.as_slice(),
+                }
+                .iter()
+                .filter_map(|cursor| editor_state.cursor_box_at(buffer, *cursor))
+                .collect(),
+                selection_rects: editor_state.selection_boxes(
+                    buffer,
+                    buffer_width,
+                    empty_line_style,
+                ),
+                find_rects,
+                active_find_rects,
+                layer_rects,
+                buffer_size,
+            };
+        }
+    }
+
+    /// The cursor of a UI editor, drawn as a UI node of its own
+    ///
+    /// This is spawned as a root node rather than as a child of the editor, because a text node
+    /// with children is no longer measured by its text. It is absolutely positioned over the
+    /// editor and styled from the editor's [`CursorConfig`], so it can be queried like any other
+    /// UI node.
+    #[derive(Component, Clone, Copy, Debug, Reflect)]
+    #[reflect(Component)]
+    pub struct CursorVisual {
+        /// The editor that this is the cursor of
+        pub editor: Entity,
+        /// Which of the [`ComputedEditorVisuals::secondary_cursor_boxes`] this is, or `None` for
+        /// the cursor
+        pub secondary: Option<usize>,
+    }
+
+    /// Spawns, positions and despawns the [`CursorVisual`]s of each UI editor, following
+    /// [`ComputedEditorVisuals::cursor_rect`] and
+    /// [`ComputedEditorVisuals::secondary_cursor_boxes`]
+    #[allow(clippy::type_complexity)]
+    pub fn update_cursor_visuals(
+        mut commands: Commands,
+        camera_query: Query<&Camera>,
+        default_ui_camera: DefaultUiCamera,
+        ui_scale: Res<UiScale>,
+        editors: Query<
+            (
+                Entity,
+                &Node,
+                &GlobalTransform,
+                &ViewVisibility,
+                Option<&TargetCamera>,
+                Option<&CursorConfig>,
+                Option<&CursorBlink>,
+                &ComputedEditorVisuals,
+                &EditorState,
+            ),
+            With<Text>,
+        >,
+        mut visuals: Query<(
+            Entity,
+            &CursorVisual,
+            &mut Style,
+            &mut BackgroundColor,
+            &mut Visibility,
+            Option<&TargetCamera>,
+        )>,
+    ) {
+        let mut has_visual = Vec::new();
+        for (
+            visual_entity,
+            cursor_visual,
+            mut style,
+            mut background_color,
+            mut visibility,
+            visual_camera,
+        ) in &mut visuals
+        {
+            let Ok((
+                _,
+                uinode,
+                global_transform,
+                view_visibility,
+                camera,
+                cursor_config,
+                cursor_blink,
+                computed_visuals,
+                editor_state,
+            )) = editors.get(cursor_visual.editor)
+            else {
+                // the editor is gone
+                commands.entity(visual_entity).despawn_recursive();
+                continue;
+            };
+            let cursor_box = match cursor_visual.secondary {
+                Some(i) => computed_visuals.secondary_cursor_boxes.get(i).copied(),
+                None => computed_visuals
+                    .cursor_rect
+                    .map(|rect| (rect, computed_visuals.cursor_baseline)),
+            };
+            let Some((cursor_rect, cursor_baseline)) = cursor_box else {
+                commands.entity(visual_entity).despawn_recursive();
+                continue;
+            };
+            has_visual.push((cursor_visual.editor, cursor_visual.secondary));
+
+            let Some(camera_entity) = camera.map(TargetCamera::entity).or(default_ui_camera.get())
+            else {
+                continue;
+            };
+            if visual_camera.map(TargetCamera::entity) != Some(camera_entity) {
+                commands
+                    .entity(visual_entity)
+                    .insert(TargetCamera(camera_entity));
+            }
+
+            let scale_factor = camera_query
+                .get(camera_entity)
+                .ok()
+                .and_then(Camera::target_scaling_factor)
+                .unwrap_or(1.0)
+                * ui_scale.0;
+
+            // the text coordinates are relative to the node's top left corner
+            let logical_top_left = global_transform.translation().truncate() - 0.5 * uinode.size();
+            let cursor_config = cursor_config.copied().unwrap_or_default();
+
+            let rect = ui_caret_rect(
+                logical_top_left,
+                cursor_config.cursor_rect(
+                    cursor_rect,
+                    cursor_baseline,
+                    editor_state.overwrite,
+                    scale_factor,
+                ),
+                scale_factor,
+            );
+            // Hide the cursor while it is blinked off, scrolled out of view, or if the editor is
+            // not visible or its size is zero (e.g. when a parent is set to `Display::None`)
+            let editor_rect =
+                Rect::from_center_size(global_transform.translation().truncate(), uinode.size());
+            let visible = view_visibility.get()
+                && uinode.size().x != 0.
+                && uinode.size().y != 0.
+                && !cursor_blink.is_some_and(|blink| !blink.visible)
+                && editor_rect.contains(rect.center());
+
+            let new_visibility = if visible {
+                let new_style = Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(rect.min.x),
+                    top: Val::Px(rect.min.y),
+                    width: Val::Px(rect.width()),
+                    height: Val::Px(rect.height()),
+                    ..default()
+                };
+                if *style != new_style {
+                    *style = new_style;
+                }
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+            if *visibility != new_visibility {
+                *visibility = new_visibility;
+            }
+            let color = cursor_config.current_color(editor_state.overwrite);
+            if background_color.0 != color {
+                background_color.0 = color;
+            }
+        }
+
+        for (editor, .., computed_visuals, _) in &editors {
+            let cursor = computed_visuals.cursor_rect.map(|_| None);
+            let secondary = (0..computed_visuals.secondary_cursor_boxes.len()).map(Some);
+            for secondary in cursor.into_iter().chain(secondary) {
+                if has_visual.contains(&(editor, secondary)) {
+                    continue;
+                }
+                // positioned on the next update
+                commands.spawn((
+                    CursorVisual { editor, secondary },
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                        visibility: Visibility::Hidden,
+                        // drawn over the text of the editor
+                        z_index: ZIndex::Global(i32::MAX),
+                        ..default()
+                    },
+                ));
+            }
+        }
+    }
+
+    /// Blinks the cursor on and off
+    ///
+    /// The cursor is held in the "on" phase whenever the [`EditorState`] changes, so it stays solid
+    /// while typing or moving the cursor.
+    #[derive(Component, Clone, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct CursorBlink {
+        pub on_duration: Duration,
+        pub off_duration: Duration,
+        pub timer: Timer,
+        /// Whether the cursor is currently in the "on" phase
+        pub visible: bool,
+    }
+
+    impl Default for CursorBlink {
+        fn default() -> Self {
+            Self::new(Duration::from_millis(500), Duration::from_millis(500))
+        }
+    }
+
+    impl CursorBlink {
+        pub fn new(on_duration: Duration, off_duration: Duration) -> Self {
+            Self {
+                on_duration,
+                off_duration,
+                timer: Timer::new(on_duration, TimerMode::Once),
+                visible: true,
+            }
+        }
+
+        /// Restarts the "on" phase
+        pub fn reset(&mut self) {
+            self.visible = true;
+            self.timer = Timer::new(self.on_duration, TimerMode::Once);
+        }
+    }
+
+    pub fn blink_cursor(
+        time: Res<Time>,
+        mut query: Query<(&mut CursorBlink, Ref<EditorState>, Option<&CursorConfig>)>,
+    ) {
+        for (mut blink, editor_state, cursor_config) in &mut query {
+            // held "on" while blinking is turned off
+            if editor_state.is_changed() || cursor_config.is_some_and(|config| !config.blink) {
+                blink.reset();
+                continue;
+            }
+            if blink.timer.tick(time.delta()).just_finished() {
+                blink.visible = !blink.visible;
+                let duration = if blink.visible {
+                    blink.on_duration
+                } else {
+                    blink.off_duration
+                };
+                blink.timer = Timer::new(duration, TimerMode::Once);
+            }
+        }
+    }
+
+    /// How the Tab key is handled
+    ///
+    /// With a multi-line selection, Tab indents every selected line. Shift+Tab outdents.
+    #[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub enum TabBehavior {
+        /// Insert a tab character
+        InsertTab,
+        /// Insert this many spaces
+        InsertSpaces(u8),
+        /// Do nothing
+        Ignore,
+        /// Move focus to the next editor, or to the previous one with Shift+Tab, see [`TabIndex`]
+        FocusTraversal(FocusCursor),
+    }
+
+    impl Default for TabBehavior {
+        fn default() -> Self {
+            Self::InsertSpaces(4)
+        }
+    }
+
+    impl TabBehavior {
+        /// The text inserted for one level of indentation, if any
+        pub fn indent(&self) -> Option<String> {
+            match self {
+                Self::InsertTab => Some("\t".to_string()),
+                Self::InsertSpaces(0) | Self::Ignore | Self::FocusTraversal(_) => None,
+                Self::InsertSpaces(n) => Some(" ".repeat(*n as usize)),
+            }
+        }
+    }
+
+    /// Whether Home moves to the first non-whitespace character of the line, then to the start of
+    /// the line when pressed again
+    ///
+    /// Without this, Home moves to the start of the line (or of the wrapped part of it).
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct SmartHome(pub bool);
+
+    /// Where the cursor is placed in an editor focused with [`TabBehavior::FocusTraversal`]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    pub enum FocusCursor {
+        Start,
+        #[default]
+        End,
+    }
+
+    /// The order editors are focused in with [`TabBehavior::FocusTraversal`]
+    ///
+    /// Editors are focused from the lowest index to the highest, and editors without one count as
+    /// 0. Editors with the same index are focused in the order they were spawned.
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct TabIndex(pub i32);
+
+    /// Moves [`Focused`] to the next editor on Tab (the previous on Shift+Tab), if the focused
+    /// editor has [`TabBehavior::FocusTraversal`]
+    ///
+    /// Like clicking, this clears the cursor and selection of the previously focused editor.
+    #[allow(clippy::type_complexity)]
+    pub fn traverse_focus(
+        mut events: EventReader<KeyboardInput>,
+        mut modifiers: Local<Modifiers>,
+        key_bindings: Res<KeyBindings>,
+        mut commands: Commands,
+        mut editors: Query<
+            (
+                Entity,
+                &CosmicBuffer,
+                &mut EditorState,
+                Has<Focused>,
+                Option<&TabBehavior>,
+                Option<&TabIndex>,
+            ),
+            With<Text>,
+        >,
+    ) {
+        let mut steps = 0;
+        for event in events.read() {
+            match event.logical_key {
+                Key::Control => modifiers.control = event.state == ButtonState::Pressed,
+                Key::Shift => modifiers.shift = event.state == ButtonState::Pressed,
+                _ => {}
+            }
+            if event.state == ButtonState::Pressed {
+                match key_bindings.command(&event.logical_key, *modifiers) {
+                    Some(EditorCommand::Indent) => steps += 1,
+                    Some(EditorCommand::Outdent) => steps -= 1,
+                    _ => {}
+                }
+            }
+        }
+        if steps == 0 {
+            return;
+        }
+
+        let Some((focused, _, _, _, Some(&TabBehavior::FocusTraversal(cursor)), _)) =
+            editors.iter().find(|(_, _, _, focused, _, _)| *focused)
+        else {
+            return;
+        };
+        let mut order: Vec<_> = editors
+            .iter()
+            .map(|(entity, _, _, _, _, tab_index)| (tab_index.copied().unwrap_or_default(), entity))
+            .collect();
+        order.sort();
+        let Some(index) = order.iter().position(|(_, entity)| *entity == focused) else {
+            return;
+        };
+        let next = order[(index as i32 + steps).rem_euclid(order.len() as i32) as usize].1;
+        if next == focused {
+            return;
+        }
+
+        commands.entity(focused).remove::<Focused>();
+        commands.entity(next).insert(Focused);
+        if let Ok((_, _, mut editor_state, _, _, _)) = editors.get_mut(focused) {
+            *editor_state = EditorState::default();
+        }
+        if let Ok((_, buf, mut editor_state, _, _, _)) = editors.get_mut(next) {
+            editor_state.cursor = Some(match cursor {
+                FocusCursor::Start => Cursor::new(0, 0),
+                FocusCursor::End => buffer_end(buf),
+            });
+            editor_state.selection = Selection::None;
+        }
+    }
+
+    /// The width of tab stops, in spaces
+    ///
+    /// Tabs are drawn up to the next tab stop, and [`EditorState::line_col`] counts columns by tab
+    /// stops. A width of 0 is treated as 1.
+    #[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct TabWidth(pub u32);
+
+    impl Default for TabWidth {
+        fn default() -> Self {
+            // cosmic-text's default
+            Self(8)
+        }
+    }
+
+    /// Applies the [`TabWidth`] of editors to their buffers, so that the text is laid out again
+    /// with the new tab stops
+    pub fn update_tab_width(
+        mut query: Query<(&TabWidth, &mut CosmicBuffer, &mut Text)>,
+        mut text_pipeline: ResMut<TextPipeline>,
+    ) {
+        for (tab_width, mut buffer, mut text) in &mut query {
+            let tab_width = tab_width.0.clamp(1, u16::MAX as u32) as u16;
+            if buffer.tab_width() != tab_width {
+                buffer.set_tab_width(text_pipeline.font_system_mut(), tab_width);
+                // lay out the text again, which also clamps the cursor to the new layout
+                text.set_changed();
+            }
+        }
+    }
+
+    /// The line endings written back to the [`Text`] after an edit
+    ///
+    /// cosmic-text keeps the ending of each line as it was parsed, and Enter always inserts `\n`.
+    /// Pasted text is inserted with Enter, so whatever its line endings, it's written back with
+    /// `\n`, or `\r\n` for [`LineEnding::CrLf`].
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub enum LineEnding {
+        /// Write every line ending as `\n`
+        Lf,
+        /// Write every line ending as `\r\n`
+        CrLf,
+        /// Write each line ending as it was in the original text
+        #[default]
+        Preserve,
+    }
+
+    impl LineEnding {
+        /// The text written after a line that cosmic-text parsed with `ending`
+        ///
+        /// The last line, which has no ending, stays without one.
+        pub fn write(&self, ending: bevy::text::cosmic_text::LineEnding) -> &'static str {
+            match (self, ending) {
+                (_, bevy::text::cosmic_text::LineEnding::None) => "",
+                (Self::Lf, _) => "\n",
+                (Self::CrLf, _) => "\r\n",
+                (Self::Preserve, ending) => ending.as_str(),
+            }
+        }
+
+        /// `text` with its `\r\n` (Windows) and `\r` (classic Mac OS) line endings replaced by
+        /// `\n`
+        pub fn normalize(text: &str) -> std::borrow::Cow<'_, str> {
+            if text.contains('\r') {
+                text.replace("\r\n", "\n").replace('\r', "\n").into()
+            } else {
+                text.into()
+            }
+        }
+    }
+
+    /// The style of text typed into an empty editor
+    ///
+    /// Otherwise, typed text takes the style of the section it's typed into. The font family and
+    /// weight come from the [`Font`] of the style, as bevy lays out each section with its font.
+    #[derive(Component, Clone, Debug, Default, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct EditorFontConfig {
+        pub style: TextStyle,
+    }
+
+    /// The maximum number of characters (including line endings) in the editor, or `None` for no
+    /// limit
+    ///
+    /// Characters are Unicode scalar values (`char`s), as counted by [`buffer_char_count`] and
+    /// [`EditorState::char_count`], so an emoji made of several code points counts as several.
+    /// This limits typed and pasted text. Text set in other ways, e.g. with
+    /// [`EditorState::set_text`], isn't truncated.
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct MaxLength(pub Option<usize>);
+
+    /// Which characters can be typed or pasted into the editor, e.g. only digits for a numeric
+    /// field
+    ///
+    /// Characters that aren't allowed are skipped, while line breaks in pasted text are left to the
+    /// [`EditorCapabilities`]. Text set in other ways, e.g. with [`EditorState::set_text`], isn't
+    /// filtered.
+    #[derive(Component, Clone)]
+    pub enum InputFilter {
+        /// ASCII digits
+        Numeric,
+        /// Letters and digits
+        Alphanumeric,
+        /// The characters this returns true for
+        Custom(Arc<dyn Fn(char) -> bool + Send + Sync>),
+    }
+
+    impl std::fmt::Debug for InputFilter {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Numeric => write!(f, "Numeric"),
+                Self::Alphanumeric => write!(f, "Alphanumeric"),
+                Self::Custom(_) => write!(f, "Custom(..)"),
+            }
+        }
+    }
+
+    impl InputFilter {
+        /// Allows the characters `filter` returns true for
+        pub fn custom(filter: impl Fn(char) -> bool + Send + Sync + 'static) -> Self {
+            Self::Custom(Arc::new(filter))
+        }
+
+        /// Whether `c` can be typed or pasted
+        pub fn allows(&self, c: char) -> bool {
+            match self {
+                Self::Numeric => c.is_ascii_digit(),
+                Self::Alphanumeric => c.is_alphanumeric(),
+                Self::Custom(filter) => filter(c),
+            }
+        }
+    }
+
+    /// `text` without the characters that `filter` doesn't allow, keeping line breaks
+    fn filter_input<'a>(filter: Option<&InputFilter>, text: &'a str) -> std::borrow::Cow<'a, str> {
+        match filter {
+            Some(filter) if !text.chars().all(|c| c == '\n' || filter.allows(c)) => text
+                .chars()
+                .filter(|c| *c == '\n' || filter.allows(*c))
+                .collect::<String>()
+                .into(),
+            _ => text.into(),
+        }
+    }
+
+    /// Draws every character of the editor as this character (e.g. for passwords), or `None` to
+    /// draw the text as it is
+    ///
+    /// Only the drawing is masked, the [`Text`] keeps its value. Masked text can't be copied or cut.
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct TextMask(pub Option<char>);
+
+    /// Whether the text can't be edited
+    ///
+    /// A read-only editor can still be focused, and its text selected and copied.
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct ReadOnly(pub bool);
+
+    /// The editing actions allowed from the keyboard, more granular than [`ReadOnly`]
+    ///
+    /// For example, a single-line input clears `NEWLINE`, so that Enter sends [`Submit`] instead of
+    /// inserting a line break, and line breaks in pasted text become spaces.
+    #[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct EditorCapabilities(u8);
+
+    // the struct is defined outside of `bitflags!`, so that it can derive `Reflect`
+    bitflags::bitflags! {
+        impl EditorCapabilities: u8 {
+            /// Typing, indenting and yanking
+            const INSERT = 1 << 0;
+            /// Backspace, Delete, deleting words and lines, and cutting
+            const DELETE = 1 << 1;
+            /// Inserting line breaks with Enter
+            const NEWLINE = 1 << 2;
+            const PASTE = 1 << 3;
+            /// Extending the selection, and selecting all
+            const SELECT = 1 << 4;
+        }
+    }
+
+    impl Default for EditorCapabilities {
+        fn default() -> Self {
+            Self::all()
+        }
+    }
+
+    impl std::fmt::Debug for EditorCapabilities {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("EditorCapabilities(")?;
+            bitflags::parser::to_writer(self, &mut *f)?;
+            f.write_str(")")
+        }
+    }
+
+    impl EditorCapabilities {
+        /// Whether a key press with `command` (or typing, if `typed`) is allowed
+        pub fn allows(&self, command: Option<EditorCommand>, typed: bool) -> bool {
+            let required = match command {
+                Some(EditorCommand::Move { select: true, .. } | EditorCommand::SelectAll) => {
+                    Self::SELECT
+                }
+                Some(EditorCommand::Insert(_) | EditorCommand::Indent | EditorCommand::Yank) => {
+                    Self::INSERT
+                }
+                Some(
+                    EditorCommand::Backspace
+                    | EditorCommand::Delete
+                    | EditorCommand::DeleteWordBackward
+                    | EditorCommand::DeleteWordForward
+                    | EditorCommand::Outdent
+                    | EditorCommand::KillLine
+                    | EditorCommand::Cut,
+                ) => Self::DELETE,
+                Some(EditorCommand::Enter) => Self::NEWLINE,
+                Some(EditorCommand::Paste) => Self::PASTE,
+                Some(
+                    EditorCommand::Move { select: false, .. }
+                    | EditorCommand::Copy
+                    | EditorCommand::ToggleOverwrite
+                    | EditorCommand::Undo
+                    | EditorCommand::Redo,
+                ) => Self::empty(),
+                None if typed => Self::INSERT,
+                None => Self::empty(),
+            };
+            self.contains(required)
+        }
+
+        /// `text` with its line breaks replaced by spaces, unless `NEWLINE` is allowed
+        fn fit_lines<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, str> {
+            if self.contains(Self::NEWLINE) || !text.contains('\n') {
+                text.into()
+            } else {
+                text.replace('\n', " ").into()
+            }
+        }
+    }
+
+    /// Sent when Enter is pressed in an editor whose [`EditorCapabilities`] don't allow
+    /// `NEWLINE`, e.g. to submit a form
+    #[derive(Event, Clone, Debug)]
+    pub struct Submit {
+        pub entity: Entity,
+        /// The editor's text
+        pub value: String,
+    }
+
+    /// Sent when a key press in an editor is ignored or cut short, e.g. to flash a warning
+    #[derive(Event, Clone, Copy, Debug)]
+    pub struct InputRejected {
+        pub entity: Entity,
+        pub reason: RejectReason,
+    }
+
+    /// Why input was rejected, see [`InputRejected`]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum RejectReason {
+        /// The text would be longer than the [`MaxLength`]
+        MaxLength,
+        /// The [`InputFilter`] doesn't allow a typed or pasted character
+        InputFilter,
+        /// The editor is [`ReadOnly`]
+        ReadOnly,
+        /// The [`EditorCapabilities`] don't allow the action
+        CapabilityDisabled,
+    }
+
+    /// Whether Shift+Enter still inserts a line break when [`EditorCapabilities`] don't allow
+    /// `NEWLINE`, rather than sending [`Submit`]
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct ShiftEnterNewline(pub bool);
+
+    /// How the editor copies to and pastes from the clipboard
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Component, Default)]
+    pub enum ClipboardMode {
+        /// Copy and paste plain text
+        #[default]
+        Plain,
+        /// Also copy the styles of the selected sections into the [`RichClipboard`], and paste
+        /// them back while the system clipboard still holds the copied text
+        ///
+        /// Plain text copied from other apps is pasted as before.
+        Rich,
+    }
+
+    /// Where editors copy and cut text to, and paste it from, see [`EditorClipboard`]
+    pub trait Clipboard: Send + Sync + 'static {
+        /// The text on the clipboard, if it could be read
+        fn get(&mut self) -> Option<String>;
+        fn set(&mut self, text: &str);
+
+        /// The primary selection (the text last selected, pasted with the middle mouse button),
+        /// on platforms that have one
+        fn get_primary(&mut self) -> Option<String> {
+            None
+        }
+    }
+
+    /// The system clipboard, through `arboard`
+    #[cfg(not(target_arch = "wasm32"))]
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct NativeClipboard;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    impl Clipboard for NativeClipboard {
+        fn get(&mut self) -> Option<String> {
+            match arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+                Ok(text) => Some(text),
+                Err(error) => {
+                    warn!("Could not read from the clipboard: {error}");
+                    None
+                }
+            }
+        }
+
+        fn set(&mut self, text: &str) {
+            if let Err(error) = arboard::Clipboard::new().and_then(|mut c| c.set_text(text)) {
+                warn!("Could not write to the clipboard: {error}");
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        fn get_primary(&mut self) -> Option<String> {
+            use arboard::{GetExtLinux, LinuxClipboardKind};
+            match arboard::Clipboard::new()
+                .and_then(|mut c| c.get().clipboard(LinuxClipboardKind::Primary).text())
+            {
+                Ok(text) => Some(text),
+                Err(error) => {
+                    warn!("Could not read the primary selection: {error}");
+                    None
+                }
+            }
+        }
+    }
+
+    /// A clipboard that only holds the text copied within the app
+    ///
+    /// This is the default on the web, where the system clipboard can only be read
+    /// asynchronously. Apps can provide their own [`Clipboard`] (e.g. using `web-sys`) that
+    /// keeps the last text read from the system clipboard to return from [`Clipboard::get`].
+    #[derive(Clone, Debug, Default)]
+    pub struct MemoryClipboard {
+        pub text: Option<String>,
+    }
+
+    impl Clipboard for MemoryClipboard {
+        fn get(&mut self) -> Option<String> {
+            self.text.clone()
+        }
+
+        fn set(&mut self, text: &str) {
+            self.text = Some(text.to_string());
+        }
+    }
+
+    /// The [`Clipboard`] used by all editors
+    ///
+    /// Defaults to [`NativeClipboard`], or [`MemoryClipboard`] on wasm. Insert this resource to
+    /// use another clipboard.
+    #[derive(Resource)]
+    pub struct EditorClipboard(pub Box<dyn Clipboard>);
+
+    impl Default for EditorClipboard {
+        fn default() -> Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            let clipboard = NativeClipboard;
+            #[cfg(target_arch = "wasm32")]
+            let clipboard = MemoryClipboard::default();
+            Self(Box::new(clipboard))
+        }
+    }
+
+    /// Whether middle-clicking an editor pastes the primary selection, see
+    /// [`handle_middle_click`]
+    ///
+    /// Defaults to true on Linux, the only platform where [`NativeClipboard`] has a primary
+    /// selection.
+    #[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct EnablePrimarySelection(pub bool);
+
+    impl Default for EnablePrimarySelection {
+        fn default() -> Self {
+            Self(cfg!(target_os = "linux"))
+        }
+    }
+
+    /// The sections last copied from an editor with [`ClipboardMode::Rich`]
+    ///
+    /// The system clipboard only holds plain text, so the styles are kept here.
+    #[derive(Resource, Clone, Debug, Default)]
+    pub struct RichClipboard {
+        pub sections: Vec<TextSection>,
+    }
+
+    impl RichClipboard {
+        /// Whether `text` (e.g. from the system clipboard) is the text of the copied sections
+        pub fn matches(&self, text: &str) -> bool {
+            !self.sections.is_empty()
+                && self
+                    .sections
+                    .iter()
+                    .map(|section| section.value.len())
+                    .sum::<usize>()
+                    == text.len()
+                && self
+                    .sections
+                    .iter()
+                    .scan(0, |offset, section| {
+                        let start = *offset;
+                        *offset += section.value.len();
+                        Some(text.get(start..*offset) == Some(section.value.as_str()))
+                    })
+                    .all(|matches| matches)
+        }
+
+        /// The attributes to insert the copied text with, where each section's metadata is its
+        /// index plus `first_index`
+        fn attrs_list(&self, first_index: usize) -> AttrsList {
+            let mut attrs_list = AttrsList::new(Attrs::new().metadata(first_index));
+            let mut offset = 0;
+            for (i, section) in self.sections.iter().enumerate() {
+                let range = offset..offset + section.value.len();
+                attrs_list.add_span(range, Attrs::new().metadata(first_index + i));
+                offset += section.value.len();
+            }
+            attrs_list
+        }
+    }
+
+    /// Replaces the glyphs of editors with a [`TextMask`] with the mask character, after the text
+    /// is laid out
+    ///
+    /// Each mask glyph is centred on the glyph it replaces and sits on the same baseline, so the
+    /// cursor and selection still line up with the text.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn mask_text(
+        fonts: Res<Assets<Font>>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        ui_scale: Res<UiScale>,
+        mut text_pipeline: ResMut<TextPipeline>,
+        mut font_atlas_sets: ResMut<FontAtlasSets>,
+        mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
+        mut textures: ResMut<Assets<Image>>,
+        mut query: Query<(
+            &Text,
+            &CosmicBuffer,
+            &TextMask,
+            &mut TextLayoutInfo,
+            Has<Node>,
+        )>,
+    ) {
+        let window_scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0);
+
+        for (text, buffer, mask, mut info, is_ui) in &mut query {
+            let Some(mask) = mask.0 else {
+                continue;
+            };
+            // only mask freshly laid out text
+            if !info.is_changed() {
+                continue;
+            }
+            let scale_factor = if is_ui {
+                window_scale_factor * ui_scale.0
+            } else {
+                window_scale_factor
+            };
+
+            // one mask character per section, in the style of that section
+            let sections: Vec<_> = text
+                .sections
+                .iter()
+                .map(|section| TextSection::new(mask, section.style.clone()))
+                .collect();
+            let mut mask_buffer = CosmicBuffer::default();
+            let mask_info = match text_pipeline.queue_text(
+                &fonts,
+                &sections,
+                scale_factor.into(),
+                JustifyText::Left,
+                BreakLineOn::NoWrap,
+                Vec2::INFINITY,
+                &mut font_atlas_sets,
+                &mut texture_atlases,
+                &mut textures,
+                YAxisOrientation::TopToBottom,
+                &mut mask_buffer,
+            ) {
+                Ok(mask_info) => mask_info,
+                // the font hasn't loaded yet, the text will be laid out again when it has
+                Err(TextError::NoSuchFont) => continue,
+                Err(error) => {
+                    warn!("Could not lay out text mask: {error}");
+                    continue;
+                }
+            };
+            let Some(mask_baseline) = mask_buffer.layout_runs().next().map(|run| run.line_y) else {
+                continue;
+            };
+
+            // don't trigger change detection, so this only runs again when the text is laid out
+            let info = info.bypass_change_detection();
+            let height = info.size.y;
+            for glyph in &mut info.glyphs {
+                let Some(mask_glyph) = mask_info
+                    .glyphs
+                    .iter()
+                    .find(|mask_glyph| mask_glyph.section_index == glyph.section_index)
+                else {
+                    continue;
+                };
+                // glyph positions of 2d text are measured from the bottom
+                let y = if is_ui {
+                    glyph.position.y
+                } else {
+                    height - glyph.position.y
+                };
+                let Some(baseline) = buffer
+                    .layout_runs()
+                    .find(|run| y < run.line_top + run.line_height)
+                    .map(|run| run.line_y)
+                else {
+                    continue;
+                };
+                let y = baseline + mask_glyph.position.y - mask_baseline;
+                glyph.position.y = if is_ui { y } else { height - y };
+                glyph.size = mask_glyph.size;
+                glyph.atlas_info = mask_glyph.atlas_info.clone();
+            }
+        }
+    }
+
+    /// Hint text shown in place of the text while the editor is empty
+    ///
+    /// The placeholder is only drawn: it is not part of the [`Text`], and can't be selected or hit.
+    #[derive(Component, Clone, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct Placeholder {
+        pub value: String,
+        pub style: TextStyle,
+    }
+
+    impl Default for Placeholder {
+        fn default() -> Self {
+            Self::new(String::new())
+        }
+    }
+
+    impl Placeholder {
+        /// A placeholder with the default (greyed-out) style
+        pub fn new(value: impl Into<String>) -> Self {
+            Self {
+                value: value.into(),
+                style: TextStyle {
+                    color: Color::srgba(1.0, 1.0, 1.0, 0.5),
+                    ..Default::default()
+                },
+            }
+        }
+    }
+
+    /// The laid out text of a [`Placeholder`], maintained by [`layout_placeholder`]
+    #[derive(Component, Debug)]
+    pub struct PlaceholderLayout {
+        pub info: TextLayoutInfo,
+        /// The node size and scale factor this was laid out for
+        size: Vec2,
+        scale_factor: f32,
+    }
+
+    /// Lays out the [`Placeholder`] of empty editors
+    #[allow(clippy::too_many_arguments)]
+    pub fn layout_placeholder(
+        mut commands: Commands,
+        fonts: Res<Assets<Font>>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        ui_scale: Res<UiScale>,
+        mut text_pipeline: ResMut<TextPipeline>,
+        mut font_atlas_sets: ResMut<FontAtlasSets>,
+        mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
+        mut textures: ResMut<Assets<Image>>,
+        query: Query<(
+            Entity,
+            &Node,
+            &Text,
+            &CosmicBuffer,
+            Ref<Placeholder>,
+            Option<&PlaceholderLayout>,
+        )>,
+    ) {
+        let scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0)
+            * ui_scale.0;
+
+        for (entity, node, text, buffer, placeholder, layout) in &query {
+            // the placeholder is only drawn while the editor is empty
+            if !buffer_is_empty(buffer) {
+                continue;
+            }
+            let up_to_date = !placeholder.is_changed()
+                && layout.is_some_and(|layout| {
+                    layout.size == node.size() && layout.scale_factor == scale_factor
+                });
+            if up_to_date {
+                continue;
+            }
+
+            let mut placeholder_buffer = CosmicBuffer::default();
+            match text_pipeline.queue_text(
+                &fonts,
+                &[TextSection::new(
+                    placeholder.value.clone(),
+                    placeholder.style.clone(),
+                )],
+                scale_factor.into(),
+                text.justify,
+                text.linebreak_behavior,
+                node.size() * scale_factor,
+                &mut font_atlas_sets,
+                &mut texture_atlases,
+                &mut textures,
+                YAxisOrientation::TopToBottom,
+                &mut placeholder_buffer,
+            ) {
+                Ok(info) => {
+                    commands.entity(entity).insert(PlaceholderLayout {
+                        info,
+                        size: node.size(),
+                        scale_factor,
+                    });
+                }
+                // the font hasn't loaded yet, try again next frame
+                Err(TextError::NoSuchFont) => {}
+                Err(error) => warn!("Could not lay out placeholder: {error}"),
+            }
+        }
+    }
+
+    /// Draws the line numbers of a UI editor in a gutter to the left of the text
+    ///
+    /// The gutter is `width` logical pixels wide and outside of the node, so leave room for it,
+    /// e.g. with a left margin. Numbers are drawn in the font and size of the first section so that
+    /// they line up with the text, right-aligned on the first row of each line: rows that a line
+    /// wraps onto are left blank. Set `enabled` to false to hide them.
+    #[derive(Component, Clone, Copy, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct LineNumbers {
+        pub enabled: bool,
+        pub color: Color,
+        pub width: f32,
+    }
+
+    impl Default for LineNumbers {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                color: Color::srgba(1.0, 1.0, 1.0, 0.4),
+                width: 40.0,
+            }
+        }
+    }
+
+    /// The laid out numbers of [`LineNumbers`], maintained by [`layout_line_numbers`]
+    #[derive(Component, Debug, Default)]
+    pub struct LineNumbersLayout {
+        /// The top of each numbered row, in the buffer's coordinates, and its laid out number
+        pub rows: Vec<(f32, TextLayoutInfo)>,
+    }
+
+    /// Lays out the [`LineNumbers`] of the rows of UI editors in view, after they're scrolled
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn layout_line_numbers(
+        mut commands: Commands,
+        fonts: Res<Assets<Font>>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        ui_scale: Res<UiScale>,
+        mut text_pipeline: ResMut<TextPipeline>,
+        mut font_atlas_sets: ResMut<FontAtlasSets>,
+        mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
+        mut textures: ResMut<Assets<Image>>,
+        query: Query<
+            (Entity, &Text, &CosmicBuffer, &LineNumbers),
+            (
+                With<Node>,
+                Or<(
+                    Changed<CosmicBuffer>,
+                    Changed<LineNumbers>,
+                    Without<LineNumbersLayout>,
+                )>,
+            ),
+        >,
+    ) {
+        let scale_factor = windows
+            .get_single()
+            .map(|window| window.resolution.scale_factor())
+            .unwrap_or(1.0)
+            * ui_scale.0;
+
+        for (entity, text, buffer, line_numbers) in &query {
+            let mut rows = Vec::new();
+            if line_numbers.enabled {
+                let style = TextStyle {
+                    color: line_numbers.color,
+                    ..text
+                        .sections
+                        .first()
+                        .map(|section| section.style.clone())
+                        .unwrap_or_default()
+                };
+                let mut last_line = None;
+                for run in buffer.layout_runs() {
+                    // only the first row of each line is numbered
+                    if last_line.replace(run.line_i) == Some(run.line_i) {
+                        continue;
+                    }
+                    let mut row_buffer = CosmicBuffer::default();
+                    match text_pipeline.queue_text(
+                        &fonts,
+                        &[TextSection::new(
+                            (run.line_i + 1).to_string(),
+                            style.clone(),
+                        )],
+                        scale_factor.into(),
+                        JustifyText::Right,
+                        BreakLineOn::NoWrap,
+                        Vec2::new(line_numbers.width * scale_factor, run.line_height),
+                        &mut font_atlas_sets,
+                        &mut texture_atlases,
+                        &mut textures,
+                        YAxisOrientation::TopToBottom,
+                        &mut row_buffer,
+                    ) {
+                        Ok(info) => rows.push((run.line_top, info)),
+                        // the font hasn't loaded yet, the numbers are laid out with the text
+                        Err(TextError::NoSuchFont) => {}
+                        Err(error) => warn!("Could not lay out line numbers: {error}"),
+                    }
+                }
+            }
+            commands.entity(entity).insert(LineNumbersLayout { rows });
+        }
+    }
+
+    /// Ranges of an editor highlighted underneath the selection, e.g. the matches of a search
+    /// from [`find_all`]
+    ///
+    /// Each range is highlighted like a selection with these bounds. To stop highlighting, clear
+    /// the ranges rather than removing the component.
+    #[derive(Component, Clone, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct FindHighlights {
+        #[reflect(ignore)]
+        pub ranges: Vec<(Cursor, Cursor)>,
+        pub color: Color,
+        /// The index in `ranges` of the current match, which is highlighted with `active_color`
+        pub active: Option<usize>,
+        pub active_color: Color,
+    }
+
+    impl Default for FindHighlights {
+        fn default() -> Self {
+            Self {
+                ranges: Vec::new(),
+                color: Color::srgba(1.0, 0.8, 0.0, 0.4),
+                active: None,
+                active_color: Color::srgba(1.0, 0.5, 0.0, 0.6),
+            }
+        }
+    }
+
+    impl FindHighlights {
+        /// Highlights `ranges`, e.g. from [`find_all`], without a current match
+        pub fn new(ranges: Vec<(Cursor, Cursor)>) -> Self {
+            Self {
+                ranges,
+                ..default()
+            }
+        }
+
+        /// The highlight colors, which are transparent without [`FindHighlights`]
+        #[cfg(feature = "render")]
+        fn colors(find_highlights: Option<&Self>) -> (LinearRgba, LinearRgba) {
+            find_highlights.map_or((LinearRgba::NONE, LinearRgba::NONE), |find_highlights| {
+                (
+                    find_highlights.color.into(),
+                    find_highlights.active_color.into(),
+                )
+            })
+        }
+    }
+
+    /// Ranges of an editor highlighted in their own colors, underneath the [`FindHighlights`] and
+    /// the selection, e.g. the current line of a code editor
+    ///
+    /// Layers are drawn in order, so later layers are drawn over earlier ones. Like a selection, a
+    /// range that continues onto the next line is highlighted up to the edge of the editor, so the
+    /// range from the start of a line to the start of the next highlights the whole line.
+    #[derive(Component, Clone, Debug, Default, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct SelectionLayers {
+        pub layers: Vec<SelectionLayer>,
+    }
+
+    /// One range of [`SelectionLayers`]
+    #[derive(Clone, Copy, Debug, Reflect)]
+    pub struct SelectionLayer {
+        #[reflect(ignore)]
+        pub range: (Cursor, Cursor),
+        pub color: Color,
+    }
+
+    /// Highlights the bracket next to the cursor and its matching bracket, for code editing
+    ///
+    /// The bracket after the cursor is matched first, then the bracket before it. A bracket
+    /// without a match is highlighted in `unmatched_color`. The brackets are highlighted over the
+    /// [`SelectionLayers`].
+    #[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct BracketMatch {
+        pub color: Color,
+        pub unmatched_color: Color,
+        /// The bracket next to the cursor and its match, if it has one, maintained by
+        /// [`match_brackets`]
+        #[reflect(ignore)]
+        pub brackets: Option<(Cursor, Option<Cursor>)>,
+    }
+
+    impl Default for BracketMatch {
+        fn default() -> Self {
+            Self {
+                color: Color::srgba(0.5, 0.5, 0.5, 0.4),
+                unmatched_color: Color::srgba(1.0, 0.0, 0.0, 0.4),
+                brackets: None,
+            }
+        }
+    }
+
+    /// The brackets that [`BracketMatch`] matches, as (opening, closing) pairs
+    const BRACKET_PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+    /// The bracket that matches the bracket at `bracket`, skipping nested pairs of the same
+    /// brackets, or `None` if it isn't a bracket or has no match
+    pub fn matching_bracket(buffer: &Buffer, bracket: Cursor) -> Option<Cursor> {
+        let line = buffer.lines.get(bracket.line)?.text();
+        let c = line.get(bracket.index..)?.chars().next()?;
+        let (open, close, forward) = BRACKET_PAIRS.iter().find_map(|&(open, close)| {
+            (c == open || c == close).then_some((open, close, c == open))
+        })?;
+
+        let mut depth = 0usize;
+        let mut visit = |line_i: usize, index: usize, c: char| {
+            if c == open || c == close {
+                if (c == open) == forward {
+                    depth += 1;
+                } else {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(Cursor::new(line_i, index));
+                    }
+                }
+            }
+            None
+        };
+        if forward {
+            for (line_i, line) in buffer.lines.iter().enumerate().skip(bracket.line) {
+                let start = if line_i == bracket.line {
+                    bracket.index
+                } else {
+                    0
+                };
+                for (index, c) in line.text()[start..].char_indices() {
+                    if let Some(found) = visit(line_i, start + index, c) {
+                        return Some(found);
+                    }
+                }
+            }
+        } else {
+            for (line_i, line) in buffer.lines.iter().enumerate().take(bracket.line + 1).rev() {
+                let text = line.text();
+                let end = if line_i == bracket.line {
+                    bracket.index + c.len_utf8()
+                } else {
+                    text.len()
+                };
+                for (index, c) in text[..end].char_indices().rev() {
+                    if let Some(found) = visit(line_i, index, c) {
+                        return Some(found);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Finds the bracket next to the cursor of editors with [`BracketMatch`], and its match
+    pub fn match_brackets(
+        mut query: Query<
+            (&CosmicBuffer, &EditorState, &mut BracketMatch),
+            Or<(Changed<CosmicBuffer>, Changed<EditorState>)>,
+        >,
+    ) {
+        for (buffer, editor_state, mut bracket_match) in &mut query {
+            let is_bracket = |cursor: Cursor| {
+                buffer
+                    .lines
+                    .get(cursor.line)
+                    .and_then(|line| line.text().get(cursor.index..)?.chars().next())
+                    .is_some_and(|c| {
+                        BRACKET_PAIRS
+                            .iter()
+                            .any(|&(open, close)| c == open || c == close)
+                    })
+            };
+            let brackets = editor_state
+                .cursor
+                .and_then(|cursor| {
+                    let before = buffer
+                        .lines
+                        .get(cursor.line)?
+                        .text()
+                        .get(..cursor.index)?
+                        .chars()
+                        .next_back()
+                        .map(|c| Cursor::new(cursor.line, cursor.index - c.len_utf8()));
+                    std::iter::once(cursor)
+                        .chain(before)
+                        .find(|cursor| is_bracket(*cursor))
+                })
+                .map(|bracket| (bracket, matching_bracket(buffer, bracket)));
+            if bracket_match.brackets != brackets {
+                bracket_match.brackets = brackets;
+            }
+        }
+    }
+
+    #[derive(Component, Clone, Copy, Debug, Reflect)]
+    #[reflect(Component, Default)]
+    pub struct SelectionConfig {
+        pub color: Color,
+        /// The radius of the corners of each highlight, in logical pixels (UI editors only)
+        pub corner_radius: f32,
+        /// How far each highlight extends past the selected text, in logical pixels
+        pub padding: Vec2,
+        /// How the empty lines inside a selection are highlighted
+        pub empty_line_style: EmptyLineSelection,
+    }
+
+    /// How a [`SelectionConfig`] highlights the empty lines inside a selection
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+    #[reflect(Default)]
+    pub enum EmptyLineSelection {
+        /// Across the whole width of the editor
+        #[default]
+        FullWidth,
+        /// A marker a quarter of the line height wide, placed where the cursor sits on the empty
+        /// line given its alignment and direction
+        Marker,
+    }
+
+    impl Default for SelectionConfig {
+        fn default() -> Self {
+            Self {
+                color: Color::LinearRgba(LinearRgba::BLACK),
+                corner_radius: 0.0,
+                padding: Vec2::ZERO,
+                empty_line_style: EmptyLineSelection::default(),
+            }
+        }
+    }
+
+    impl SelectionConfig {
+        /// The highlights of [`ComputedEditorVisuals::selection_rects`], expanded by the padding
+        ///
+        /// So that the highlights of neighbouring lines don't overlap, only the top of the first
+        /// and the bottom of the last highlight are padded vertically. Highlights aren't padded past
+        /// the start of the line or past `max_x`.
+        pub fn padded_rects<'a>(
+            &self,
+            rects: &'a [Rect],
+            scale_factor: f32,
+            max_x: f32,
+        ) -> impl Iterator<Item = Rect> + 'a {
+            // the buffer is laid out in physical pixels
+            let padding = self.padding * scale_factor;
+            let last = rects.len().saturating_sub(1);
+            rects.iter().enumerate().map(move |(i, rect)| {
+                let mut rect = *rect;
+                rect.min.x = (rect.min.x - padding.x).max(rect.min.x.min(0.0));
+                rect.max.x = (rect.max.x + padding.x).min(rect.max.x.max(max_x));
+                if i == 0 {
+                    rect.min.y -= padding.y;
+                }
+                if i == last {
+                    rect.max.y += padding.y;
+                }
+                rect
+            })
+        }
+    }
+
+    /// The touch that acts as the pointer, like the left mouse button: tapping places the cursor
+    /// and dragging selects
+    ///
+    /// Only the first touch is followed, until it ends, so other fingers don't move the cursor.
+    /// Touches are in the primary window.
+    #[derive(Resource, Clone, Copy, Debug, Default)]
+    pub struct TouchPointer {
+        /// The id of the followed touch, with its position in logical pixels
+        pub touch: Option<(u64, Vec2)>,
+        just_pressed: bool,
+    }
+
+    impl TouchPointer {
+        /// Whether the followed touch started this frame
+        pub fn just_pressed(&self) -> bool {
+            self.just_pressed
+        }
+
+        /// Whether a touch is being followed
+        pub fn pressed(&self) -> bool {
+            self.touch.is_some()
+        }
+
+        /// The position of the followed touch, in logical pixels
+        pub fn position(&self) -> Option<Vec2> {
+            self.touch.map(|(_, position)| position)
+        }
+    }
+
+    /// Follows the first touch in [`TouchPointer`]
+    pub fn track_touch_pointer(touches: Res<Touches>, mut touch_pointer: ResMut<TouchPointer>) {
+        touch_pointer.just_pressed = false;
+        touch_pointer.touch = touch_pointer.touch.and_then(|(id, _)| {
+            let touch = touches.get_pressed(id)?;
+            Some((id, touch.position()))
+        });
+        if touch_pointer.touch.is_none() {
+            // a tap may start and end in the same frame, so isn't necessarily still pressed
+            if let Some(touch) = touches.iter_just_pressed().next() {
+                touch_pointer.touch = Some((touch.id(), touch.position()));
+                touch_pointer.just_pressed = true;
+            }
+        }
+    }
+
+    /// Finds the pointer in the window that a camera renders to
+    ///
+    /// The pointer is the [`TouchPointer`] while there is one, otherwise the mouse cursor.
+    #[derive(SystemParam)]
+    pub struct PointerParams<'w, 's> {
+        pub primary_window: Query<'w, 's, (Entity, &'static Window), With<PrimaryWindow>>,
+        pub windows: Query<'w, 's, &'static Window>,
+        pub cameras: Query<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+        pub default_ui_camera: DefaultUiCamera<'w, 's>,
+        pub ui_scale: Res<'w, UiScale>,
+        pub touch_pointer: Res<'w, TouchPointer>,
+    }
+
+    impl PointerParams<'_, '_> {
+        /// The cursor position in the window that `camera` renders to, in logical pixels, along
+        /// with that window's scale factor
+        pub fn cursor_position(&self, camera: &Camera) -> Option<(Vec2, f32)> {
+            let RenderTarget::Window(window_ref) = camera.target else {
+                return None;
+            };
+            let primary_window = self.primary_window.get_single().ok().map(|(e, _)| e);
+            let window_entity = window_ref.normalize(primary_window)?.entity();
+            let window = self.windows.get(window_entity).ok()?;
+            let position = match self.touch_pointer.position() {
+                Some(position) if Some(window_entity) == primary_window => position,
+                _ => window.cursor_position()?,
+            };
+            Some((position, window.resolution.scale_factor()))
+        }
+
+        /// The cursor position for a UI node rendered by its [`TargetCamera`] (or the default UI
+        /// camera), along with the UI scale factor
+        pub fn ui_cursor_position(
+            &self,
+            target_camera: Option<&TargetCamera>,
+        ) -> Option<(Vec2, f32)> {
+            let camera_entity = target_camera
+                .map(TargetCamera::entity)
+                .or(self.default_ui_camera.get())?;
+            let (camera, _) = self.cameras.get(camera_entity).ok()?;
+            let (position, scale_factor) = self.cursor_position(camera)?;
+            Some((position, scale_factor * self.ui_scale.0))
+        }
+
+        /// The cursor position in world space, for each active camera whose window has the cursor
+        pub fn world_cursor_positions(&self) -> impl Iterator<Item = Vec2> + '_ {
+            self.cameras
+                .iter()
+                .filter(|(camera, _)| camera.is_active)
+                .filter_map(|(camera, camera_transform)| {
+                    let (position, _) = self.cursor_position(camera)?;
+                    camera.viewport_to_world_2d(camera_transform, position)
+                })
+        }
+
+        /// The scale factor that 2d text is laid out with
+        pub fn primary_scale_factor(&self) -> f32 {
+            self.primary_window
+                .get_single()
+                .map(|(_, window)| window.resolution.scale_factor())
+                .unwrap_or(1.0)
+        }
+
+        /// The pointer position over the buffer of a 2d editor (with an `anchor`) or a UI editor,
+        /// and the size of the buffer, see [`buffer_position_2d`] and [`ui_buffer_position`]
+        pub fn buffer_position(
+            &self,
+            buffer: &Buffer,
+            transform: &GlobalTransform,
+            anchor: Option<&Anchor>,
+            target_camera: Option<&TargetCamera>,
+        ) -> Option<(Vec2, Vec2)> {
+            match anchor {
+                // 2d text
+                Some(anchor) => {
+                    let world_position = self.world_cursor_positions().next()?;
+                    Some(buffer_position_2d(
+                        world_position,
+                        buffer,
+                        transform,
+                        anchor,
+                        self.primary_scale_factor(),
+                    ))
+                }
+                // UI node
+                None => {
+                    let (cursor_window_position, scale_factor) =
+                        self.ui_cursor_position(target_camera)?;
+                    Some(ui_buffer_position(
+                        cursor_window_position,
+                        buffer,
+                        transform,
+                        scale_factor,
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Finds where the carets of editors are on screen from main world systems, e.g. to position
+    /// a tooltip or draw a gizmo at the caret
+    #[allow(clippy::type_complexity)]
+    #[derive(SystemParam)]
+    pub struct CaretParams<'w, 's> {
+        pub editors: Query<
+            'w,
+            's,
+            (
+                &'static CosmicBuffer,
+                &'static EditorState,
+                &'static GlobalTransform,
+                Option<&'static CursorConfig>,
+                Option<&'static Anchor>,
+                Option<&'static TargetCamera>,
+            ),
+        >,
+        pub primary_window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+        pub cameras: Query<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+        pub default_ui_camera: DefaultUiCamera<'w, 's>,
+        pub ui_scale: Res<'w, UiScale>,
+    }
+
+    impl CaretParams<'_, '_> {
+        /// The centre of the caret of the editor `entity`, in logical pixels from the top left of
+        /// the viewport of the camera that renders it
+        ///
+        /// UI editors are rendered by their [`TargetCamera`] (or the default UI camera), and 2d
+        /// editors by the active camera with the highest order. Returns `None` if the editor has
+        /// no cursor, or if a 2d editor's caret is outside of the camera's view.
+        pub fn caret_viewport_position(&self, entity: Entity) -> Option<Vec2> {
+            let (buffer, editor_state, transform, cursor_config, anchor, target_camera) =
+                self.editors.get(entity).ok()?;
+            let cursor_config = cursor_config.copied().unwrap_or_default();
+            match anchor {
+                // 2d text, placed like in `extract_cursor_2d`
+                Some(anchor) => {
+                    let scale_factor = self
+                        .primary_window
+                        .get_single()
+                        .map(|window| window.resolution.scale_factor())
+                        .unwrap_or(1.0);
+                    let (cursor_box, baseline) = editor_state.cursor_box(buffer)?;
+                    let cursor_rect = cursor_config.cursor_rect(
+                        cursor_box,
+                        baseline,
+                        editor_state.overwrite,
+                        scale_factor,
+                    );
+                    // the buffer is laid out in physical pixels, with +Y down
+                    let size = buffer_dimensions(buffer);
+                    let alignment_translation = size / scale_factor * -(anchor.as_vec() + 0.5);
+                    let position =
+                        Vec2::new(cursor_rect.center().x, size.y - cursor_rect.center().y)
+                            / scale_factor;
+                    let world_position =
+                        transform.transform_point((alignment_translation + position).extend(0.));
+                    let (camera, camera_transform) = self
+                        .cameras
+                        .iter()
+                        .filter(|(camera, _)| camera.is_active)
+                        .max_by_key(|(camera, _)| camera.order)?;
+                    camera.world_to_viewport(camera_transform, world_position)
+                }
+                // UI node, which is laid out in the viewport of its camera
+                None => {
+                    let camera_entity = target_camera
+                        .map(TargetCamera::entity)
+                        .or(self.default_ui_camera.get())?;
+                    let (camera, _) = self.cameras.get(camera_entity).ok()?;
+                    let scale_factor =
+                        camera.target_scaling_factor().unwrap_or(1.0) * self.ui_scale.0;
+                    let rect = editor_state.caret_screen_rect(
+                        buffer,
+                        transform,
+                        scale_factor,
+                        &cursor_config,
+                    )?;
+                    Some(rect.center())
+                }
+            }
+        }
+    }
+
+    #[derive(SystemParam)]
+    pub struct HitSystemParams<'w, 's> {
+        pub pointer: PointerParams<'w, 's>,
+        pub buffers_2d: Query<
+            'w,
+            's,
+            (
+                Entity,
+                &'static CosmicBuffer,
+                &'static GlobalTransform,
+                &'static Anchor,
+            ),
+            Without<Node>,
+        >,
+        pub buffers: Query<
+            'w,
+            's,
+            (
+                Entity,
+                &'static CosmicBuffer,
+                &'static GlobalTransform,
+                &'static Node,
+                Option<&'static TargetCamera>,
+            ),
+        >,
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct HitOutput {
+        pub entity: Entity,
+        pub span_index: usize,
+        pub position: Vec2,
+    }
+
+    /// The result of [`hit`] for this frame, updated by [`update_pointer_hit`] so that the pointer
+    /// systems don't each run the hit test
+    #[derive(Resource, Clone, Copy, Debug, Default)]
+    pub struct PointerHit(pub Option<HitOutput>);
+
+    /// Stores the result of [`hit`] in the [`PointerHit`]
+    pub fn update_pointer_hit(params: HitSystemParams, mut pointer_hit: ResMut<PointerHit>) {
+        pointer_hit.0 = hit(params);
+    }
+
+    /// Returns the topmost entity under the cursor, i.e. the UI node with the highest
+    /// [`Node::stack_index`], or if no UI node is hit, the 2d text with the highest Z
+    ///
+    /// Each editor is only hit by the cursor in the window that its camera renders to.
+    #[allow(clippy::type_complexity)]
+    pub fn hit(params: HitSystemParams) -> Option<HitOutput> {
+        let mut topmost: Option<(u32, HitOutput)> = None;
+        for (entity, buffer, transform, node, target_camera) in &params.buffers {
+            let stack_index = node.stack_index();
+            if topmost
+                .as_ref()
+                .is_some_and(|(topmost_index, _)| *topmost_index >= stack_index)
+            {
+                continue;
+            }
+            // the cursor in the window this node is rendered to
+            let Some((cursor_window_position, ui_scale_factor)) =
+                params.pointer.ui_cursor_position(target_camera)
+            else {
+                continue;
+            };
+            if let Some((_, span_index)) =
+                buffer_hit(buffer, transform, cursor_window_position, ui_scale_factor)
+            {
+                let (position, _) =
+                    ui_buffer_position(cursor_window_position, buffer, transform, ui_scale_factor);
+                // the buffer's glyphs don't include the horizontal scroll
+                let position = position + Vec2::X * buffer.scroll().horizontal;
+                // notify only the relevant child
+                topmost = Some((
+                    stack_index,
+                    HitOutput {
+                        entity,
+                        span_index,
+                        position,
+                    },
+                ));
+            }
+        }
+
+        if let Some((_, hit)) = topmost {
+            return Some(hit);
+        }
+
+        let scale_factor = params.pointer.primary_scale_factor();
+        hit_2d(
+            params.pointer.world_cursor_positions(),
+            &params.buffers_2d,
+            scale_factor,
+        )
+    }
+
+    /// Returns the 2d text with the highest Z under any of `world_positions`
+    #[allow(clippy::type_complexity)]
+    fn hit_2d(
+        world_positions: impl Iterator<Item = Vec2>,
+        buffers_2d: &Query<(Entity, &CosmicBuffer, &GlobalTransform, &Anchor), Without<Node>>,
+        scale_factor: f32,
+    ) -> Option<HitOutput> {
+        let mut topmost_2d: Option<(f32, HitOutput)> = None;
+        for world_position in world_positions {
+            for (entity, buffer, transform, anchor) in buffers_2d {
+                let z = transform.translation().z;
+                if topmost_2d
+                    .as_ref()
+                    .is_some_and(|(topmost_z, _)| *topmost_z >= z)
+                {
+                    continue;
+                }
+                let (position, size) =
+                    buffer_position_2d(world_position, buffer, transform, anchor, scale_factor);
+                if let Some((_, span_index)) = hit_buffer_position(buffer, position, size) {
+                    // the buffer's glyphs don't include the horizontal scroll
+                    let position = position + Vec2::X * buffer.scroll().horizontal;
+                    topmost_2d = Some((
+                        z,
+                        HitOutput {
+                            entity,
+                            span_index,
+                            position,
+                        },
+                    ));
+                }
+            }
+        }
+
+        topmost_2d.map(|(_, hit)| hit)
+    }
+
+    /// Hit-tests the buffer of a UI node at `point`, a window position in logical pixels
+    ///
+    /// Returns the cursor at the point and the index of the [`TextSection`] it's in, or `None` if
+    /// the point is outside of the node. This doesn't need a window, so it can be used from other
+    /// tools, e.g. gizmos.
+    pub fn buffer_hit(
+        buffer: &CosmicBuffer,
+        transform: &GlobalTransform,
+        point: Vec2,
+        scale_factor: f32,
+    ) -> Option<(Cursor, usize)> {
+        let (position, size) = ui_buffer_position(point, buffer, transform, scale_factor);
+        hit_buffer_position(buffer, position, size)
+    }
+
+    /// Hit-tests a position in a buffer of `size`, returning the cursor and its span index
+    fn hit_buffer_position(buffer: &Buffer, position: Vec2, size: Vec2) -> Option<(Cursor, usize)> {
+        if !Rect::from_corners(Vec2::ZERO, size).contains(position) {
+            return None;
+        }
+        // the buffer's glyphs don't include the horizontal scroll
+        let position = position + Vec2::X * buffer.scroll().horizontal;
+        // TODO: fix the issue where this always registers a hit on the first span if no other is hit
+        let cursor = buffer.hit(position.x, position.y)?;
+        // get attrs from cursor
+        let line = buffer.lines.get(cursor.line)?;
+        let span_index = line.attrs_list().get_span(cursor.index).metadata;
+        Some((cursor, span_index))
+    }
+
+    /// Converts a window position into a position in the buffer of a UI node (where +Y down, +X
+    /// right, in physical pixels), also returning the size of the buffer
+    ///
+    /// `scale_factor` is the UI scale factor (the camera's target scaling factor multiplied by the
+    /// [`UiScale`]): the node is positioned in logical pixels, but the buffer is laid out in
+    /// physical pixels. For example, at a scale factor of 2.0, a 100x50 node has a 200x100 buffer,
+    /// and clicking 10 logical pixels right of its top left corner hits the buffer at x = 20.
+    pub fn ui_buffer_position(
+        window_position: Vec2,
+        buffer: &Buffer,
+        transform: &GlobalTransform,
+        scale_factor: f32,
+    ) -> (Vec2, Vec2) {
+        let size = buffer.size();
+        let size = Vec2::new(
+            size.0.expect("Buffer has a width"),
+            size.1.expect("Buffer has a height"),
+        );
+        let origin = transform.translation().truncate();
+        // top left corner of buffer (where +Y down, +X right), in logical pixels
+        let offset = origin - size / scale_factor / 2.0;
+        ((window_position - offset) * scale_factor, size)
+    }
+
+    /// The top left corner of the buffer of a UI node, in logical pixels from the top left of the
+    /// window, or `None` if the buffer hasn't been sized yet
+    fn ui_buffer_top_left(
+        buffer: &Buffer,
+        transform: &GlobalTransform,
+        scale_factor: f32,
+    ) -> Option<Vec2> {
+        let (Some(width), Some(height)) = buffer.size() else {
+            return None;
+        };
+        Some(transform.translation().truncate() - Vec2::new(width, height) / scale_factor / 2.0)
+    }
+
+    /// Converts a world position into a position in the buffer of 2d text (where +Y down, +X
+    /// right, in physical pixels), also returning the size of the buffer
+    pub fn buffer_position_2d(
+        world_position: Vec2,
+        buffer: &Buffer,
+        transform: &GlobalTransform,
+        anchor: &Anchor,
+        scale_factor: f32,
+    ) -> (Vec2, Vec2) {
+        // logical size of the text, and its bottom left corner relative to the transform
+        let size = buffer_dimensions(buffer) / scale_factor;
+        let bottom_left = size * -(anchor.as_vec() + 0.5);
+        let local_position = transform
+            .affine()
+            .inverse()
+            .transform_point3(world_position.extend(0.))
+            .truncate();
+        let position = Vec2::new(
+            local_position.x - bottom_left.x,
+            bottom_left.y + size.y - local_position.y,
+        );
+        (position * scale_factor, size * scale_factor)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use bevy::ecs::system::{System, SystemState};
+        use bevy::input::keyboard::NativeKeyCode;
+        use bevy::text::cosmic_text::{Family, Metrics, Shaping};
+
+        fn font_system() -> FontSystem {
+            FontSystem::new()
+        }
+
+        /// A 400x200 buffer of `text` in a monospace font, at a font size and line height of 20
+        fn buffer(font_system: &mut FontSystem, text: &str) -> Buffer {
+            let mut buffer = Buffer::new(font_system, Metrics::new(20.0, 20.0));
+            buffer.set_size(font_system, Some(400.0), Some(200.0));
+            buffer.set_text(
+                font_system,
+                text,
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+            buffer.shape_until_scroll(font_system, false);
+            buffer
+        }
+
+        fn new_editor(font_system: &mut FontSystem, text: &str) -> Editor<'static> {
+            Editor::new(buffer(font_system, text))
+        }
+
+        fn text(editor: &Editor) -> String {
+            editor.with_buffer(|buffer| {
+                buffer
+                    .lines
+                    .iter()
+                    .map(|line| line.text())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+
+        fn cosmic_buffer(font_system: &mut FontSystem, text: &str) -> CosmicBuffer {
+            let mut cosmic_buffer = CosmicBuffer::default();
+            *cosmic_buffer = buffer(font_system, text);
+            cosmic_buffer
+        }
+
+        /// A 400x200 buffer of `sections` laid out like bevy lays out text, styling each section with
+        /// its index as metadata
+        fn rich_buffer(font_system: &mut FontSystem, sections: &[&str]) -> CosmicBuffer {
+            let mut buffer = CosmicBuffer::default();
+            buffer.set_metrics(font_system, Metrics::new(20.0, 20.0));
+            buffer.set_size(font_system, Some(400.0), Some(200.0));
+            buffer.set_rich_text(
+                font_system,
+                sections.iter().enumerate().map(|(i, section)| {
+                    (*section, Attrs::new().family(Family::Monospace).metadata(i))
+                }),
+                Attrs::new(),
+                Shaping::Advanced,
+            );
+            buffer.shape_until_scroll(font_system, false);
+            buffer
+        }
+
+        #[test]
+        fn left_and_right_collapse_the_selection() {
+            let mut font_system = font_system();
+            let mut editor = new_editor(&mut font_system, "hello world");
+            editor.set_cursor(Cursor::new(0, 2));
+            apply_motion(&mut editor, &mut font_system, Motion::Right, true);
+            apply_motion(&mut editor, &mut font_system, Motion::Right, true);
+            assert_eq!(
+                editor.selection_bounds(),
+                Some((Cursor::new(0, 2), Cursor::new(0, 4)))
+            );
+
+            // releasing Shift, Right moves to the end of the selection and no further
+            apply_motion(&mut editor, &mut font_system, Motion::Right, false);
+            assert_eq!(editor.cursor().index, 4);
+            assert_eq!(editor.selection(), Selection::None);
+
+            apply_motion(&mut editor, &mut font_system, Motion::Left, true);
+            apply_motion(&mut editor, &mut font_system, Motion::Left, true);
+            apply_motion(&mut editor, &mut font_system, Motion::Left, false);
+            assert_eq!(editor.cursor().index, 2);
+            assert_eq!(editor.selection(), Selection::None);
+
+            // without a selection, they move as usual
+            apply_motion(&mut editor, &mut font_system, Motion::Right, false);
+            assert_eq!(editor.cursor().index, 3);
+        }
+
+        #[test]
+        fn section_scratch_skips_unwritten_sections() {
+            let mut sections = SectionScratch::default();
+            sections.entry(0).push_str("abc");
+            sections.entry(3).push_str("def");
+            assert_eq!(
+                sections.iter().collect::<Vec<_>>(),
+                vec![(0, "abc"), (3, "def")]
+            );
+            assert_eq!(sections.get(1), None);
+            assert_eq!(sections.get(3), Some("def"));
+
+            // cleared sections keep their slots, but aren't written until they're written again
+            sections.clear();
+            sections.entry(3);
+            assert_eq!(sections.iter().collect::<Vec<_>>(), vec![(3, "")]);
+            assert_eq!(sections.get(0), None);
+        }
+
+        #[test]
+        fn section_scratch_reuses_its_allocations() {
+            let line = "the quick brown fox jumps over the lazy dog\n";
+            let mut sections = SectionScratch::default();
+            let write_back = |sections: &mut SectionScratch| {
+                sections.clear();
+                sections.entry(0).push_str(line);
+                sections.entry(2).push_str(line);
+                sections
+                    .values
+                    .iter()
+                    .map(|value| (value.as_ptr(), value.capacity()))
+                    .collect::<Vec<_>>()
+            };
+
+            // once the sections have grown, writing the same text back doesn't allocate again
+            let allocations = write_back(&mut sections);
+            for _ in 0..3 {
+                assert_eq!(write_back(&mut sections), allocations);
+            }
+        }
+
+        #[test]
+        fn write_line_with_sparse_span_metadata() {
+            let mut attrs_list = AttrsList::new(Attrs::new().metadata(9));
+            attrs_list.add_span(0..3, Attrs::new().metadata(0));
+            attrs_list.add_span(4..7, Attrs::new().metadata(5));
+            let line = BufferLine::new(
+                "abc def",
+                bevy::text::cosmic_text::LineEnding::Lf,
+                attrs_list,
+                Shaping::Advanced,
+            );
+            let mut sections = SectionScratch::default();
+            let mut span_index = 0;
+            write_line(&line, LineEnding::Preserve, &mut span_index, &mut sections);
+            // the unstyled space takes the style of the span after it
+            assert_eq!(
+                sections.iter().collect::<Vec<_>>(),
+                vec![(0, "abc"), (5, " def\n")]
+            );
+            assert_eq!(span_index, 5);
+        }
+
+        #[test]
+        fn next_word_stops_at_each_word_end() {
+            let mut font_system = font_system();
+            let mut buffer = buffer(&mut font_system, "hello world foo");
+            let mut cursor = Cursor::new(0, 0);
+            let mut stops = Vec::new();
+            for _ in 0..3 {
+                (cursor, _) = buffer
+                    .cursor_motion(&mut font_system, cursor, None, Motion::NextWord)
+                    .unwrap();
+                stops.push(cursor.index);
+            }
+            assert_eq!(stops, vec![5, 11, 15]);
+
+            // at the end of the last line, it stays put
+            let (end, _) = buffer
+                .cursor_motion(&mut font_system, cursor, None, Motion::NextWord)
+                .unwrap();
+            assert_eq!(end.index, 15);
+        }
+
+        /// A UI node at `stack_index`, which is usually set by the UI layout
+        fn node_at(stack_index: u32) -> Node {
+            use bevy::reflect::GetField as _;
+            let mut node = Node::default();
+            *node.get_field_mut::<u32>("stack_index").unwrap() = stack_index;
+            node
+        }
+
+        #[test]
+        fn hit_picks_the_node_with_the_highest_stack_index() {
+            let mut font_system = font_system();
+            let mut world = World::new();
+            world.init_resource::<UiScale>();
+            world.init_resource::<TouchPointer>();
+            let mut window = Window::default();
+            window.set_cursor_position(Some(Vec2::new(10.0, 5.0)));
+            world.spawn((window, PrimaryWindow));
+            world.spawn(Camera::default());
+            // both 400x200 buffers have their top left corner at the window's top left corner
+            let transform = GlobalTransform::from_translation(Vec3::new(200.0, 100.0, 0.0));
+            let front = world
+                .spawn((
+                    cosmic_buffer(&mut font_system, "front"),
+                    transform,
+                    node_at(2),
+                ))
+                .id();
+            world.spawn((
+                cosmic_buffer(&mut font_system, "back"),
+                transform,
+                node_at(1),
+            ));
+
+            let mut state = SystemState::<HitSystemParams>::new(&mut world);
+            let hit = hit(state.get(&world)).expect("the cursor is over both nodes");
+            assert_eq!(hit.entity, front);
+        }
+
+        #[test]
+        fn pointer_systems_read_the_stored_hit() {
+            let mut world = World::new();
+            world.init_resource::<Events<SpanHovered>>();
+            world.init_resource::<Events<SpanUnhovered>>();
+            let entity = world.spawn_empty().id();
+            world.insert_resource(PointerHit(Some(HitOutput {
+                entity,
+                span_index: 1,
+                position: Vec2::ZERO,
+            })));
+
+            let mut system = IntoSystem::into_system(handle_span_hover);
+            system.initialize(&mut world);
+            system.run((), &mut world);
+
+            let hovered: Vec<_> = world
+                .resource_mut::<Events<SpanHovered>>()
+                .drain()
+                .map(|hovered| (hovered.entity, hovered.span_index))
+                .collect();
+            assert_eq!(hovered, vec![(entity, 1)]);
+        }
+
+        #[test]
+        fn edits_are_forwarded_to_span_children() {
+            let mut world = World::new();
+            world.init_resource::<Events<TextChanged>>();
+            let editor = world
+                .spawn(Text::from_sections([
+                    TextSection::new("ab", TextStyle::default()),
+                    TextSection::new("cd", TextStyle::default()),
+                    TextSection::new("ef", TextStyle::default()),
+                ]))
+                .with_children(|parent| {
+                    parent.spawn(EditorSpan::new("ab", TextStyle::default()));
+                    parent.spawn(EditorSpan::new("c", TextStyle::default()));
+                })
+                .id();
+            world.send_event(TextChanged { entity: editor });
+
+            let mut system = IntoSystem::into_system(forward_edits_to_spans);
+            system.initialize(&mut world);
+            system.run((), &mut world);
+            system.apply_deferred(&mut world);
+
+            let children = world.get::<Children>(editor).unwrap().to_vec();
+            let values: Vec<&str> = children
+                .iter()
+                .map(|child| world.get::<EditorSpan>(*child).unwrap().value.as_str())
+                .collect();
+            assert_eq!(values, vec!["ab", "cd", "ef"]);
+        }
+
+        #[test]
+        fn hit_2d_picks_the_text_with_the_highest_z() {
+            let mut font_system = font_system();
+            let mut world = World::new();
+            let front = world
+                .spawn((
+                    cosmic_buffer(&mut font_system, "front"),
+                    GlobalTransform::from_translation(Vec3::Z),
+                    Anchor::Center,
+                ))
+                .id();
+            world.spawn((
+                cosmic_buffer(&mut font_system, "back"),
+                GlobalTransform::IDENTITY,
+                Anchor::Center,
+            ));
+
+            let mut state = SystemState::<
+                Query<(Entity, &CosmicBuffer, &GlobalTransform, &Anchor), Without<Node>>,
+            >::new(&mut world);
+            // near the top left corner of both centred 400x200 buffers
+            let world_position = Vec2::new(-190.0, 95.0);
+            let hit = hit_2d(std::iter::once(world_position), &state.get(&world), 1.0)
+                .expect("the point is over both texts");
+            assert_eq!(hit.entity, front);
+        }
+
+        #[test]
+        fn ui_buffer_position_at_scale_factor_2() {
+            let mut font_system = font_system();
+            let mut buffer = buffer(&mut font_system, "hello");
+            // a 100x50 node is laid out in a 200x100 buffer
+            buffer.set_size(&mut font_system, Some(200.0), Some(100.0));
+            // the node's top left corner is at (10, 20)
+            let transform = GlobalTransform::from_translation(Vec3::new(60.0, 45.0, 0.0));
+
+            let (position, size) =
+                ui_buffer_position(Vec2::new(20.0, 25.0), &buffer, &transform, 2.0);
+            assert_eq!(size, Vec2::new(200.0, 100.0));
+            assert_eq!(position, Vec2::new(20.0, 10.0));
+        }
+
+        #[test]
+        fn ctrl_end_moves_to_the_buffer_end() {
+            assert_eq!(
+                KeyBindings::default().command(&Key::End, Modifiers::CONTROL),
+                Some(EditorCommand::Move {
+                    motion: Motion::BufferEnd,
+                    select: false,
+                })
+            );
+
+            let mut font_system = font_system();
+            let mut editor = new_editor(&mut font_system, "first line\nsecond\nlast line");
+            editor.set_cursor(Cursor::new(0, 3));
+            apply_motion(&mut editor, &mut font_system, Motion::BufferEnd, false);
+            let end = editor.with_buffer(buffer_end);
+            assert_eq!(
+                (editor.cursor().line, editor.cursor().index),
+                (end.line, end.index)
+            );
+            assert_eq!((end.line, end.index), (2, 9));
+        }
+
+        #[test]
+        fn highlight_boxes_of_a_multi_line_selection() {
+            let mut font_system = font_system();
+            let buffer = buffer(&mut font_system, "hello\n\nworld");
+            // every glyph of the monospace font is as wide
+            let advance = buffer.layout_runs().next().unwrap().glyphs[0].w;
+            let x = |chars: f32| (chars * advance) as i32 as f32;
+
+            let rects = highlight_boxes(
+                &buffer,
+                (Cursor::new(0, 2), Cursor::new(2, 3)),
+                Some(400.0),
+                EmptyLineSelection::FullWidth,
+                Scroll::default(),
+            );
+            assert_eq!(
+                rects,
+                vec![
+                    // to the end of the buffer, as the selection continues onto the next line
+                    Rect::new(x(2.0), 0.0, 400.0, 20.0),
+                    // the empty line is highlighted across the buffer
+                    Rect::new(0.0, 20.0, 400.0, 40.0),
+                    Rect::new(0.0, 40.0, x(3.0), 60.0),
+                ]
+            );
+        }
+
+        #[test]
+        fn delete_word_around_punctuation() {
+            let mut font_system = font_system();
+
+            // Ctrl+Backspace deletes back to the start of the word, with the punctuation after it
+            let mut editor = new_editor(&mut font_system, "hello, world!");
+            editor.set_cursor(Cursor::new(0, 13));
+            delete_word(&mut editor, &mut font_system, Motion::PreviousWord);
+            assert_eq!(text(&editor), "hello, ");
+            delete_word(&mut editor, &mut font_system, Motion::PreviousWord);
+            assert_eq!(text(&editor), "");
+
+            // Ctrl+Delete deletes to the end of the word, with the punctuation before it
+            let mut editor = new_editor(&mut font_system, "hello, world!");
+            editor.set_cursor(Cursor::new(0, 0));
+            delete_word(&mut editor, &mut font_system, Motion::NextWord);
+            assert_eq!(text(&editor), ", world!");
+            delete_word(&mut editor, &mut font_system, Motion::NextWord);
+            assert_eq!(text(&editor), "!");
+
+            // punctuation between letters is part of the word
+            let mut editor = new_editor(&mut font_system, "don't stop");
+            editor.set_cursor(Cursor::new(0, 0));
+            delete_word(&mut editor, &mut font_system, Motion::NextWord);
+            assert_eq!(text(&editor), " stop");
+        }
+
+        #[test]
+        fn line_col_counts_tabs_to_the_next_tab_stop() {
+            let mut font_system = font_system();
+            let mut buffer = cosmic_buffer(&mut font_system, "a\tbc\n\tx");
+            buffer.set_tab_width(&mut font_system, 4);
+            let mut editor_state = EditorState::default();
+
+            let mut line_col = |cursor: Cursor| {
+                editor_state.set_cursor(&mut buffer, cursor);
+                editor_state.line_col(&buffer)
+            };
+            assert_eq!(line_col(Cursor::new(0, 1)), (1, 2));
+            // the tab after "a" advances to the tab stop at column 4
+            assert_eq!(line_col(Cursor::new(0, 2)), (1, 5));
+            assert_eq!(line_col(Cursor::new(0, 3)), (1, 6));
+            // a tab at the start of the line advances a whole tab width
+            assert_eq!(line_col(Cursor::new(1, 1)), (2, 5));
+            assert_eq!(line_col(Cursor::new(1, 2)), (2, 6));
+        }
+
+        /// A world with what [`listen_keyboard_input_events`] needs, and a focused editor of
+        /// `text` that can be undone
+        fn keyboard_world(text: &str) -> (World, Entity) {
+            let mut world = World::new();
+            let mut text_pipeline = bevy::text::TextPipeline::default();
+            *text_pipeline.font_system_mut() = font_system();
+            let buffer = rich_buffer(text_pipeline.font_system_mut(), &[text]);
+            world.insert_resource(text_pipeline);
+            world.insert_resource(EditorClipboard(Box::new(MemoryClipboard::default())));
+            world.init_resource::<Time>();
+            world.init_resource::<RichClipboard>();
+            world.init_resource::<KeyBindings>();
+            world.init_resource::<KillRing>();
+            world.init_resource::<Events<KeyboardInput>>();
+            world.init_resource::<Events<TextChanged>>();
+            world.init_resource::<Events<Submit>>();
+            world.init_resource::<Events<InputRejected>>();
+            let entity = world
+                .spawn((
+                    buffer,
+                    Text::from_section(text, TextStyle::default()),
+                    EditorState::default(),
+                    UndoHistory::default(),
+                    Focused,
+                ))
+                .id();
+            (world, entity)
+        }
+
+        fn press(world: &mut World, key: Key) {
+            world.send_event(KeyboardInput {
+                key_code: KeyCode::Unidentified(NativeKeyCode::Unidentified),
+                logical_key: key,
+                state: ButtonState::Pressed,
+                window: Entity::PLACEHOLDER,
+            });
+        }
+
+        fn text_value(world: &World, entity: Entity) -> String {
+            let text = world.get::<Text>(entity).unwrap();
+            text.sections
+                .iter()
+                .map(|section| section.value.as_str())
+                .collect()
+        }
+
+        #[test]
+        fn line_sections_are_only_assigned_in_range() {
+            let sections = ["one\n", "\ntwo"];
+            let mut buffer = rich_buffer(&mut font_system(), &sections);
+            let text = Text::from_sections(
+                sections.map(|section| TextSection::new(section, TextStyle::default())),
+            );
+            let default_sections = |buffer: &CosmicBuffer| -> Vec<usize> {
+                buffer
+                    .lines
+                    .iter()
+                    .map(|line| line.attrs_list().defaults().metadata)
+                    .collect()
+            };
+
+            // the empty line's ending is in the second section
+            assign_line_sections(&mut buffer, &text, 1..=1);
+            assert_eq!(default_sections(&buffer), vec![0, 1, 0]);
+            assign_line_sections(&mut buffer, &text, 0..=usize::MAX);
+            assert_eq!(default_sections(&buffer), vec![0, 1, 1]);
+        }
+
+        #[test]
+        fn typing_into_text_with_cr_line_endings() {
+            let (mut world, entity) = keyboard_world("one\rtwo\r");
+            world.entity_mut(entity).insert(LineEnding::Lf);
+            let mut system = IntoSystem::into_system(listen_keyboard_input_events);
+            system.initialize(&mut world);
+
+            // a lone CR ends a line, like a LF
+            let (mut buffer, mut editor_state) = world
+                .query::<(&mut CosmicBuffer, &mut EditorState)>()
+                .single_mut(&mut world);
+            assert_eq!(buffer.lines[1].text(), "two");
+            editor_state.set_cursor(&mut buffer, Cursor::new(1, 3));
+
+            press(&mut world, Key::Character("X".into()));
+            system.run((), &mut world);
+            assert_eq!(text_value(&world, entity), "one\ntwoX\n");
+        }
+
+        #[test]
+        fn tab_does_not_indent_a_newly_focused_editor() {
+            let (mut world, entity) = keyboard_world("ab");
+            let mut system = IntoSystem::into_system(listen_keyboard_input_events);
+            system.initialize(&mut world);
+            system.run((), &mut world);
+
+            let (mut buffer, mut editor_state) = world
+                .query::<(&mut CosmicBuffer, &mut EditorState)>()
+                .single_mut(&mut world);
+            editor_state.set_cursor(&mut buffer, Cursor::new(0, 0));
+            // as if `traverse_focus` moved focus here on this Tab
+            world.entity_mut(entity).remove::<Focused>().insert(Focused);
+
+            press(&mut world, Key::Tab);
+            system.run((), &mut world);
+            assert_eq!(text_value(&world, entity), "ab");
+
+            press(&mut world, Key::Tab);
+            system.run((), &mut world);
+            assert_ne!(text_value(&world, entity), "ab");
+        }
+
+        #[test]
+        fn undo_typing_over_a_selection_restores_it() {
+            let (mut world, entity) = keyboard_world("hello world");
+            let mut system = IntoSystem::into_system(listen_keyboard_input_events);
+            system.initialize(&mut world);
+
+            let (mut buffer, mut editor_state) = world
+                .query::<(&mut CosmicBuffer, &mut EditorState)>()
+                .single_mut(&mut world);
+            editor_state.set_selection(&mut buffer, Cursor::new(0, 6), Cursor::new(0, 11));
+
+            press(&mut world, Key::Character("X".into()));
+            system.run((), &mut world);
+            assert_eq!(text_value(&world, entity), "hello X");
+
+            press(&mut world, Key::Control);
+            press(&mut world, Key::Character("z".into()));
+            // the presses after Ctrl wait for the next frame, as undo replaces the text
+            system.run((), &mut world);
+            system.run((), &mut world);
+            assert_eq!(text_value(&world, entity), "hello world");
+            let editor_state = world.get::<EditorState>(entity).unwrap();
+            assert_eq!(
+                editor_state.selection_bounds(),
+                Some((Cursor::new(0, 6), Cursor::new(0, 11)))
+            );
+            assert!(world.get::<UndoHistory>(entity).unwrap().undo.is_empty());
+        }
+
+        /// `text` as it's written back from a buffer with `line_ending`
+        fn written_with(line_ending: LineEnding, text: &str) -> String {
+            let buffer = buffer(&mut font_system(), text);
+            buffer
+                .lines
+                .iter()
+                .map(|line| line.text().to_owned() + line_ending.write(line.ending()))
+                .collect()
+        }
+
+        #[test]
+        fn line_ending_lf() {
+            let text = "one\ntwo\n";
+            assert!(matches!(
+                LineEnding::normalize(text),
+                std::borrow::Cow::Borrowed("one\ntwo\n")
+            ));
+            assert_eq!(written_with(LineEnding::Preserve, text), "one\ntwo\n");
+            assert_eq!(written_with(LineEnding::Lf, text), "one\ntwo\n");
+            assert_eq!(written_with(LineEnding::CrLf, text), "one\r\ntwo\r\n");
+        }
+
+        #[test]
+        fn line_ending_crlf() {
+            let text = "one\r\ntwo\r\n";
+            assert_eq!(LineEnding::normalize(text), "one\ntwo\n");
+            assert_eq!(written_with(LineEnding::Preserve, text), "one\r\ntwo\r\n");
+            assert_eq!(written_with(LineEnding::Lf, text), "one\ntwo\n");
+            assert_eq!(written_with(LineEnding::CrLf, text), "one\r\ntwo\r\n");
+        }
+
+        #[test]
+        fn line_ending_cr() {
+            let text = "one\rtwo\r";
+            assert_eq!(LineEnding::normalize(text), "one\ntwo\n");
+            assert_eq!(written_with(LineEnding::Preserve, text), "one\rtwo\r");
+            assert_eq!(written_with(LineEnding::Lf, text), "one\ntwo\n");
+            assert_eq!(written_with(LineEnding::CrLf, text), "one\r\ntwo\r\n");
+        }
+
+        #[test]
+        fn line_ending_mixed() {
+            let text = "one\r\ntwo\rthree\nfour";
+            assert_eq!(LineEnding::normalize(text), "one\ntwo\nthree\nfour");
+            assert_eq!(written_with(LineEnding::Preserve, text), text);
+            assert_eq!(written_with(LineEnding::Lf, text), "one\ntwo\nthree\nfour");
+            assert_eq!(
+                written_with(LineEnding::CrLf, text),
+                "one\r\ntwo\r\nthree\r\nfour"
+            );
+        }
+
+        #[test]
+        fn enter_submits_single_line_editors() {
+            let (mut world, entity) = keyboard_world("hello");
+            world
+                .entity_mut(entity)
+                .insert(EditorCapabilities::all() - EditorCapabilities::NEWLINE);
+            let mut system = IntoSystem::into_system(listen_keyboard_input_events);
+            system.initialize(&mut world);
+
+            press(&mut world, Key::Enter);
+            system.run((), &mut world);
+            let submitted: Vec<_> = world
+                .resource_mut::<Events<Submit>>()
+                .drain()
+                .map(|submit| (submit.entity, submit.value))
+                .collect();
+            assert_eq!(submitted, vec![(entity, "hello".to_owned())]);
+            assert_eq!(text_value(&world, entity), "hello");
+            assert!(world.resource::<Events<TextChanged>>().is_empty());
+        }
 
-            transform.translation *= scale_factor;
-            transform.translation = transform.translation.round();
-            transform.translation *= inverse_scale_factor;
+        #[test]
+        fn emoji_are_edited_one_grapheme_at_a_time() {
+            let mut font_system = font_system();
+            // a flag (two regional indicators) and a family (three emoji joined by ZWJs)
+            let emoji = "a\u{1F1EF}\u{1F1F5}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}b";
+            let mut editor = new_editor(&mut font_system, emoji);
+            editor.set_cursor(Cursor::new(0, 0));
 
-            let selection_config = match selection_config {
-                Some(c) => *c,
-                None => Default::default(),
-            };
-            let color = selection_config.color.into();
+            let mut stops = Vec::new();
+            for _ in 0..4 {
+                apply_motion(&mut editor, &mut font_system, Motion::Right, false);
+                stops.push(editor.cursor().index);
+            }
+            assert_eq!(stops, vec![1, 9, 27, 28]);
 
-            for run in buffer.layout_runs() {
-                // TODO: this should happen in the main world so that we do as little work as possible here
-                if let Some((x, y, width)) =
-                    highlight_selection(editor_state.selection_bounds, buffer.size().0, &run)
-                {
-                    let position = Vec2::new(
-                        x as f32 + width as f32 / 2.0,
-                        y as f32 + run.line_height / 2.0,
-                    );
-                    extracted_uinodes.uinodes.insert(
-                        commands.spawn_empty().id(),
-                        ExtractedUiNode {
-                            stack_index: uinode.stack_index(),
-                            transform: transform
-                                * Mat4::from_translation(
-                                    position.extend(0.) * inverse_scale_factor,
-                                ),
-                            color,
-                            rect: Rect {
-                                min: Vec2::ZERO,
-                                // TODO: size?
-                                max: Vec2::new(width as f32, run.line_height),
-                            },
-                            image: AssetId::default(),
-                            atlas_size: None,
-                            clip: clip.map(|clip| clip.clip),
-                            flip_x: false,
-                            flip_y: false,
-                            camera_entity,
-                            border: [0.; 4],
-                            border_radius: [0.; 4],
-                            node_type: NodeType::Rect,
-                        },
-                    );
-                }
+            let mut remaining = Vec::new();
+            for _ in 0..4 {
+                backspace(&mut editor, &mut font_system);
+                remaining.push(text(&editor));
             }
+            assert_eq!(
+                remaining,
+                vec![
+                    "a\u{1F1EF}\u{1F1F5}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}",
+                    "a\u{1F1EF}\u{1F1F5}",
+                    "a",
+                    "",
+                ]
+            );
         }
-    }
 
-    // from cosmic-text/src/edit/editor.rs:66
-    pub fn cursor_position(cursor: &Cursor, run: &LayoutRun) -> Option<(i32, i32)> {
-        let (cursor_glyph, cursor_glyph_offset) = cursor_glyph_opt(cursor, run)?;
-        let x = match run.glyphs.get(cursor_glyph) {
-            Some(glyph) => {
-                // Start of detected glyph
-                if glyph.level.is_rtl() {
-                    (glyph.x + glyph.w - cursor_glyph_offset) as i32
-                } else {
-                    (glyph.x + cursor_glyph_offset) as i32
-                }
-            }
-            None => match run.glyphs.last() {
-                Some(glyph) => {
-                    // End of last glyph
-                    if glyph.level.is_rtl() {
-                        glyph.x as i32
-                    } else {
-                        (glyph.x + glyph.w) as i32
-                    }
-                }
-                None => {
-                    // Start of empty line
-                    0
-                }
-            },
-        };
+        #[test]
+        fn edit_at_cursors_moves_the_later_cursors() {
+            let mut font_system = font_system();
+            let mut editor = new_editor(&mut font_system, "ab cd ef");
+            editor.set_cursor(Cursor::new(0, 2));
+            let secondary = [Cursor::new(0, 5), Cursor::new(0, 0)];
 
-        Some((x, run.line_top as i32))
-    }
+            let secondary = edit_at_cursors(&mut editor, &secondary, |editor| {
+                editor.action(&mut font_system, Action::Insert('X'))
+            });
+            assert_eq!(text(&editor), "XabX cdX ef");
+            assert_eq!(editor.cursor(), Cursor::new(0, 4));
+            assert_eq!(secondary, vec![Cursor::new(0, 1), Cursor::new(0, 8)]);
+        }
 
-    // adapted from cosmic-text/src/edit/editor.rs:?
-    pub fn highlight_selection(
-        selection_bounds: Option<(Cursor, Cursor)>,
-        buffer_width: Option<f32>,
-        run: &LayoutRun,
-    ) -> Option<(i32, i32, u32)> {
-        let line_i = run.line_i;
-        let line_top = run.line_top;
+        #[test]
+        fn edit_at_cursors_across_lines() {
+            let mut font_system = font_system();
+            let mut editor = new_editor(&mut font_system, "ab\ncd");
+            editor.set_cursor(Cursor::new(1, 0));
 
-        // Highlight selection
-        if let Some((start, end)) = selection_bounds {
-            if line_i >= start.line && line_i <= end.line {
-                let mut range_opt = None;
-                for glyph in run.glyphs.iter() {
-                    // Guess x offset based on characters
-                    let cluster = &run.text[glyph.start..glyph.end];
-                    let total = cluster.grapheme_indices(true).count();
-                    let mut c_x = glyph.x;
-                    let c_w = glyph.w / total as f32;
-                    for (i, c) in cluster.grapheme_indices(true) {
-                        let c_start = glyph.start + i;
-                        let c_end = glyph.start + i + c.len();
-                        if (start.line != line_i || c_end > start.index)
-                            && (end.line != line_i || c_start < end.index)
-                        {
-                            range_opt = match range_opt.take() {
-                                Some((min, max)) => Some((
-                                    cmp::min(min, c_x as i32),
-                                    cmp::max(max, (c_x + c_w) as i32),
-                                )),
-                                None => Some((c_x as i32, (c_x + c_w) as i32)),
-                            };
-                        } else if let Some((min, max)) = range_opt.take() {
-                            return Some((min, line_top as i32, cmp::max(0, max - min) as u32));
-                        }
-                        c_x += c_w;
-                    }
-                }
+            // joining the lines moves the later cursor back onto the first line
+            let secondary = edit_at_cursors(&mut editor, &[Cursor::new(0, 1)], |editor| {
+                backspace(editor, &mut font_system)
+            });
+            assert_eq!(text(&editor), "bcd");
+            assert_eq!(editor.cursor(), Cursor::new(0, 1));
+            assert_eq!(secondary, vec![Cursor::new(0, 0)]);
+        }
 
-                if run.glyphs.is_empty() && end.line > line_i {
-                    // Highlight all of internal empty lines
-                    range_opt = Some((0, buffer_width.unwrap_or(0.0) as i32));
-                }
+        #[test]
+        fn edit_at_cursors_merges_cursors_that_meet() {
+            let mut font_system = font_system();
+            let mut editor = new_editor(&mut font_system, "abc");
+            editor.set_cursor(Cursor::new(0, 2));
 
-                if let Some((mut min, mut max)) = range_opt.take() {
-                    if end.line > line_i {
-                        // Draw to end of line
-                        if run.rtl {
-                            min = 0;
-                        } else {
-                            max = buffer_width.unwrap_or(0.0) as i32;
-                        }
-                    }
-                    return Some((min, line_top as i32, cmp::max(0, max - min) as u32));
-                }
-            }
+            let secondary = edit_at_cursors(&mut editor, &[Cursor::new(0, 1)], |editor| {
+                backspace(editor, &mut font_system)
+            });
+            assert_eq!(text(&editor), "c");
+            assert_eq!(editor.cursor(), Cursor::new(0, 0));
+            assert!(secondary.is_empty());
         }
-        None
-    }
 
-    // from cosmic-text/src/edit/editor.rs:30
-    pub fn cursor_glyph_opt(cursor: &Cursor, run: &LayoutRun) -> Option<(usize, f32)> {
-        if cursor.line == run.line_i {
-            for (glyph_i, glyph) in run.glyphs.iter().enumerate() {
-                if cursor.index == glyph.start {
-                    return Some((glyph_i, 0.0));
-                } else if cursor.index > glyph.start && cursor.index < glyph.end {
-                    // Guess x offset based on characters
-                    let mut before = 0;
-                    let mut total = 0;
+        #[test]
+        fn enter_at_every_cursor_respects_max_length() {
+            let (mut world, entity) = keyboard_world("ab");
+            world.entity_mut(entity).insert(MaxLength(Some(3)));
+            let mut system = IntoSystem::into_system(listen_keyboard_input_events);
+            system.initialize(&mut world);
 
-                    let cluster = &run.text[glyph.start..glyph.end];
-                    for (i, _) in cluster.grapheme_indices(true) {
-                        if glyph.start + i < cursor.index {
-                            before += 1;
-                        }
-                        total += 1;
-                    }
+            let (mut buffer, mut editor_state) = world
+                .query::<(&mut CosmicBuffer, &mut EditorState)>()
+                .single_mut(&mut world);
+            editor_state.set_cursor(&mut buffer, Cursor::new(0, 2));
+            editor_state.set_secondary_cursors(&buffer, [Cursor::new(0, 1)]);
 
-                    let offset = glyph.w * (before as f32) / (total as f32);
-                    return Some((glyph_i, offset));
-                }
-            }
-            match run.glyphs.last() {
-                Some(glyph) => {
-                    if cursor.index == glyph.end {
-                        return Some((run.glyphs.len(), 0.0));
-                    }
-                }
-                None => {
-                    return Some((0, 0.0));
-                }
-            }
+            press(&mut world, Key::Enter);
+            system.run((), &mut world);
+            // only one of the two new lines fits
+            assert_eq!(text_value(&world, entity).chars().count(), 3);
+            let rejected: Vec<_> = world
+                .resource_mut::<Events<InputRejected>>()
+                .drain()
+                .map(|rejected| rejected.reason)
+                .collect();
+            assert_eq!(rejected, vec![RejectReason::MaxLength]);
         }
-        None
-    }
 
-    #[derive(Component, Clone, Copy, Debug)]
-    pub struct EditorState {
-        pub cursor: Option<Cursor>,
-        pub selection: Selection,
-        pub selection_bounds: Option<(Cursor, Cursor)>,
-    }
+        #[test]
+        fn char_count_is_counted_like_max_length() {
+            // "e" and a combining acute accent are one grapheme but two chars
+            let (mut world, entity) = keyboard_world("e\u{301}\nb");
+            let mut system = IntoSystem::into_system(listen_keyboard_input_events);
+            system.initialize(&mut world);
 
-    impl Default for EditorState {
-        fn default() -> Self {
-            Self {
-                cursor: None,
-                selection: Selection::None,
-                selection_bounds: None,
-            }
+            let (mut buffer, mut editor_state) = world
+                .query::<(&mut CosmicBuffer, &mut EditorState)>()
+                .single_mut(&mut world);
+            let char_count = editor_state.char_count(&buffer);
+            assert_eq!(char_count, 4);
+            editor_state.set_cursor(&mut buffer, Cursor::new(1, 1));
+            world.entity_mut(entity).insert(MaxLength(Some(char_count)));
+
+            // the editor is already full
+            press(&mut world, Key::Character("X".into()));
+            system.run((), &mut world);
+            assert_eq!(text_value(&world, entity), "e\u{301}\nb");
         }
-    }
 
-    impl EditorState {
-        fn resume<'es, 'buf>(&'es mut self, buffer: &'buf mut Buffer) -> TempEditor<'es, 'buf> {
-            TempEditor::new(self, buffer)
+        #[test]
+        fn secondary_cursors_are_clamped_to_the_buffer() {
+            let mut buffer = cosmic_buffer(&mut font_system(), "ab\ncd");
+            let mut editor_state = EditorState::default();
+            editor_state.set_cursor(&mut buffer, Cursor::new(0, 0));
+
+            editor_state.set_secondary_cursors(
+                &buffer,
+                [Cursor::new(5, 3), Cursor::new(1, 9), Cursor::new(0, 0)],
+            );
+            assert_eq!(
+                editor_state.secondary_cursors().to_vec(),
+                vec![Cursor::new(1, 2)]
+            );
         }
-    }
 
-    pub struct TempEditor<'es, 'buf> {
-        editor: Editor<'buf>,
-        editor_state: &'es mut EditorState,
-    }
+        #[test]
+        fn matching_bracket_skips_nested_pairs() {
+            let buffer = buffer(&mut font_system(), "(a[b(c)d])");
+            assert_eq!(
+                matching_bracket(&buffer, Cursor::new(0, 0)),
+                Some(Cursor::new(0, 9))
+            );
+            assert_eq!(
+                matching_bracket(&buffer, Cursor::new(0, 9)),
+                Some(Cursor::new(0, 0))
+            );
+            assert_eq!(
+                matching_bracket(&buffer, Cursor::new(0, 2)),
+                Some(Cursor::new(0, 8))
+            );
+            assert_eq!(
+                matching_bracket(&buffer, Cursor::new(0, 6)),
+                Some(Cursor::new(0, 4))
+            );
+            // not a bracket
+            assert_eq!(matching_bracket(&buffer, Cursor::new(0, 1)), None);
+        }
 
-    impl<'es, 'buf> TempEditor<'es, 'buf> {
-        fn new(editor_state: &'es mut EditorState, buffer: &'buf mut Buffer) -> Self {
-            let mut editor = Editor::new(buffer);
-            if let Some(cursor) = editor_state.cursor {
-                editor.set_cursor(cursor);
-                editor.set_selection(editor_state.selection);
-            }
-            Self {
-                editor,
-                editor_state,
-            }
+        #[test]
+        fn matching_bracket_across_lines() {
+            let buffer = buffer(&mut font_system(), "fn f() {\n    (x)\n}");
+            assert_eq!(
+                matching_bracket(&buffer, Cursor::new(0, 7)),
+                Some(Cursor::new(2, 0))
+            );
+            assert_eq!(
+                matching_bracket(&buffer, Cursor::new(2, 0)),
+                Some(Cursor::new(0, 7))
+            );
         }
 
-        pub fn with_editor_mut(mut self, func: impl FnOnce(&mut Editor)) -> Self {
-            func(&mut self.editor);
-            self.editor_state.cursor = Some(self.editor.cursor());
-            self.editor_state.selection = self.editor.selection();
-            self.editor_state.selection_bounds = self.editor.selection_bounds();
-            self
+        #[test]
+        fn unmatched_brackets_have_no_match() {
+            let buffer = buffer(&mut font_system(), "(()");
+            assert_eq!(matching_bracket(&buffer, Cursor::new(0, 0)), None);
+            assert_eq!(
+                matching_bracket(&buffer, Cursor::new(0, 1)),
+                Some(Cursor::new(0, 2))
+            );
+
+            // other kinds of brackets don't close each other
+            let mismatched = buffer(&mut font_system(), "(]");
+            assert_eq!(matching_bracket(&mismatched, Cursor::new(0, 0)), None);
+            assert_eq!(matching_bracket(&mismatched, Cursor::new(0, 1)), None);
         }
-    }
 
-    #[derive(Component, Clone, Copy, Debug)]
-    pub struct CursorConfig {
-        pub color: Color,
-        pub width: f32,
-    }
+        #[test]
+        fn find_all_matches_dont_overlap() {
+            let buffer = buffer(&mut font_system(), "aaaaa");
+            assert_eq!(
+                find_all(&buffer, "aa", true),
+                vec![
+                    (Cursor::new(0, 0), Cursor::new(0, 2)),
+                    (Cursor::new(0, 2), Cursor::new(0, 4)),
+                ]
+            );
+        }
 
-    impl Default for CursorConfig {
-        fn default() -> Self {
-            Self {
-                color: Color::LinearRgba(LinearRgba::WHITE),
-                width: 1.0,
-            }
+        #[test]
+        fn find_all_across_lines() {
+            let buffer = buffer(&mut font_system(), "ab\ncd\nab\ncd");
+            let matches = vec![
+                (Cursor::new(0, 1), Cursor::new(1, 1)),
+                (Cursor::new(2, 1), Cursor::new(3, 1)),
+            ];
+            assert_eq!(find_all(&buffer, "b\nc", true), matches);
+            // the query's line endings match any line ending
+            assert_eq!(find_all(&buffer, "b\r\nc", true), matches);
         }
-    }
 
-    #[derive(Component, Clone, Copy, Debug)]
-    pub struct SelectionConfig {
-        pub color: Color,
-    }
+        #[test]
+        fn find_all_ignoring_case() {
+            let buffer = buffer(&mut font_system(), "Ab ab AB");
+            assert_eq!(find_all(&buffer, "ab", true).len(), 1);
+            assert_eq!(find_all(&buffer, "ab", false).len(), 3);
+            assert_eq!(find_all(&buffer, "", false), vec![]);
+        }
 
-    impl Default for SelectionConfig {
-        fn default() -> Self {
-            Self {
-                color: Color::LinearRgba(LinearRgba::BLACK),
-            }
+        #[test]
+        fn replace_all_across_lines_and_sections() {
+            let mut pipeline = TextPipeline::default();
+            *pipeline.font_system_mut() = font_system();
+            let mut buffer = rich_buffer(pipeline.font_system_mut(), &["ab\n", "ab\nab"]);
+            let mut text = Text::from_sections([
+                TextSection::new("ab\n", TextStyle::default()),
+                TextSection::new("ab\nab", TextStyle::default()),
+            ]);
+
+            let replaced = replace_all(&mut buffer, &mut text, &mut pipeline, "b\na", "XYZ", true);
+            assert_eq!(replaced, 2);
+            let value: String = text
+                .sections
+                .iter()
+                .map(|section| section.value.as_str())
+                .collect();
+            assert_eq!(value, "aXYZbXYZb");
+            let lines: Vec<&str> = buffer.lines.iter().map(|line| line.text()).collect();
+            assert_eq!(lines, vec!["aXYZbXYZb"]);
         }
-    }
 
-    // TODO: does not support multiple windows
-    #[derive(SystemParam)]
-    pub struct HitSystemParams<'w, 's> {
-        pub window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
-        pub buffers:
-            Query<'w, 's, (Entity, &'static CosmicBuffer, &'static GlobalTransform), With<Node>>,
-    }
+        #[test]
+        fn text_binding_round_trips() {
+            #[derive(Resource)]
+            struct Form {
+                name: String,
+            }
 
-    pub struct HitOutput {
-        pub entity: Entity,
-        pub span_index: usize,
-        pub position: Vec2,
-    }
+            let mut world = World::new();
+            world.init_resource::<Events<TextChanged>>();
+            world.insert_resource(Form {
+                name: "initial".into(),
+            });
+            let entity = world
+                .spawn((
+                    Text::from_section("", TextStyle::default()),
+                    TextBinding::<Form>::new(|form| &form.name, |form, name| form.name = name),
+                ))
+                .id();
+            let mut system = IntoSystem::into_system(sync_text_binding::<Form>);
+            system.initialize(&mut world);
+            let text_value = |world: &World| -> String {
+                let text = world.get::<Text>(entity).unwrap();
+                text.sections
+                    .iter()
+                    .map(|section| section.value.as_str())
+                    .collect()
+            };
 
-    /// Assumes only one entity gets hit (early returns)
-    #[allow(clippy::type_complexity)]
-    pub fn hit(params: HitSystemParams) -> Option<HitOutput> {
-        let window = params.window.single();
+            // the new binding takes the resource's value
+            system.run((), &mut world);
+            assert_eq!(text_value(&world), "initial");
 
-        let cursor_window_position = window.cursor_position()?;
+            // edits are written to the resource, and not written back
+            world.get_mut::<Text>(entity).unwrap().sections[0].value = "edited".into();
+            world.send_event(TextChanged { entity });
+            system.run((), &mut world);
+            assert_eq!(world.resource::<Form>().name, "edited");
+            assert_eq!(text_value(&world), "edited");
 
-        for (entity, buffer, transform) in &params.buffers {
-            let size = buffer.size();
-            let size = Vec2::new(
-                size.0.expect("Buffer has a width"),
-                size.1.expect("Buffer has a height"),
-            );
-            let origin = transform.translation().truncate();
-            let rect = Rect::from_center_size(origin, size);
-            if rect.contains(cursor_window_position) {
-                // top left corner of buffer (where +Y down, +X right)
-                // TODO: slightly off for some reason, unsure if cosmic-text or this is wrong
-                let offset = origin - size / 2.0;
-                // position in buffer
-                let position = cursor_window_position - offset;
-                // TODO: fix the issue where this always registers a hit on the first span if no other is hit
-                if let Some(text_cursor) = buffer.hit(position.x, position.y) {
-                    // get attrs from cursor
-                    let line = &buffer.lines[text_cursor.line];
-                    let attrs = line.attrs_list().get_span(text_cursor.index);
-                    let span_index = attrs.metadata;
-                    // notify only the relevant child
-                    return Some(HitOutput {
-                        entity,
-                        span_index,
-                        position,
-                    });
-                }
-            }
+            // changes to the resource replace the text
+            world.resource_mut::<Form>().name = "reset".into();
+            system.run((), &mut world);
+            assert_eq!(text_value(&world), "reset");
+
+            // and nothing changes until either does
+            system.run((), &mut world);
+            assert_eq!(world.resource::<Form>().name, "reset");
+            assert_eq!(text_value(&world), "reset");
         }
 
-        None
+        #[test]
+        fn snapshot_restores_the_text_and_selection() {
+            let mut pipeline = TextPipeline::default();
+            *pipeline.font_system_mut() = font_system();
+            let style = |font_size| TextStyle {
+                font_size,
+                ..default()
+            };
+            let mut buffer = rich_buffer(pipeline.font_system_mut(), &["ab\n", "cd"]);
+            let text = Text::from_sections([
+                TextSection::new("ab\n", style(10.0)),
+                TextSection::new("cd", style(20.0)),
+            ]);
+            let mut editor_state = EditorState::default();
+            editor_state.set_selection(&mut buffer, Cursor::new(0, 1), Cursor::new(1, 1));
+            let snapshot = editor_state.snapshot(&text);
+
+            let mut restored_buffer = cosmic_buffer(pipeline.font_system_mut(), "x");
+            let mut restored_text = Text::from_section("x", style(30.0));
+            let mut restored_state = EditorState::default();
+            restored_state.restore(
+                &snapshot,
+                &mut restored_buffer,
+                &mut restored_text,
+                &mut pipeline,
+            );
+
+            let sections: Vec<(&str, f32)> = restored_text
+                .sections
+                .iter()
+                .map(|section| (section.value.as_str(), section.style.font_size))
+                .collect();
+            // the sections without a style of their own take the last style
+            assert_eq!(sections, vec![("ab\n", 30.0), ("cd", 30.0)]);
+            assert_eq!(restored_state.snapshot(&restored_text), snapshot);
+        }
+
+        #[test]
+        fn snapshot_restore_clamps_the_cursor_and_selection() {
+            let mut pipeline = TextPipeline::default();
+            *pipeline.font_system_mut() = font_system();
+            let mut buffer = cosmic_buffer(pipeline.font_system_mut(), "");
+            let mut text = Text::from_section("", TextStyle::default());
+            let mut editor_state = EditorState::default();
+            // e.g. a snapshot that was edited, or from an older version
+            let snapshot = EditorSnapshot {
+                sections: vec!["ab\ncd".into()],
+                cursor: Some((1, 9)),
+                selection_bounds: Some(((0, 1), (1, 9))),
+            };
+
+            editor_state.restore(&snapshot, &mut buffer, &mut text, &mut pipeline);
+            let restored = editor_state.snapshot(&text);
+            assert_eq!(restored.sections, vec!["ab\ncd".to_string()]);
+            assert_eq!(restored.cursor, Some((1, 2)));
+            assert_eq!(restored.selection_bounds, Some(((0, 1), (1, 2))));
+
+            let past_the_end = EditorSnapshot {
+                cursor: Some((5, 0)),
+                selection_bounds: None,
+                ..snapshot
+            };
+            editor_state.restore(&past_the_end, &mut buffer, &mut text, &mut pipeline);
+            assert_eq!(editor_state.snapshot(&text).cursor, Some((1, 2)));
+        }
     }
 }